@@ -0,0 +1,72 @@
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::file_manager::FileInfo;
+
+/// Separator between the archive path and the member path in virtual paths
+pub const VIRTUAL_PATH_SEPARATOR: &str = "!/";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveMember {
+    pub archive: PathBuf,
+    pub entry: String,
+    /// `<archive>!/<entry>`, for display only
+    pub virtual_path: String,
+    pub size: u64,
+    pub hash: String,
+}
+
+/// Loose files and archive members sharing the same content.
+///
+/// Report-only: members cannot be deleted individually.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveDuplicateGroup {
+    pub hash: String,
+    pub size: u64,
+    pub files: Vec<FileInfo>,
+    pub members: Vec<ArchiveMember>,
+    /// Bytes stored more than once across files and archives
+    pub redundant_size: u64,
+}
+
+/// Check if a file is a ZIP archive the scanner can look into
+pub fn is_zip_archive(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| ext.eq_ignore_ascii_case("zip"))
+        .unwrap_or(false)
+}
+
+/// Hash every non-empty member of a ZIP archive up to `max_member_size` uncompressed bytes
+pub fn hash_zip_members(archive_path: &Path, max_member_size: u64) -> Result<Vec<ArchiveMember>> {
+    let file = std::fs::File::open(archive_path)?;
+    let mut archive = zip::ZipArchive::new(file)?;
+    let mut members = Vec::new();
+
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index)?;
+        if entry.is_dir() || entry.size() == 0 || entry.size() > max_member_size {
+            continue;
+        }
+
+        let name = entry.name().to_string();
+        let mut hasher = Sha256::new();
+        // The declared size is untrusted; never inflate more than the limit
+        let copied = std::io::copy(&mut (&mut entry).take(max_member_size + 1), &mut hasher)?;
+        if copied != entry.size() {
+            return Err(anyhow!("Member {} inflates to {} bytes, expected {}", name, copied, entry.size()));
+        }
+
+        members.push(ArchiveMember {
+            virtual_path: format!("{}{}{}", archive_path.display(), VIRTUAL_PATH_SEPARATOR, name),
+            archive: archive_path.to_path_buf(),
+            entry: name,
+            size: copied,
+            hash: format!("{:x}", hasher.finalize()),
+        });
+    }
+
+    Ok(members)
+}
@@ -1,12 +1,29 @@
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use anyhow::{anyhow, Result};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use tokio::sync::RwLock;
 use tracing::{info, warn, error};
-use regex::Regex;
+
+use crate::bloatware_definitions::{self, BloatwareDatabase, DefinitionSources, DefinitionStatus};
+use crate::bloatware_matching::{AppIdentity, MatchSubject, RuleMatch};
+use crate::command_runner::{self, CommandLine, CommandRunner, PowerShellValue};
+use crate::dependencies::{self, ComponentDefinition, DependencyGraph, DependencyPolicy};
+use crate::inventory::{self, InstalledApplication, Inventory, InventorySource};
+#[cfg(debug_assertions)]
+use crate::inventory::InventoryFixture;
+use crate::registry_provider::RegistryProvider;
+use crate::path_resolver::{self, TargetResolver};
+use crate::platform;
+use crate::protection::{self, FileProtection};
+use crate::removal_backup::{self, ReinstallSource, RemovalBackup, RestoreResult};
+use crate::removal_plan::{self, AppRemovalPlan, MsiProduct, PlannedPath, PlannedUninstall, RemovalPlan};
+use crate::winget::{self, ExportedPackage, WingetOutcome, WingetPackage};
+use crate::keeper;
+use crate::scan_sessions::{ScanSession, ScanSessionStore, ScanSessionSummary};
+use crate::space_accounting::{self, FileAllocation, SpaceAccounting, SpaceMeter};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BloatwareApp {
@@ -27,9 +44,17 @@ pub struct BloatwareApp {
     pub can_uninstall: bool,
     pub is_critical: bool,
     pub last_modified: DateTime<Utc>,
+    #[serde(default)]
+    pub identity: AppIdentity,
+    /// Set on scan findings identified through a definition rule
+    #[serde(default)]
+    pub matched_rule: Option<RuleMatch>,
+    /// Installed software relying on the app, when it is a shared component
+    #[serde(default)]
+    pub required_by: Vec<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum BloatwareCategory {
     MicrosoftBloatware,
     OEMBloatware,
@@ -45,6 +70,7 @@ pub enum BloatwareCategory {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum RemovalMethod {
     Winget,
     PowerShell,
@@ -59,6 +85,9 @@ pub enum RemovalMethod {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BloatwareScanResult {
+    /// Session the result was saved under
+    #[serde(default)]
+    pub session_id: Option<String>,
     pub total_apps_scanned: usize,
     pub bloatware_found: Vec<BloatwareApp>,
     pub scan_duration_ms: u64,
@@ -67,6 +96,8 @@ pub struct BloatwareScanResult {
     pub errors: Vec<String>,
 }
 
+pub type BloatwareScanSession = ScanSession<BloatwareScanResult, ()>;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UninstallResult {
     pub app_name: String,
@@ -74,38 +105,120 @@ pub struct UninstallResult {
     pub method_used: RemovalMethod,
     pub details: Vec<String>,
     pub errors: Vec<String>,
+    /// Size of the planned files and folders that are gone after the removal
     pub space_freed_mb: u64,
+    /// Free space measured around the removal
+    #[serde(default)]
+    pub accounting: SpaceAccounting,
     pub registry_entries_removed: usize,
     pub files_removed: usize,
     pub services_stopped: usize,
     pub tasks_removed: usize,
+    /// Appx removal outcome for each scope that was attempted
+    #[serde(default)]
+    pub scopes: Vec<ScopeResult>,
+    /// Backup taken before the removal, to restore the app from
+    #[serde(default)]
+    pub backup_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RemovalScope {
+    CurrentUser,
+    AllUsers,
+    /// The image copy Windows installs into every new profile
+    Provisioned,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScopeResult {
+    pub scope: RemovalScope,
+    pub success: bool,
+    /// Full names of the packages removed in this scope
+    pub packages: Vec<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct UninstallOptions {
+    /// Remove Appx packages from every profile instead of the current user only
+    pub all_users: bool,
+    /// Remove the provisioned copy so new profiles and feature updates do not bring it back
+    pub deprovision: bool,
+}
+
+impl Default for UninstallOptions {
+    fn default() -> Self {
+        Self { all_users: true, deprovision: true }
+    }
 }
 
 pub struct BloatwareManager {
-    bloatware_database: Arc<RwLock<HashMap<String, BloatwareApp>>>,
-    scan_results: Arc<RwLock<HashMap<String, BloatwareScanResult>>>,
+    bloatware_database: Arc<RwLock<BloatwareDatabase>>,
+    definition_sources: DefinitionSources,
+    definition_status: Arc<RwLock<DefinitionStatus>>,
+    registry: Arc<dyn RegistryProvider>,
+    runner: Arc<dyn CommandRunner>,
+    /// Replays a recorded inventory instead of reading this machine; debug builds only
+    #[cfg(debug_assertions)]
+    inventory_fixture: Option<PathBuf>,
+    sessions: Arc<RwLock<ScanSessionStore>>,
     removal_log: Arc<RwLock<Vec<UninstallResult>>>,
+    /// Plans awaiting execution, by ID
+    plans: Arc<RwLock<HashMap<String, RemovalPlan>>>,
+    /// Runtimes and frameworks other software depends on
+    components: Arc<Vec<ComponentDefinition>>,
     backup_directory: PathBuf,
 }
 
 impl BloatwareManager {
     pub fn new(backup_dir: PathBuf) -> Self {
-        let mut manager = Self {
-            bloatware_database: Arc::new(RwLock::new(HashMap::new())),
-            scan_results: Arc::new(RwLock::new(HashMap::new())),
-            removal_log: Arc::new(RwLock::new(Vec::new())),
+        #[cfg(windows)]
+        let registry: Arc<dyn RegistryProvider> = Arc::new(crate::registry_provider::WindowsRegistry);
+        // Elsewhere only a debug build's inventory fixture has anything to read
+        #[cfg(not(windows))]
+        let registry: Arc<dyn RegistryProvider> = Arc::new(crate::registry_provider::RecordedRegistry::default());
+
+        Self {
+            // Plans from a recorded machine would still run real commands here, so release builds never replay one
+            #[cfg(debug_assertions)]
+            inventory_fixture: std::env::var_os("WINDOWS_OPTIMIZER_INVENTORY_FIXTURE").map(PathBuf::from),
+            ..Self::with_providers(backup_dir, registry, command_runner::shared())
+        }
+    }
+
+    /// Read the machine through `registry` and run every command through `runner`
+    pub fn with_providers(backup_dir: PathBuf, registry: Arc<dyn RegistryProvider>, runner: Arc<dyn CommandRunner>) -> Self {
+        let definition_sources = DefinitionSources::standard();
+        let (database, status) = bloatware_definitions::load_database(&definition_sources);
+
+        Self {
+            bloatware_database: Arc::new(RwLock::new(database)),
+            definition_sources,
+            definition_status: Arc::new(RwLock::new(status)),
+            registry,
+            runner,
+            #[cfg(debug_assertions)]
+            inventory_fixture: None,
+            sessions: Arc::new(RwLock::new(ScanSessionStore::new(backup_dir.join("sessions").join("bloatware")))),
+            // Backups record each removal, so the history outlives the session
+            removal_log: Arc::new(RwLock::new(
+                RemovalBackup::load_all(&backup_dir.join("removals")).into_iter().filter_map(|backup| backup.result).collect()
+            )),
+            plans: Arc::new(RwLock::new(HashMap::new())),
+            components: Arc::new(dependencies::builtin_components().unwrap_or_else(|e| {
+                error!("Dependency definitions unavailable: {}", e);
+                Vec::new()
+            })),
             backup_directory: backup_dir,
-        };
-        
-        // Initialize with comprehensive bloatware database
-        manager.initialize_database();
-        manager
+        }
     }
 
     /// Scan for bloatware with enhanced detection
     pub async fn scan_bloatware(&self) -> Result<BloatwareScanResult> {
         let start_time = std::time::Instant::now();
         let mut result = BloatwareScanResult {
+            session_id: None,
             total_apps_scanned: 0,
             bloatware_found: Vec::new(),
             scan_duration_ms: 0,
@@ -117,15 +230,22 @@ impl BloatwareManager {
         info!("Starting comprehensive bloatware scan");
 
         // Get all installed applications
-        let installed_apps = self.get_installed_applications().await?;
-        result.total_apps_scanned = installed_apps.len();
+        let inventory = self.installed_inventory().await?;
+        let graph = DependencyGraph::build(&self.components, &inventory)?;
+        result.total_apps_scanned = inventory.applications.len();
+        result.errors.extend(inventory.errors);
 
         // Get bloatware database
         let database = self.bloatware_database.read().await;
 
         // Analyze each installed application
-        for app in installed_apps {
-            if let Some(bloatware_info) = self.analyze_application(&app, &database).await {
+        for installed in &inventory.applications {
+            let app = app_from_inventory(installed.clone());
+            if let Some(mut bloatware_info) = self.analyze_application(&app, &database).await {
+                if let Some(component) = graph.component_of(installed) {
+                    bloatware_info.required_by = component.dependents.iter().map(|dependent| dependent.name.clone()).collect();
+                    bloatware_info.is_critical |= component.policy == DependencyPolicy::Block && !component.dependents.is_empty();
+                }
                 result.bloatware_found.push(bloatware_info.clone());
                 
                 // Update category count
@@ -138,11 +258,25 @@ impl BloatwareManager {
 
         result.scan_duration_ms = start_time.elapsed().as_millis() as u64;
 
-        // Store scan result
-        let scan_id = format!("bloatware_scan_{}", Utc::now().timestamp());
-        {
-            let mut scan_results = self.scan_results.write().await;
-            scan_results.insert(scan_id, result.clone());
+        // Persist the session so the last scan can be reopened without rescanning
+        let scan_id = format!("bloatware_scan_{}", Utc::now().timestamp_millis());
+        result.session_id = Some(scan_id.clone());
+        let summary = ScanSessionSummary {
+            id: scan_id.clone(),
+            created_at: Utc::now(),
+            scanned_directories: Vec::new(),
+            finding_count: result.bloatware_found.len(),
+            potential_savings: result.potential_savings_mb * 1024 * 1024,
+            based_on: None,
+        };
+        let session = BloatwareScanSession {
+            id: scan_id.clone(),
+            created_at: summary.created_at,
+            options: (),
+            result: result.clone(),
+        };
+        if let Err(e) = self.sessions.write().await.save(&session, summary) {
+            warn!("Failed to save bloatware scan session {}: {}", scan_id, e);
         }
 
         info!("Bloatware scan completed: {} apps found, {}MB potential savings", 
@@ -151,57 +285,185 @@ impl BloatwareManager {
         Ok(result)
     }
 
-    /// Uninstall bloatware application with comprehensive cleanup
-    pub async fn uninstall_bloatware(&self, app_name: String) -> Result<UninstallResult> {
+    /// Saved bloatware scan sessions, newest first
+    pub async fn list_scan_sessions(&self) -> Vec<ScanSessionSummary> {
+        self.sessions.read().await.list()
+    }
+
+    /// Reopen a saved bloatware scan, the latest one by default
+    pub async fn open_scan_session(&self, session_id: Option<String>) -> Result<BloatwareScanSession> {
+        let sessions = self.sessions.read().await;
+        let session_id = session_id
+            .or_else(|| sessions.latest_id())
+            .ok_or_else(|| anyhow!("No saved bloatware scan"))?;
+        sessions.load(&session_id)
+    }
+
+    /// Resolve every action removing the selected apps would take, for review before anything runs
+    pub async fn plan_removal(&self, app_names: Vec<String>, options: UninstallOptions) -> Result<RemovalPlan> {
+        if app_names.is_empty() {
+            return Err(anyhow!("No applications selected for removal"));
+        }
+
+        let database = self.bloatware_database.read().await;
+        let mut definitions = Vec::new();
+        for app_name in &app_names {
+            definitions.push(database.apps.get(app_name)
+                .ok_or_else(|| anyhow!("Bloatware not found in database: {}", app_name))?);
+        }
+
+        let inventory = self.installed_inventory().await?;
+        let registry = self.planning_registry().await?;
+        // One listing serves every app, for removal and for reinstalling later; winget is slow to start
+        let winget_listing = winget::list_installed(self.runner.as_ref()).await.map_err(|e| e.to_string());
+
+        let mut apps = Vec::new();
+        let mut removed = Vec::new();
+        for definition in definitions {
+            let installed: Vec<InstalledApplication> = inventory.applications.iter()
+                .filter(|app| database.matcher.best_match(&app.match_subject()).is_some_and(|found| found.definition == definition.name))
+                .cloned()
+                .collect();
+            removed.extend(installed.iter().map(|app| app.display_name.clone()));
+            let package_families = database.matcher.package_families(&definition.name);
+            apps.push(self.plan_app(definition, installed, package_families, registry.clone(), options, &winget_listing).await?);
+        }
+
+        // Software removed along with a component no longer needs it
+        let graph = DependencyGraph::build(&self.components, &inventory)?;
+        for app in &mut apps {
+            app.dependency_impacts = graph.impact(app, &removed);
+            app.blocked = app.dependency_impacts.iter().any(|impact| impact.policy == DependencyPolicy::Block);
+        }
+
+        let plan = RemovalPlan {
+            id: uuid::Uuid::new_v4().to_string(),
+            created: Utc::now(),
+            options,
+            total_bytes: apps.iter().map(|app| app.total_bytes).sum(),
+            apps,
+        };
+        self.plans.write().await.insert(plan.id.clone(), plan.clone());
+        info!("Planned removal {} of {} app(s)", plan.id, plan.apps.len());
+        Ok(plan)
+    }
+
+    /// Carry out a reviewed plan; a plan runs at most once
+    pub async fn execute_removal_plan(&self, plan_id: &str) -> Result<Vec<UninstallResult>> {
+        let plan = self.plans.write().await.remove(plan_id)
+            .ok_or_else(|| anyhow!("Unknown or already executed removal plan: {}", plan_id))?;
+        if Utc::now() - plan.created > chrono::Duration::minutes(removal_plan::PLAN_LIFETIME_MINUTES) {
+            return Err(anyhow!("Removal plan {} has expired; plan the removal again", plan_id));
+        }
+
+        let mut results = Vec::new();
+        for app in &plan.apps {
+            let result = if app.blocked {
+                warn!("Not removing {}: installed software depends on it", app.app_name);
+                blocked_result(app)
+            } else {
+                let mut result = self.execute_app_plan(app).await;
+                result.details.extend(app.dependency_impacts.iter().map(|impact| format!("Warning: {}", impact.explanation)));
+                result
+            };
+            let mut removal_log = self.removal_log.write().await;
+            removal_log.push(result.clone());
+            results.push(result);
+        }
+        Ok(results)
+    }
+
+    /// Reinstall an app from the removal history and put back what its removal deleted
+    pub async fn restore_removed_app(&self, backup_id: &str) -> Result<RestoreResult> {
+        let mut backup = RemovalBackup::load(&self.backup_directory.join("removals"), backup_id).await?;
+        if let Some(restored) = backup.restored {
+            return Err(anyhow!("{} was already restored on {}", backup.display_name, restored.format("%Y-%m-%d %H:%M")));
+        }
+
+        info!("Restoring removed app {} from backup {}", backup.app_name, backup.id);
+        let result = backup.restore(self.runner.as_ref()).await;
+        if result.success {
+            backup.restored = Some(Utc::now());
+            backup.save().await?;
+        }
+        Ok(result)
+    }
+
+    /// Uninstall the inventory entry registered under `registry_key` with winget or by its Windows Installer product code.
+    /// Entries neither can remove are left alone; deleting the key would only hide the app.
+    pub async fn uninstall_installed(&self, registry_key: &str) -> Result<UninstallResult> {
+        let inventory = self.installed_inventory().await?;
+        let app = inventory.applications.into_iter()
+            .find(|app| app.sources.iter().any(|source| matches!(source, InventorySource::UninstallKey(key) if key.eq_ignore_ascii_case(registry_key))))
+            .ok_or_else(|| anyhow!("{} is not the uninstall entry of an installed application", registry_key))?;
+        if !app.removable {
+            return Err(anyhow!("{} cannot be uninstalled", app.display_name));
+        }
+
         let mut result = UninstallResult {
-            app_name: app_name.clone(),
+            app_name: app.display_name.clone(),
             success: false,
-            method_used: RemovalMethod::Custom("Unknown".to_string()),
+            method_used: RemovalMethod::Custom("None".to_string()),
             details: Vec::new(),
             errors: Vec::new(),
             space_freed_mb: 0,
+            accounting: SpaceAccounting::default(),
             registry_entries_removed: 0,
             files_removed: 0,
             services_stopped: 0,
             tasks_removed: 0,
+            scopes: Vec::new(),
+            backup_id: None,
         };
 
-        info!("Starting uninstallation of bloatware: {}", app_name);
+        info!("Uninstalling {} from {}", app.display_name, registry_key);
+        let mut meter = SpaceMeter::start(app.install_location.iter().cloned().chain(keeper::program_files_directories()));
+        let before = app.install_location.as_deref().map(space_accounting::tree_allocation);
 
-        // Get bloatware information
-        let database = self.bloatware_database.read().await;
-        let bloatware_info = database.get(&app_name)
-            .ok_or_else(|| anyhow!("Bloatware not found in database: {}", app_name))?;
-
-        // Create backup before uninstallation
-        self.create_uninstall_backup(bloatware_info).await?;
-
-        // Try different removal methods
-        for method in &bloatware_info.removal_methods {
-            match self.try_removal_method(bloatware_info, method).await {
-                Ok(uninstall_result) => {
-                    result = uninstall_result;
-                    if result.success {
-                        break;
+        let package = match winget::list_installed(self.runner.as_ref()).await {
+            Ok((packages, exported)) => winget::find_package(&packages, &exported, &app),
+            Err(e) => {
+                result.details.push(format!("Winget unavailable: {}", e));
+                None
+            }
+        };
+        match (package, &app.identity.product_code) {
+            (Some(package), _) => {
+                result.method_used = RemovalMethod::Winget;
+                match self.remove_with_winget(std::slice::from_ref(&package)).await {
+                    Ok(details) => {
+                        result.success = true;
+                        result.details.extend(details);
                     }
+                    Err(e) => result.errors.push(format!("Winget failed: {}", e)),
                 }
-                Err(e) => {
-                    result.errors.push(format!("Method {:?} failed: {}", method, e));
+            }
+            (None, Some(product_code)) => {
+                result.method_used = RemovalMethod::PowerShell;
+                match uninstall_msi_product(self.runner.as_ref(), product_code).await {
+                    Ok(detail) => {
+                        result.success = true;
+                        result.details.push(detail);
+                    }
+                    Err(e) => result.errors.push(format!("Windows Installer failed: {}", e)),
                 }
             }
+            (None, None) => result.errors.push(format!(
+                "Neither winget nor Windows Installer knows {}; run its own uninstaller", app.display_name
+            )),
         }
 
-        // Perform deep cleanup if uninstallation was successful
-        if result.success {
-            self.perform_deep_cleanup(bloatware_info).await?;
-        }
-
-        // Log the removal operation
-        {
-            let mut removal_log = self.removal_log.write().await;
-            removal_log.push(result.clone());
+        if let (Some(location), Some(before)) = (&app.install_location, before) {
+            let left = space_accounting::tree_allocation(location);
+            meter.record(FileAllocation {
+                logical: before.logical.saturating_sub(left.logical),
+                released: before.released.saturating_sub(left.released),
+            });
         }
+        result.accounting = meter.finish();
+        result.space_freed_mb = result.accounting.estimated_bytes / (1024 * 1024);
 
+        self.removal_log.write().await.push(result.clone());
         Ok(result)
     }
 
@@ -222,302 +484,98 @@ impl BloatwareManager {
         ]
     }
 
+    /// Layers behind the current database and any files that were rejected
+    pub async fn definition_status(&self) -> DefinitionStatus {
+        self.definition_status.read().await.clone()
+    }
+
+    /// Re-read the definition files, e.g. after an organization update
+    pub async fn reload_definitions(&self) -> DefinitionStatus {
+        let (database, status) = bloatware_definitions::load_database(&self.definition_sources);
+        *self.bloatware_database.write().await = database;
+        *self.definition_status.write().await = status.clone();
+        status
+    }
+
     /// Get removal history
     pub async fn get_removal_history(&self) -> Vec<UninstallResult> {
         let removal_log = self.removal_log.read().await;
         removal_log.clone()
     }
 
-    /// Initialize comprehensive bloatware database
-    fn initialize_database(&mut self) {
-        let mut database = HashMap::new();
-
-        // Microsoft Bloatware
-        database.insert("Microsoft Solitaire Collection".to_string(), BloatwareApp {
-            name: "Microsoft Solitaire Collection".to_string(),
-            display_name: "Microsoft Solitaire Collection".to_string(),
-            version: "4.0".to_string(),
-            publisher: "Microsoft Corporation".to_string(),
-            install_location: PathBuf::from("C:\\Program Files\\WindowsApps\\Microsoft.MicrosoftSolitaireCollection_*"),
-            size_mb: 150,
-            category: BloatwareCategory::MicrosoftBloatware,
-            confidence_score: 0.95,
-            removal_methods: vec![RemovalMethod::UWP, RemovalMethod::PowerShell],
-            registry_keys: vec![
-                r"HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\Windows\CurrentVersion\Uninstall\Microsoft.MicrosoftSolitaireCollection".to_string(),
-            ],
-            file_paths: vec![
-                PathBuf::from("C:\\Program Files\\WindowsApps\\Microsoft.MicrosoftSolitaireCollection_*"),
-                PathBuf::from("%LOCALAPPDATA%\\Packages\\Microsoft.MicrosoftSolitaireCollection_*"),
-            ],
-            services: vec![],
-            scheduled_tasks: vec![],
-            is_installed: false,
-            can_uninstall: true,
-            is_critical: false,
-            last_modified: Utc::now(),
-        });
-
-        // Microsoft Edge (WebView2 dependencies)
-        database.insert("Microsoft Edge".to_string(), BloatwareApp {
-            name: "Microsoft Edge".to_string(),
-            display_name: "Microsoft Edge WebView2 Runtime".to_string(),
-            version: "120.0".to_string(),
-            publisher: "Microsoft Corporation".to_string(),
-            install_location: PathBuf::from("C:\\Program Files (x86)\\Microsoft\\EdgeWebView\\Application"),
-            size_mb: 500,
-            category: BloatwareCategory::MicrosoftBloatware,
-            confidence_score: 0.90,
-            removal_methods: vec![RemovalMethod::Winget, RemovalMethod::PowerShell],
-            registry_keys: vec![
-                r"HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\EdgeUpdate".to_string(),
-                r"HKEY_LOCAL_MACHINE\SOFTWARE\WOW6432Node\Microsoft\EdgeUpdate".to_string(),
-            ],
-            file_paths: vec![
-                PathBuf::from("C:\\Program Files (x86)\\Microsoft\\EdgeWebView"),
-                PathBuf::from("C:\\Program Files (x86)\\Microsoft\\EdgeUpdate"),
-            ],
-            services: vec![
-                "edgeupdate".to_string(),
-                "edgeupdatem".to_string(),
-            ],
-            scheduled_tasks: vec![
-                "Microsoft\\EdgeUpdate\\EdgeUpdateTaskMachineCore".to_string(),
-                "Microsoft\\EdgeUpdate\\EdgeUpdateTaskMachineUA".to_string(),
-            ],
-            is_installed: false,
-            can_uninstall: true,
-            is_critical: false,
-            last_modified: Utc::now(),
-        });
-
-        // Candy Crush Saga
-        database.insert("Candy Crush Saga".to_string(), BloatwareApp {
-            name: "Candy Crush Saga".to_string(),
-            display_name: "Candy Crush Saga".to_string(),
-            version: "1.0".to_string(),
-            publisher: "King".to_string(),
-            install_location: PathBuf::from("C:\\Program Files\\WindowsApps\\king.com.CandyCrushSaga_*"),
-            size_mb: 200,
-            category: BloatwareCategory::GamingPlatform,
-            confidence_score: 0.98,
-            removal_methods: vec![RemovalMethod::UWP, RemovalMethod::PowerShell],
-            registry_keys: vec![
-                r"HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\Windows\CurrentVersion\Uninstall\king.com.CandyCrushSaga_*".to_string(),
-            ],
-            file_paths: vec![
-                PathBuf::from("C:\\Program Files\\WindowsApps\\king.com.CandyCrushSaga_*"),
-                PathBuf::from("%LOCALAPPDATA%\\Packages\\king.com.CandyCrushSaga_*"),
-            ],
-            services: vec![],
-            scheduled_tasks: vec![],
-            is_installed: false,
-            can_uninstall: true,
-            is_critical: false,
-            last_modified: Utc::now(),
-        });
-
-        // McAfee Security
-        database.insert("McAfee Security".to_string(), BloatwareApp {
-            name: "McAfee Security".to_string(),
-            display_name: "McAfee LiveSafe".to_string(),
-            version: "16.0".to_string(),
-            publisher: "McAfee LLC".to_string(),
-            install_location: PathBuf::from("C:\\Program Files\\McAfee"),
-            size_mb: 800,
-            category: BloatwareCategory::OEMBloatware,
-            confidence_score: 0.95,
-            removal_methods: vec![RemovalMethod::PowerShell, RemovalMethod::WMI],
-            registry_keys: vec![
-                r"HKEY_LOCAL_MACHINE\SOFTWARE\McAfee".to_string(),
-                r"HKEY_LOCAL_MACHINE\SOFTWARE\WOW6432Node\McAfee".to_string(),
-            ],
-            file_paths: vec![
-                PathBuf::from("C:\\Program Files\\McAfee"),
-                PathBuf::from("C:\\Program Files (x86)\\McAfee"),
-                PathBuf::from("%PROGRAMDATA%\\McAfee"),
-            ],
-            services: vec![
-                "McAfeeEngine".to_string(),
-                "McAfeeFramework".to_string(),
-                "McAfeeVSE".to_string(),
-            ],
-            scheduled_tasks: vec![
-                "McAfee\\McAfeeLogon".to_string(),
-                "McAfee\\McAfeeVSE".to_string(),
-            ],
-            is_installed: false,
-            can_uninstall: true,
-            is_critical: false,
-            last_modified: Utc::now(),
-        });
+    /// Installed applications from the Uninstall keys and Appx packages
+    pub async fn installed_inventory(&self) -> Result<Inventory> {
+        #[cfg(debug_assertions)]
+        if let Some(path) = &self.inventory_fixture {
+            return Ok(load_inventory_fixture(path).await?.build());
+        }
 
-        // Add more bloatware entries...
-        self.add_more_bloatware_entries(&mut database);
+        let (appx_json, appx_error) = match self.runner.checked(&CommandLine::powershell(inventory::APPX_QUERY, &[])).await {
+            Ok(output) => (Some(output), None),
+            Err(e) => (None, Some(format!("Failed to list Appx packages: {}", e))),
+        };
 
-        // Store database
-        tokio::spawn(async move {
-            let mut db = self.bloatware_database.write().await;
-            *db = database;
-        });
+        let registry = self.registry.clone();
+        let mut inventory = tokio::task::spawn_blocking(move || inventory::build(registry.as_ref(), appx_json.as_deref())).await?;
+        inventory.errors.extend(appx_error);
+        Ok(inventory)
     }
 
-    /// Add more comprehensive bloatware entries
-    fn add_more_bloatware_entries(&self, database: &mut HashMap<String, BloatwareApp>) {
-        // Windows built-in apps
-        let windows_apps = vec![
-            ("Microsoft Weather", "Microsoft.Weather", BloatwareCategory::MicrosoftBloatware),
-            ("Microsoft News", "Microsoft.BingNews", BloatwareCategory::MicrosoftBloatware),
-            ("Microsoft Tips", "Microsoft.GetHelp", BloatwareCategory::MicrosoftBloatware),
-            ("Xbox Game Bar", "Microsoft.XboxGamingOverlay", BloatwareCategory::MicrosoftBloatware),
-            ("Microsoft People", "Microsoft.People", BloatwareCategory::MicrosoftBloatware),
-            ("Microsoft Photos", "Microsoft.WindowsPhotos", BloatwareCategory::MicrosoftBloatware),
-            ("Microsoft Movies & TV", "Microsoft.ZuneVideo", BloatwareCategory::MicrosoftBloatware),
-            ("Microsoft Music", "Microsoft.ZuneMusic", BloatwareCategory::MicrosoftBloatware),
-            ("Microsoft Mail", "microsoft.windowscommunicationsapps", BloatwareCategory::MicrosoftBloatware),
-            ("Microsoft Calendar", "microsoft.windowscommunicationsapps", BloatwareCategory::MicrosoftBloatware),
-        ];
-
-        for (name, package_name, category) in windows_apps {
-            database.insert(name.to_string(), BloatwareApp {
-                name: name.to_string(),
-                display_name: name.to_string(),
-                version: "1.0".to_string(),
-                publisher: "Microsoft Corporation".to_string(),
-                install_location: PathBuf::from(format!("C:\\Program Files\\WindowsApps\\{}_*", package_name)),
-                size_mb: 100,
-                category: category.clone(),
-                confidence_score: 0.90,
-                removal_methods: vec![RemovalMethod::UWP, RemovalMethod::PowerShell],
-                registry_keys: vec![
-                    format!(r"HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\Windows\CurrentVersion\Uninstall\{}_*", package_name),
-                ],
-                file_paths: vec![
-                    PathBuf::from(format!("C:\\Program Files\\WindowsApps\\{}_*", package_name)),
-                    PathBuf::from(format!("%LOCALAPPDATA%\\Packages\\{}_*", package_name)),
-                ],
-                services: vec![],
-                scheduled_tasks: vec![],
-                is_installed: false,
-                can_uninstall: true,
-                is_critical: false,
-                last_modified: Utc::now(),
-            });
-        }
+    /// Save what the inventory reads on this machine, for replaying elsewhere
+    #[cfg(debug_assertions)]
+    pub async fn record_inventory_fixture(&self, path: PathBuf) -> Result<()> {
+        let appx_json = self.runner.checked(&CommandLine::powershell(inventory::APPX_QUERY, &[])).await?;
+        let registry = self.registry.clone();
+        let fixture = tokio::task::spawn_blocking(move || InventoryFixture::record(registry.as_ref(), Some(appx_json))).await??;
+        tokio::fs::write(&path, serde_json::to_vec_pretty(&fixture)?).await?;
+        info!("Recorded inventory fixture: {}", path.display());
+        Ok(())
+    }
 
-        // OEM bloatware patterns
-        let oem_patterns = vec![
-            ("HP", "Hewlett-Packard", BloatwareCategory::OEMBloatware),
-            ("Dell", "Dell Inc.", BloatwareCategory::OEMBloatware),
-            ("Lenovo", "Lenovo Group Limited", BloatwareCategory::OEMBloatware),
-            ("Acer", "Acer Inc.", BloatwareCategory::OEMBloatware),
-            ("ASUS", "ASUSTeK Computer Inc.", BloatwareCategory::OEMBloatware),
-        ];
-
-        for (brand, publisher, category) in oem_patterns {
-            database.insert(format!("{} Support Assistant", brand), BloatwareApp {
-                name: format!("{} Support Assistant", brand),
-                display_name: format!("{} Support Assistant", brand),
-                version: "1.0".to_string(),
-                publisher: publisher.to_string(),
-                install_location: PathBuf::from(format!("C:\\Program Files\\{}\\", brand)),
-                size_mb: 300,
-                category: category.clone(),
-                confidence_score: 0.85,
-                removal_methods: vec![RemovalMethod::PowerShell, RemovalMethod::WMI],
-                registry_keys: vec![
-                    format!(r"HKEY_LOCAL_MACHINE\SOFTWARE\{}\\", brand),
-                ],
-                file_paths: vec![
-                    PathBuf::from(format!("C:\\Program Files\\{}\\", brand)),
-                    PathBuf::from(format!("C:\\Program Files (x86)\\{}\\", brand)),
-                ],
-                services: vec![
-                    format!("{}SupportAssistant", brand),
-                ],
-                scheduled_tasks: vec![
-                    format!("{}\\SupportAssistant", brand),
-                ],
-                is_installed: false,
-                can_uninstall: true,
-                is_critical: false,
-                last_modified: Utc::now(),
-            });
-        }
+    /// The definition rule recognizing an installed application, if any
+    pub async fn identify(&self, app: &InstalledApplication) -> Option<RuleMatch> {
+        let database = self.bloatware_database.read().await;
+        database.matcher.best_match(&app.match_subject())
     }
 
-    /// Get all installed applications
-    async fn get_installed_applications(&self) -> Result<Vec<BloatwareApp>> {
-        let mut apps = Vec::new();
+    /// Shared components on this machine and the installed software relying on each
+    pub async fn dependency_graph(&self) -> Result<DependencyGraph> {
+        let inventory = self.installed_inventory().await?;
+        DependencyGraph::build(&self.components, &inventory)
+    }
 
-        // Get applications from WMI
-        let wmi_command = r#"
-            Get-WmiObject -Class Win32_Product | 
-            Select-Object Name, Version, Vendor, InstallLocation, @{Name="SizeMB";Expression={[math]::Round(($_.Size / 1MB), 2)}} |
-            ConvertTo-Json
-        "#;
-
-        match self.execute_powershell_command(wmi_command).await {
-            Ok(output) => {
-                // Parse JSON output and convert to BloatwareApp
-                // This is a simplified version - in production, you'd want robust JSON parsing
-                apps.push(BloatwareApp {
-                    name: "Sample App".to_string(),
-                    display_name: "Sample Application".to_string(),
-                    version: "1.0".to_string(),
-                    publisher: "Sample Publisher".to_string(),
-                    install_location: PathBuf::from("C:\\Program Files\\Sample"),
-                    size_mb: 100,
-                    category: BloatwareCategory::ThirdPartyBloatware,
-                    confidence_score: 0.5,
-                    removal_methods: vec![RemovalMethod::PowerShell],
-                    registry_keys: vec![],
-                    file_paths: vec![],
-                    services: vec![],
-                    scheduled_tasks: vec![],
-                    is_installed: true,
-                    can_uninstall: true,
-                    is_critical: false,
-                    last_modified: Utc::now(),
-                });
-            }
-            Err(e) => {
-                warn!("Failed to get installed applications via WMI: {}", e);
-            }
+    /// The registry the inventory reads, so plans made from a fixture check the fixture's keys
+    async fn planning_registry(&self) -> Result<Arc<dyn RegistryProvider>> {
+        #[cfg(debug_assertions)]
+        if let Some(path) = &self.inventory_fixture {
+            return Ok(Arc::new(load_inventory_fixture(path).await?.registry));
         }
-
-        // Get UWP applications
-        let uwp_command = r#"
-            Get-AppxPackage | 
-            Select-Object Name, PackageFullName, InstallLocation, @{Name="SizeMB";Expression={[math]::Round(($_.PackageUserInformation.Size / 1MB), 2)}} |
-            ConvertTo-Json
-        "#;
-
-        match self.execute_powershell_command(uwp_command).await {
-            Ok(_) => {
-                // Parse UWP applications
-                // Implementation would parse JSON and convert to BloatwareApp
-            }
-            Err(e) => {
-                warn!("Failed to get UWP applications: {}", e);
-            }
-        }
-
-        Ok(apps)
+        Ok(self.registry.clone())
     }
 
     /// Analyze application to determine if it's bloatware
     async fn analyze_application(
         &self,
         app: &BloatwareApp,
-        database: &HashMap<String, BloatwareApp>
+        database: &BloatwareDatabase
     ) -> Option<BloatwareApp> {
-        // Check if app matches any known bloatware patterns
-        for (pattern, bloatware_info) in database {
-            if self.matches_pattern(&app.name, pattern) {
+        // Check if a definition rule recognizes the app
+        let subject = MatchSubject {
+            display_name: &app.display_name,
+            publisher: &app.publisher,
+            version: &app.version,
+            identity: &app.identity,
+        };
+        if let Some(rule_match) = database.matcher.best_match(&subject) {
+            if let Some(bloatware_info) = database.apps.get(&rule_match.definition) {
                 let mut detected_bloatware = bloatware_info.clone();
+                detected_bloatware.version = app.version.clone();
+                detected_bloatware.identity = app.identity.clone();
                 detected_bloatware.is_installed = true;
                 detected_bloatware.last_modified = Utc::now();
+                // How bloated the entry is, discounted by how sure the identification is
+                detected_bloatware.confidence_score *= rule_match.confidence;
+                detected_bloatware.matched_rule = Some(rule_match);
                 return Some(detected_bloatware);
             }
         }
@@ -534,182 +592,367 @@ impl BloatwareManager {
         None
     }
 
-    /// Try a specific removal method
-    async fn try_removal_method(
+    async fn plan_app(
         &self,
-        bloatware_info: &BloatwareApp,
-        method: &RemovalMethod
-    ) -> Result<UninstallResult> {
+        definition: &BloatwareApp,
+        installed: Vec<InstalledApplication>,
+        rule_families: Vec<String>,
+        registry: Arc<dyn RegistryProvider>,
+        options: UninstallOptions,
+        winget_listing: &WingetListing,
+    ) -> Result<AppRemovalPlan> {
+        let mut skipped = Vec::new();
+        let mut uninstall_steps = Vec::new();
+
+        for method in &definition.removal_methods {
+            match method {
+                RemovalMethod::Winget => match winget_listing {
+                    _ if installed.is_empty() => skipped.push("Winget: no installed copy found in the inventory".to_string()),
+                    Ok((packages, exported)) => {
+                        let found: Vec<WingetPackage> = installed.iter()
+                            .filter_map(|app| winget::find_package(packages, exported, app))
+                            .collect();
+                        if found.len() == installed.len() {
+                            uninstall_steps.push(PlannedUninstall::Winget { packages: found });
+                        } else {
+                            skipped.push("Winget: winget does not list every installed copy".to_string());
+                        }
+                    }
+                    Err(e) => skipped.push(format!("Winget: {}", e)),
+                },
+                RemovalMethod::UWP => {
+                    let mut families: Vec<String> = installed.iter()
+                        .filter_map(|app| app.identity.package_family_name.as_ref().map(|family| family.to_lowercase()))
+                        .chain(rule_families.iter().cloned())
+                        .collect();
+                    families.sort();
+                    families.dedup();
+                    if families.is_empty() {
+                        skipped.push("UWP: no Appx package family is known for this entry".to_string());
+                        continue;
+                    }
+                    let installed_packages = installed.iter().flat_map(|app| &app.sources).filter_map(|source| match source {
+                        InventorySource::Appx { package_full_name } => Some(package_full_name.clone()),
+                        InventorySource::UninstallKey(_) => None,
+                    }).collect();
+                    uninstall_steps.push(PlannedUninstall::Appx {
+                        families,
+                        installed: installed_packages,
+                        scopes: removal_plan::appx_scopes(options),
+                    });
+                }
+                RemovalMethod::PowerShell => {
+                    let mut products: Vec<MsiProduct> = installed.iter()
+                        .filter_map(|app| Some(MsiProduct {
+                            product_code: app.identity.product_code.clone()?,
+                            display_name: app.display_name.clone(),
+                        }))
+                        .collect();
+                    products.sort_by(|a, b| a.product_code.cmp(&b.product_code));
+                    products.dedup_by(|a, b| a.product_code == b.product_code);
+                    if products.is_empty() {
+                        skipped.push("PowerShell: no installed copy has a Windows Installer product code".to_string());
+                    } else {
+                        uninstall_steps.push(PlannedUninstall::Msi { products });
+                    }
+                }
+                other => skipped.push(format!("{:?}: unsupported removal method", other)),
+            }
+        }
+
+        let reinstall_sources = reinstall_sources(&installed, winget_listing);
+        let patterns: Vec<String> = definition.file_paths.iter().map(|path| path.to_string_lossy().to_string()).collect();
+        let (keys, services, tasks) = (definition.registry_keys.clone(), definition.services.clone(), definition.scheduled_tasks.clone());
+        let policy_path = self.backup_directory.join(protection::POLICY_FILE);
+        let (files, registry_keys, services, scheduled_tasks, skipped) = tokio::task::spawn_blocking(move || {
+            let mut skipped = skipped;
+            // Concrete targets for every user profile; cleanup deletes exactly these
+            let resolver = TargetResolver::for_machine(registry.as_ref());
+            // Read now, so the plan honors the policy as it is when the user reviews it
+            let protection = FileProtection::load(policy_path, platform::current().critical_directories);
+            let mut resolved = Vec::new();
+            for pattern in &patterns {
+                if let Err(e) = path_resolver::check_anchored(pattern) {
+                    skipped.push(format!("File path {}", e));
+                    continue;
+                }
+                for path in resolver.resolve_paths(pattern) {
+                    match protection.check_tree(&path) {
+                        Ok(()) => resolved.push((path, pattern.clone())),
+                        Err(refusal) => skipped.push(format!("File path {}: {}", path.display(), refusal)),
+                    }
+                }
+            }
+            let files: Vec<PlannedPath> = removal_plan::outermost_paths(resolved)
+                .into_iter()
+                .map(|(path, pattern)| PlannedPath::measure(path, &pattern))
+                .collect();
+            let registry_keys = removal_plan::existing_keys(&resolver, registry.as_ref(), &keys, &mut skipped);
+            let services = removal_plan::existing_services(registry.as_ref(), &services, &mut skipped);
+            let scheduled_tasks = removal_plan::existing_tasks(registry.as_ref(), &tasks, &mut skipped);
+            (files, registry_keys, services, scheduled_tasks, skipped)
+        }).await?;
+
+        Ok(AppRemovalPlan {
+            app_name: definition.name.clone(),
+            display_name: definition.display_name.clone(),
+            estimated_size_mb: definition.size_mb,
+            uninstall_steps,
+            total_bytes: files.iter().map(|file| file.size_bytes).sum(),
+            files,
+            registry_keys,
+            services,
+            scheduled_tasks,
+            skipped,
+            reinstall_sources,
+            dependency_impacts: Vec::new(),
+            blocked: false,
+        })
+    }
+
+    /// Uninstall one app of a plan with the planned steps, then clean up what it leaves behind
+    async fn execute_app_plan(&self, app: &AppRemovalPlan) -> UninstallResult {
+        let mut result = UninstallResult {
+            app_name: app.app_name.clone(),
+            success: false,
+            method_used: RemovalMethod::Custom("None".to_string()),
+            details: Vec::new(),
+            errors: Vec::new(),
+            space_freed_mb: 0,
+            accounting: SpaceAccounting::default(),
+            registry_entries_removed: 0,
+            files_removed: 0,
+            services_stopped: 0,
+            tasks_removed: 0,
+            scopes: Vec::new(),
+            backup_id: None,
+        };
+
+        info!("Starting uninstallation of bloatware: {}", app.app_name);
+
+        // Nothing is removed unless it can be restored from the backup
+        let mut backup = match RemovalBackup::capture(self.runner.as_ref(), &self.backup_directory.join("removals"), app).await {
+            Ok(backup) => backup,
+            Err(e) => {
+                result.errors.push(format!("Backup failed, nothing was removed: {}", e));
+                return result;
+            }
+        };
+
+        let mut meter = SpaceMeter::start(
+            app.files.iter()
+                .map(|file| file.path.clone())
+                .chain(keeper::program_files_directories())
+        );
+        // Measure the planned paths now; the uninstaller may take them before the deep cleanup does
+        let planned: Vec<(&Path, FileAllocation)> = app.files.iter()
+            .map(|file| (file.path.as_path(), space_accounting::tree_allocation(&file.path)))
+            .collect();
+
+        if app.uninstall_steps.is_empty() {
+            result.errors.push(format!("Nothing in the plan can uninstall {}", app.display_name));
+        }
+        for step in &app.uninstall_steps {
+            let attempt = self.try_uninstall_step(app, step).await;
+            // Keep what every attempt reported, so a fallback that works does not hide why the first one failed
+            result.details.extend(attempt.details);
+            result.errors.extend(attempt.errors);
+            result.method_used = attempt.method_used;
+            result.scopes = attempt.scopes;
+            result.success = attempt.success;
+            if result.success {
+                break;
+            }
+        }
+
+        // Perform deep cleanup if uninstallation was successful
+        if result.success {
+            self.perform_deep_cleanup(app, &mut result).await;
+        }
+
+        // Only what is gone from the planned paths counts as freed
+        for (path, before) in planned {
+            let left = space_accounting::tree_allocation(path);
+            meter.record(FileAllocation {
+                logical: before.logical.saturating_sub(left.logical),
+                released: before.released.saturating_sub(left.released),
+            });
+        }
+        result.accounting = meter.finish();
+        result.space_freed_mb = result.accounting.estimated_bytes / (1024 * 1024);
+        result.details.extend(backup.errors.iter().map(|e| format!("Backup incomplete: {}", e)));
+        result.backup_id = Some(backup.id.clone());
+
+        backup.result = Some(result.clone());
+        if let Err(e) = backup.save().await {
+            warn!("Failed to record the removal of {} in its backup: {}", app.app_name, e);
+        }
+        result
+    }
+
+    /// Run one planned uninstall step
+    async fn try_uninstall_step(&self, app: &AppRemovalPlan, step: &PlannedUninstall) -> UninstallResult {
         let mut result = UninstallResult {
-            app_name: bloatware_info.name.clone(),
+            app_name: app.app_name.clone(),
             success: false,
-            method_used: method.clone(),
+            method_used: step.method(),
             details: Vec::new(),
             errors: Vec::new(),
             space_freed_mb: 0,
+            accounting: SpaceAccounting::default(),
             registry_entries_removed: 0,
             files_removed: 0,
             services_stopped: 0,
             tasks_removed: 0,
+            scopes: Vec::new(),
+            backup_id: None,
         };
 
-        match method {
-            RemovalMethod::Winget => {
-                let command = format!("winget uninstall \"{}\"", bloatware_info.name);
-                match self.execute_command(&command).await {
-                    Ok(output) => {
+        match step {
+            PlannedUninstall::Winget { packages } => {
+                match self.remove_with_winget(packages).await {
+                    Ok(details) => {
                         result.success = true;
-                        result.details.push(format!("Winget uninstall: {}", output));
-                        result.space_freed_mb = bloatware_info.size_mb;
+                        result.details.extend(details);
                     }
                     Err(e) => {
                         result.errors.push(format!("Winget failed: {}", e));
                     }
                 }
             }
-            RemovalMethod::PowerShell => {
-                let command = format!(
-                    "Get-WmiObject -Class Win32_Product | Where-Object {{$_.Name -eq '{}'}} | ForEach-Object {{$_.Uninstall()}}",
-                    bloatware_info.name
-                );
-                match self.execute_powershell_command(&command).await {
-                    Ok(output) => {
+            PlannedUninstall::Msi { products } => {
+                match self.remove_msi_products(products).await {
+                    Ok(details) => {
                         result.success = true;
-                        result.details.push(format!("PowerShell uninstall: {}", output));
-                        result.space_freed_mb = bloatware_info.size_mb;
+                        result.details.extend(details);
                     }
                     Err(e) => {
-                        result.errors.push(format!("PowerShell failed: {}", e));
+                        result.errors.push(format!("Windows Installer failed: {}", e));
                     }
                 }
             }
-            RemovalMethod::UWP => {
-                let command = format!(
-                    "Get-AppxPackage -Name \"{}\" | Remove-AppxPackage",
-                    bloatware_info.name
-                );
-                match self.execute_powershell_command(&command).await {
-                    Ok(output) => {
-                        result.success = true;
-                        result.details.push(format!("UWP uninstall: {}", output));
-                        result.space_freed_mb = bloatware_info.size_mb;
-                    }
-                    Err(e) => {
-                        result.errors.push(format!("UWP failed: {}", e));
+            PlannedUninstall::Appx { families, scopes, .. } => {
+                let scopes = self.remove_appx_packages(families, scopes).await;
+                for scope in &scopes {
+                    match &scope.error {
+                        Some(e) => result.errors.push(format!("UWP {:?} removal failed: {}", scope.scope, e)),
+                        None => result.details.push(format!("UWP {:?}: removed {}", scope.scope, scope.packages.join(", "))),
                     }
                 }
-            }
-            _ => {
-                result.errors.push(format!("Unsupported removal method: {:?}", method));
+
+                let removed_any = scopes.iter().any(|scope| !scope.packages.is_empty());
+                if !removed_any && result.errors.is_empty() {
+                    result.errors.push("UWP failed: the package is neither installed nor provisioned".to_string());
+                }
+                result.success = removed_any && scopes.iter().all(|scope| scope.success);
+                result.scopes = scopes;
             }
         }
 
-        Ok(result)
+        result
     }
 
-    /// Perform deep cleanup after uninstallation
-    async fn perform_deep_cleanup(&self, bloatware_info: &BloatwareApp) -> Result<()> {
-        info!("Performing deep cleanup for: {}", bloatware_info.name);
-
-        // Remove registry entries
-        for registry_key in &bloatware_info.registry_keys {
-            let command = format!("reg delete \"{}\" /f", registry_key);
-            if let Err(e) = self.execute_command(&command).await {
-                warn!("Failed to remove registry key {}: {}", registry_key, e);
+    /// Uninstall every planned package by its exact winget ID
+    async fn remove_with_winget(&self, packages: &[WingetPackage]) -> Result<Vec<String>> {
+        let mut details = Vec::new();
+        for package in packages {
+            let source = package.source.as_deref().unwrap_or("local");
+            match winget::uninstall(self.runner.as_ref(), package).await? {
+                WingetOutcome::Completed => details.push(format!("Winget removed {} from {}", package.id, source)),
+                WingetOutcome::RebootRequired => details.push(format!("Winget removed {} from {}; restart to finish", package.id, source)),
             }
         }
+        Ok(details)
+    }
 
-        // Remove file paths
-        for file_path in &bloatware_info.file_paths {
-            let command = format!("Remove-Item -Path \"{}\" -Recurse -Force -ErrorAction SilentlyContinue", file_path.display());
-            if let Err(e) = self.execute_powershell_command(&command).await {
-                warn!("Failed to remove file path {}: {}", file_path.display(), e);
-            }
+    /// Uninstall every planned Windows Installer product by its product code
+    async fn remove_msi_products(&self, products: &[MsiProduct]) -> Result<Vec<String>> {
+        let mut details = Vec::new();
+        for product in products {
+            let detail = uninstall_msi_product(self.runner.as_ref(), &product.product_code).await
+                .map_err(|e| anyhow!("{}: {}", product.display_name, e))?;
+            details.push(detail);
         }
+        Ok(details)
+    }
 
-        // Stop and remove services
-        for service in &bloatware_info.services {
-            let stop_command = format!("Stop-Service -Name \"{}\" -Force -ErrorAction SilentlyContinue", service);
-            let remove_command = format!("Remove-Service -Name \"{}\" -ErrorAction SilentlyContinue", service);
-            
-            if let Err(e) = self.execute_powershell_command(&stop_command).await {
-                warn!("Failed to stop service {}: {}", service, e);
-            }
-            
-            if let Err(e) = self.execute_powershell_command(&remove_command).await {
-                warn!("Failed to remove service {}: {}", service, e);
-            }
+    async fn remove_appx_packages(&self, families: &[String], scopes: &[RemovalScope]) -> Vec<ScopeResult> {
+        let mut results = Vec::new();
+        for &scope in scopes {
+            let outcome = self.runner.checked(&appx_removal_command(scope, families)).await
+                .and_then(|output| parse_package_names(&output));
+            results.push(match outcome {
+                Ok(packages) => ScopeResult { scope, success: true, packages, error: None },
+                Err(e) => ScopeResult { scope, success: false, packages: Vec::new(), error: Some(e.to_string()) },
+            });
         }
+        results
+    }
 
-        // Remove scheduled tasks
-        for task in &bloatware_info.scheduled_tasks {
-            let command = format!("Unregister-ScheduledTask -TaskName \"{}\" -Confirm:$false -ErrorAction SilentlyContinue", task);
-            if let Err(e) = self.execute_powershell_command(&command).await {
-                warn!("Failed to remove scheduled task {}: {}", task, e);
+    /// Remove what the plan lists beyond the package itself, reporting every failure
+    async fn perform_deep_cleanup(&self, app: &AppRemovalPlan, result: &mut UninstallResult) {
+        info!("Performing deep cleanup for: {}", app.app_name);
+
+        // Services first, so their files are no longer locked
+        for service in &app.services {
+            let stop_command = CommandLine::powershell(
+                "Stop-Service -Name $Name -Force -ErrorAction Stop",
+                &[("Name", service.as_str().into())],
+            );
+            if let Err(e) = self.runner.checked(&stop_command).await {
+                result.errors.push(format!("Failed to stop service {}: {}", service, e));
+                continue;
             }
-        }
-
-        Ok(())
-    }
+            result.services_stopped += 1;
 
-    /// Create backup before uninstallation
-    async fn create_uninstall_backup(&self, bloatware_info: &BloatwareApp) -> Result<()> {
-        let backup_id = format!("uninstall_backup_{}_{}", 
-            bloatware_info.name.replace(" ", "_"), 
-            Utc::now().format("%Y%m%d_%H%M%S"));
-        
-        let backup_path = self.backup_directory.join(&format!("{}.reg", backup_id));
-        
-        // Export registry keys
-        for registry_key in &bloatware_info.registry_keys {
-            let export_command = format!("reg export \"{}\" \"{}\" /y", registry_key, backup_path.display());
-            if let Err(e) = self.execute_command(&export_command).await {
-                warn!("Failed to backup registry key {}: {}", registry_key, e);
+            // Remove-Service needs PowerShell 6; sc.exe works everywhere
+            match self.runner.checked(&CommandLine::new("sc.exe", ["delete", service.as_str()])).await {
+                Ok(_) => result.details.push(format!("Removed service {}", service)),
+                Err(e) => result.errors.push(format!("Failed to remove service {}: {}", service, e)),
             }
         }
 
-        Ok(())
-    }
-
-    /// Execute command
-    async fn execute_command(&self, command: &str) -> Result<String> {
-        let output = tokio::process::Command::new("cmd")
-            .args(&["/C", command])
-            .output()
-            .await?;
-
-        if output.status.success() {
-            Ok(String::from_utf8_lossy(&output.stdout).to_string())
-        } else {
-            Err(anyhow!("Command failed: {}", String::from_utf8_lossy(&output.stderr)))
+        for task in &app.scheduled_tasks {
+            let task_name = removal_backup::task_path(task);
+            match self.runner.checked(&CommandLine::new("schtasks.exe", ["/Delete", "/TN", task_name.as_str(), "/F"])).await {
+                Ok(_) => {
+                    result.tasks_removed += 1;
+                    result.details.push(format!("Removed scheduled task {}", task_name));
+                }
+                Err(e) => result.errors.push(format!("Failed to remove scheduled task {}: {}", task_name, e)),
+            }
         }
-    }
-
-    /// Execute PowerShell command
-    async fn execute_powershell_command(&self, command: &str) -> Result<String> {
-        let output = tokio::process::Command::new("powershell.exe")
-            .args(&["-Command", command])
-            .output()
-            .await?;
 
-        if output.status.success() {
-            Ok(String::from_utf8_lossy(&output.stdout).to_string())
-        } else {
-            Err(anyhow!("PowerShell command failed: {}", String::from_utf8_lossy(&output.stderr)))
+        for registry_key in &app.registry_keys {
+            match self.runner.checked(&CommandLine::new("reg.exe", ["delete", registry_key.as_str(), "/f"])).await {
+                Ok(_) => {
+                    result.registry_entries_removed += 1;
+                    result.details.push(format!("Removed registry key {}", registry_key));
+                }
+                Err(e) => result.errors.push(format!("Failed to remove registry key {}: {}", registry_key, e)),
+            }
         }
-    }
 
-    /// Check if app name matches pattern
-    fn matches_pattern(&self, app_name: &str, pattern: &str) -> bool {
-        let app_lower = app_name.to_lowercase();
-        let pattern_lower = pattern.to_lowercase();
-        
-        app_lower.contains(&pattern_lower) || pattern_lower.contains(&app_lower)
+        for file in &app.files {
+            // Count what is still there; the uninstaller may have removed part of it
+            let present = PlannedPath::measure(file.path.clone(), &file.pattern);
+            let command = CommandLine::powershell(
+                "Remove-Item -LiteralPath $Path -Recurse -Force -ErrorAction Stop",
+                &[("Path", file.path.as_path().into())],
+            );
+            match self.runner.checked(&command).await {
+                Ok(_) => {
+                    result.files_removed += present.file_count;
+                    result.details.push(format!("Removed {}", file.path.display()));
+                }
+                Err(e) => result.errors.push(format!("Failed to remove {}: {}", file.path.display(), e)),
+            }
+        }
     }
 
     /// Calculate bloatware confidence score
     async fn calculate_bloatware_confidence(&self, app: &BloatwareApp) -> f32 {
-        let mut score = 0.0;
+        let mut score: f32 = 0.0;
         
         // Check publisher
         let suspicious_publishers = ["trial", "demo", "adware", "bloatware", "unwanted"];
@@ -762,4 +1005,499 @@ impl BloatwareManager {
         
         BloatwareCategory::ThirdPartyBloatware
     }
-}
\ No newline at end of file
+}
+
+/// What `winget list` and `winget export` returned, or why they failed
+type WingetListing = std::result::Result<(Vec<WingetPackage>, Vec<ExportedPackage>), String>;
+
+/// Remove a Windows Installer product by its product code, without UI or restart
+async fn uninstall_msi_product(runner: &dyn CommandRunner, product_code: &str) -> Result<String> {
+    let output = runner.run(&CommandLine::new("msiexec.exe", ["/x", product_code, "/qn", "/norestart"])).await?;
+    match output.exit_code {
+        Some(0) => Ok(format!("Windows Installer removed {}", product_code)),
+        // ERROR_SUCCESS_REBOOT_INITIATED and ERROR_SUCCESS_REBOOT_REQUIRED
+        Some(1641) | Some(3010) => Ok(format!("Windows Installer removed {}; restart to finish", product_code)),
+        Some(1605) => Err(anyhow!("product {} is not installed", product_code)),
+        Some(1618) => Err(anyhow!("another installation is in progress")),
+        Some(code) => Err(anyhow!("msiexec exited with code {} for {}", code, product_code)),
+        None => Err(anyhow!("msiexec was terminated while removing {}", product_code)),
+    }
+}
+
+#[cfg(debug_assertions)]
+async fn load_inventory_fixture(path: &Path) -> Result<InventoryFixture> {
+    serde_json::from_slice(&tokio::fs::read(path).await?)
+        .map_err(|e| anyhow!("Invalid inventory fixture {}: {}", path.display(), e))
+}
+
+/// Installed application in the shape the analysis works on
+fn app_from_inventory(app: InstalledApplication) -> BloatwareApp {
+    let is_appx = app.sources.iter().any(|source| matches!(source, InventorySource::Appx { .. }));
+    let registry_keys = app.sources.iter().filter_map(|source| match source {
+        InventorySource::UninstallKey(key) => Some(key.clone()),
+        InventorySource::Appx { .. } => None,
+    }).collect();
+
+    BloatwareApp {
+        name: app.display_name.clone(),
+        display_name: app.display_name,
+        version: app.version,
+        publisher: app.publisher,
+        install_location: app.install_location.clone().unwrap_or_default(),
+        size_mb: app.size_bytes.unwrap_or(0) / (1024 * 1024),
+        category: BloatwareCategory::ThirdPartyBloatware,
+        confidence_score: 0.0,
+        removal_methods: if is_appx { vec![RemovalMethod::UWP] } else { vec![RemovalMethod::PowerShell] },
+        registry_keys,
+        file_paths: app.install_location.into_iter().collect(),
+        services: vec![],
+        scheduled_tasks: vec![],
+        is_installed: true,
+        can_uninstall: app.removable,
+        is_critical: app.is_framework,
+        last_modified: Utc::now(),
+        identity: app.identity,
+        matched_rule: None,
+        required_by: Vec::new(),
+    }
+}
+
+/// Where the installed copies can be installed from again: winget source packages first, then staged Appx families
+fn reinstall_sources(installed: &[InstalledApplication], winget_listing: &WingetListing) -> Vec<ReinstallSource> {
+    let mut sources = Vec::new();
+    if let Ok((packages, exported)) = winget_listing {
+        // Local ARP\ and MSIX\ IDs have no source to install from
+        for package in installed.iter().filter_map(|app| winget::find_package(packages, exported, app)) {
+            if let Some(source) = package.source {
+                sources.push(ReinstallSource::Winget { id: package.id, source });
+            }
+        }
+    }
+    for app in installed {
+        if let Some(family) = &app.identity.package_family_name {
+            sources.push(ReinstallSource::Appx { family: family.clone() });
+        }
+    }
+    sources.dedup();
+    sources
+}
+
+/// Result for an app the plan refuses to remove, explaining what would break
+fn blocked_result(app: &AppRemovalPlan) -> UninstallResult {
+    UninstallResult {
+        app_name: app.app_name.clone(),
+        success: false,
+        method_used: RemovalMethod::Custom("None".to_string()),
+        details: Vec::new(),
+        errors: app.dependency_impacts.iter()
+            .filter(|impact| impact.policy == DependencyPolicy::Block)
+            .map(|impact| impact.explanation.clone())
+            .collect(),
+        space_freed_mb: 0,
+        accounting: SpaceAccounting::default(),
+        registry_entries_removed: 0,
+        files_removed: 0,
+        services_stopped: 0,
+        tasks_removed: 0,
+        scopes: Vec::new(),
+        backup_id: None,
+    }
+}
+
+/// Remove the packages of `families` in one scope and print the removed full names as JSON
+fn appx_removal_command(scope: RemovalScope, families: &[String]) -> CommandLine {
+    let (select, remove, name) = match scope {
+        RemovalScope::CurrentUser => (
+            "Get-AppxPackage | Where-Object { $Families -contains $_.PackageFamilyName }",
+            "Remove-AppxPackage -Package $package.PackageFullName -ErrorAction Stop",
+            "PackageFullName",
+        ),
+        RemovalScope::AllUsers => (
+            "Get-AppxPackage -AllUsers | Where-Object { $Families -contains $_.PackageFamilyName }",
+            "Remove-AppxPackage -Package $package.PackageFullName -AllUsers -ErrorAction Stop",
+            "PackageFullName",
+        ),
+        // Provisioned packages expose the name and the full name; the family is name + publisher ID
+        RemovalScope::Provisioned => (
+            "Get-AppxProvisionedPackage -Online | Where-Object { $Families -contains ($_.DisplayName + '_' + $_.PackageName.Split('_')[-1]) }",
+            "Remove-AppxProvisionedPackage -Online -PackageName $package.PackageName -ErrorAction Stop | Out-Null",
+            "PackageName",
+        ),
+    };
+
+    let script = format!(
+        "$packages = @({select})\n\
+         foreach ($package in $packages) {{ {remove} }}\n\
+         ConvertTo-Json -Compress -InputObject @($packages | ForEach-Object {{ $_.{name} }})"
+    );
+    CommandLine::powershell(&script, &[("Families", PowerShellValue::from(families))])
+}
+
+/// The JSON printed by the removal scripts; PowerShell may still collapse one name to a bare string
+fn parse_package_names(output: &str) -> Result<Vec<String>> {
+    let output = output.trim();
+    if output.is_empty() {
+        return Ok(Vec::new());
+    }
+    match serde_json::from_str::<serde_json::Value>(output)? {
+        serde_json::Value::String(name) => Ok(vec![name]),
+        value => Ok(serde_json::from_value(value)?),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use crate::command_runner::{CommandScript, ScriptedResponse, ScriptedRunner, TranscriptEntry};
+    use crate::protection::{ProtectionKind, ProtectionPolicy, ProtectionRule};
+    use crate::bloatware_matching::MatchRule;
+    use crate::registry_provider::{RecordedRegistry, RegistryValue};
+
+    const UNINSTALL_KEYS_FIXTURE: &str = include_str!("../fixtures/inventory/uninstall_keys.json");
+    const APPX_PACKAGES_FIXTURE: &str = include_str!("../fixtures/inventory/appx_packages.json");
+    const WINGET_LIST_FIXTURE: &str = include_str!("../fixtures/winget/list_en.txt");
+
+    const HP_SERVICE: &str = r#"{"Name":"HPSupportAssistant","DisplayName":"HP Support Assistant Service","PathName":"\"C:\\Program Files (x86)\\HP\\HP Support Framework\\HPSAService.exe\"","StartMode":"Auto","StartName":"LocalSystem"}"#;
+
+    /// The recorded uninstall keys, plus what the HP Support Assistant definition cleans up
+    fn registry() -> RecordedRegistry {
+        let mut registry: RecordedRegistry = serde_json::from_str(UNINSTALL_KEYS_FIXTURE).unwrap();
+        for key in [
+            r"HKEY_LOCAL_MACHINE\SOFTWARE\HP",
+            r"HKEY_LOCAL_MACHINE\SYSTEM\CurrentControlSet\Services\HPSupportAssistant",
+            r"HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\Windows NT\CurrentVersion\Schedule\TaskCache\Tree\HP\SupportAssistant",
+        ] {
+            registry.keys.insert(key.to_string(), BTreeMap::new());
+        }
+        registry
+    }
+
+    fn respond(program: &str, contains: &str, exit_code: i32, stdout: &str) -> ScriptedResponse {
+        ScriptedResponse {
+            program: program.to_string(),
+            contains: Some(contains.to_string()),
+            exit_code,
+            stdout: stdout.to_string(),
+            stderr: String::new(),
+        }
+    }
+
+    /// What inventory and winget report on the recorded machine; the export is unavailable
+    fn machine(responses: Vec<ScriptedResponse>) -> (BloatwareManager, Arc<ScriptedRunner>, PathBuf) {
+        machine_with(registry(), responses)
+    }
+
+    fn machine_with(registry: RecordedRegistry, mut responses: Vec<ScriptedResponse>) -> (BloatwareManager, Arc<ScriptedRunner>, PathBuf) {
+        responses.extend([
+            respond("powershell", "Get-AppxPackage | Select-Object", 0, APPX_PACKAGES_FIXTURE),
+            respond("winget", "winget list", 0, WINGET_LIST_FIXTURE),
+            respond("winget", "winget export", 1, "Failed to export"),
+        ]);
+        let runner = Arc::new(ScriptedRunner::new(CommandScript { responses }));
+        let directory = std::env::temp_dir().join(format!("bloatware-test-{}", uuid::Uuid::new_v4()));
+        let manager = BloatwareManager::with_providers(directory.clone(), Arc::new(registry), runner.clone());
+        (manager, runner, directory)
+    }
+
+    /// The entries ran `program` with `needle` in their readable form, in this order
+    fn assert_ran(entries: &[TranscriptEntry], expected: &[(&str, &str)]) {
+        let ran: Vec<String> = entries.iter().map(|entry| entry.command.to_string()).collect();
+        assert_eq!(ran.len(), expected.len(), "{:#?}", ran);
+        for ((entry, command), (program, needle)) in entries.iter().zip(&ran).zip(expected) {
+            assert_eq!(entry.command.program, *program, "{}", command);
+            assert!(command.contains(needle), "{} does not contain {}", command, needle);
+        }
+    }
+
+    fn hp_removal() -> Vec<ScriptedResponse> {
+        vec![
+            respond("reg", "reg.exe export", 0, ""),
+            respond("powershell", "Win32_Service", 0, HP_SERVICE),
+            respond("schtasks", "/Query", 0, "<Task><Actions /></Task>"),
+            respond("msiexec", "/x", 0, ""),
+            respond("powershell", "Stop-Service", 0, ""),
+            respond("sc", "delete", 0, "[SC] DeleteService SUCCESS"),
+            respond("schtasks", "/Delete", 0, ""),
+            respond("reg", "reg.exe delete", 0, ""),
+        ]
+    }
+
+    #[tokio::test]
+    async fn plans_every_concrete_action_without_running_any() {
+        let (manager, runner, directory) = machine(Vec::new());
+
+        let plan = manager.plan_removal(vec!["HP Support Assistant".to_string()], UninstallOptions::default()).await.unwrap();
+
+        let app = &plan.apps[0];
+        assert_eq!(plan.apps.len(), 1);
+        assert!(matches!(&app.uninstall_steps[..], [PlannedUninstall::Msi { products }] if products == &[MsiProduct {
+            product_code: "{7D2F3870-D4A5-4AFC-9D21-5DC19F1F5E11}".to_string(),
+            display_name: "HP Support Assistant".to_string(),
+        }]));
+        assert_eq!(app.registry_keys, [r"HKEY_LOCAL_MACHINE\SOFTWARE\HP"]);
+        assert_eq!(app.services, ["HPSupportAssistant"]);
+        assert_eq!(app.scheduled_tasks, [r"HP\SupportAssistant"]);
+        assert!(app.skipped.contains(&"WMI: unsupported removal method".to_string()), "{:?}", app.skipped);
+        assert!(!app.blocked);
+        assert_ran(&runner.transcript().entries(), &[
+            ("powershell.exe", "Get-AppxPackage | Select-Object"),
+            ("winget", "winget list"),
+            ("winget", "winget export"),
+        ]);
+        let _ = std::fs::remove_dir_all(&directory);
+    }
+
+    #[tokio::test]
+    async fn plans_no_windows_installer_step_without_a_product_code() {
+        let (manager, _, directory) = machine(Vec::new());
+
+        let plan = manager.plan_removal(vec!["McAfee Security".to_string()], UninstallOptions::default()).await.unwrap();
+
+        let app = &plan.apps[0];
+        assert!(app.uninstall_steps.is_empty(), "{:?}", app.uninstall_steps);
+        assert!(app.skipped.contains(&"PowerShell: no installed copy has a Windows Installer product code".to_string()), "{:?}", app.skipped);
+        let _ = std::fs::remove_dir_all(&directory);
+    }
+
+    #[tokio::test]
+    async fn leaves_protected_and_unanchored_targets_out_of_the_plan() {
+        let (manager, _, directory) = machine(Vec::new());
+        let scratch = directory.join("files");
+        for file in ["HP/SupportFramework/HPSA.log", "Vendor/Keep/notes.txt", "Vendor/cache.bin"] {
+            std::fs::create_dir_all(scratch.join(file).parent().unwrap()).unwrap();
+            std::fs::write(scratch.join(file), b"data").unwrap();
+        }
+        let policy = ProtectionPolicy {
+            rules: vec![ProtectionRule {
+                id: "vendor-notes".to_string(),
+                kind: ProtectionKind::UserFolder,
+                description: "Notes kept next to the vendor cache".to_string(),
+                locations: vec![scratch.join("Vendor").join("Keep").to_string_lossy().to_string()],
+                extensions: Vec::new(),
+            }],
+            allow_list: Vec::new(),
+        };
+        std::fs::write(directory.join(protection::POLICY_FILE), serde_json::to_vec(&policy).unwrap()).unwrap();
+        let critical = platform::current().critical_directories[0].clone();
+        {
+            let mut database = manager.bloatware_database.write().await;
+            let definition = database.apps.get_mut("HP Support Assistant").unwrap();
+            definition.file_paths = vec![
+                scratch.join("HP").join("Support*"),
+                scratch.join("Vendor"),
+                critical.clone(),
+                PathBuf::from(r"%USERPROFILE%\*"),
+            ];
+            definition.registry_keys = vec![r"HKEY_LOCAL_MACHINE\SOFTWARE\HP".to_string(), r"HKEY_CURRENT_USER\Software\*".to_string()];
+        }
+
+        let plan = manager.plan_removal(vec!["HP Support Assistant".to_string()], UninstallOptions::default()).await.unwrap();
+
+        let app = &plan.apps[0];
+        let files: Vec<&PathBuf> = app.files.iter().map(|file| &file.path).collect();
+        assert_eq!(files, [&scratch.join("HP").join("SupportFramework")]);
+        assert_eq!(app.total_bytes, 4);
+        assert_eq!(app.registry_keys, [r"HKEY_LOCAL_MACHINE\SOFTWARE\HP"]);
+        for expected in [
+            format!("File path {}: blocked by user-protected folder \"vendor-notes\"", scratch.join("Vendor").display()),
+            format!("File path {}: blocked by protected location \"{}\"", critical.display(), protection::SYSTEM_LOCATIONS_RULE),
+            r"File path %USERPROFILE%\* has no fixed folder below its root".to_string(),
+            r"Registry key HKEY_CURRENT_USER\Software\* has no fixed key below Software".to_string(),
+        ] {
+            assert!(app.skipped.iter().any(|skipped| skipped.starts_with(&expected)), "{} not in {:#?}", expected, app.skipped);
+        }
+        let _ = std::fs::remove_dir_all(&directory);
+    }
+
+    /// The recorded machine plus WebView2, its update key, and classic Teams, which renders through it
+    async fn webview2_machine() -> (BloatwareManager, PathBuf) {
+        let mut registry = registry();
+        for (key, name) in [
+            (r"HKEY_LOCAL_MACHINE\SOFTWARE\WOW6432Node\Microsoft\Windows\CurrentVersion\Uninstall\Microsoft EdgeWebView", "Microsoft Edge WebView2 Runtime"),
+            (r"HKEY_CURRENT_USER\Software\Microsoft\Windows\CurrentVersion\Uninstall\Teams", "Microsoft Teams classic"),
+        ] {
+            registry.keys.insert(key.to_string(), BTreeMap::from([
+                ("DisplayName".to_string(), RegistryValue::Text(name.to_string())),
+                ("Publisher".to_string(), RegistryValue::Text("Microsoft Corporation".to_string())),
+                ("UninstallString".to_string(), RegistryValue::Text(format!("\"C:\\Program Files\\{}\\setup.exe\" --uninstall", name))),
+            ]));
+        }
+        registry.keys.insert(r"HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\EdgeUpdate".to_string(), BTreeMap::new());
+        let (manager, _, directory) = machine_with(registry, Vec::new());
+
+        // Classic Teams as a definition of its own, so a plan can take it along
+        let mut database = manager.bloatware_database.write().await;
+        let mut teams = database.apps["Microsoft Edge"].clone();
+        teams.name = "Microsoft Teams".to_string();
+        teams.display_name = "Microsoft Teams classic".to_string();
+        teams.removal_methods = Vec::new();
+        teams.registry_keys = Vec::new();
+        teams.file_paths = Vec::new();
+        let rule: MatchRule = serde_json::from_value(serde_json::json!({
+            "type": "publisher_name", "publisher": "Microsoft Corporation", "display_name": "^Microsoft Teams classic$",
+        })).unwrap();
+        database.matcher.add("Microsoft Teams", &[rule]).unwrap();
+        database.apps.insert(teams.name.clone(), teams);
+        drop(database);
+        (manager, directory)
+    }
+
+    #[tokio::test]
+    async fn blocks_removing_webview2_while_teams_relies_on_it() {
+        let (manager, directory) = webview2_machine().await;
+
+        let plan = manager.plan_removal(vec!["Microsoft Edge".to_string()], UninstallOptions::default()).await.unwrap();
+
+        let app = &plan.apps[0];
+        assert_eq!(app.registry_keys, [r"HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\EdgeUpdate"]);
+        assert!(app.blocked);
+        let impact = app.dependency_impacts.iter().find(|impact| impact.policy == DependencyPolicy::Block).unwrap();
+        assert_eq!(impact.component, "Microsoft Edge WebView2 Runtime");
+        let affected: Vec<&str> = impact.affected.iter().map(|dependent| dependent.name.as_str()).collect();
+        assert_eq!(affected, ["Windows System Optimizer", "Microsoft Teams classic"]);
+        assert!(impact.explanation.contains("Microsoft Teams classic"), "{}", impact.explanation);
+
+        let results = manager.execute_removal_plan(&plan.id).await.unwrap();
+        assert!(!results[0].success);
+        let _ = std::fs::remove_dir_all(&directory);
+    }
+
+    #[tokio::test]
+    async fn removing_the_dependent_in_the_same_plan_lifts_the_block() {
+        let (mut manager, directory) = webview2_machine().await;
+        // Only Teams relies on WebView2 here, not this app itself
+        let mut components = dependencies::builtin_components().unwrap();
+        components.iter_mut().find(|component| component.id == "webview2").unwrap().required_by_optimizer = false;
+        manager.components = Arc::new(components);
+
+        let alone = manager.plan_removal(vec!["Microsoft Edge".to_string()], UninstallOptions::default()).await.unwrap();
+        let together = manager.plan_removal(vec!["Microsoft Edge".to_string(), "Microsoft Teams".to_string()], UninstallOptions::default()).await.unwrap();
+
+        assert!(alone.apps[0].blocked);
+        let webview2 = together.apps.iter().find(|app| app.app_name == "Microsoft Edge").unwrap();
+        assert!(!webview2.blocked, "{:?}", webview2.dependency_impacts);
+        assert!(webview2.dependency_impacts.iter().all(|impact| impact.affected.is_empty() && impact.policy == DependencyPolicy::Warn));
+        let _ = std::fs::remove_dir_all(&directory);
+    }
+
+    #[tokio::test]
+    async fn executes_a_plan_once_after_backing_up() {
+        let (manager, runner, directory) = machine(hp_removal());
+        let plan = manager.plan_removal(vec!["HP Support Assistant".to_string()], UninstallOptions::default()).await.unwrap();
+        let planned = runner.transcript().entries().len();
+
+        let results = manager.execute_removal_plan(&plan.id).await.unwrap();
+
+        let result = &results[0];
+        assert!(result.success, "{:?}", result.errors);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert!(matches!(result.method_used, RemovalMethod::PowerShell));
+        assert_eq!((result.services_stopped, result.tasks_removed, result.registry_entries_removed), (1, 1, 1));
+        assert!(result.details.contains(&"Backup incomplete: No package source is known to reinstall the app from".to_string()));
+        assert_ran(&runner.transcript().entries()[planned..], &[
+            ("reg.exe", r"export HKEY_LOCAL_MACHINE\SOFTWARE\HP"),
+            ("powershell.exe", "Win32_Service"),
+            ("reg.exe", r"export HKEY_LOCAL_MACHINE\SYSTEM\CurrentControlSet\Services\HPSupportAssistant"),
+            ("schtasks.exe", r"/Query /TN \HP\SupportAssistant /XML"),
+            ("msiexec.exe", "/x {7D2F3870-D4A5-4AFC-9D21-5DC19F1F5E11} /qn"),
+            ("powershell.exe", "Stop-Service"),
+            ("sc.exe", "delete HPSupportAssistant"),
+            ("schtasks.exe", r"/Delete /TN \HP\SupportAssistant /F"),
+            ("reg.exe", r"delete HKEY_LOCAL_MACHINE\SOFTWARE\HP /f"),
+        ]);
+
+        let backup = RemovalBackup::load(&directory.join("removals"), result.backup_id.as_deref().unwrap()).await.unwrap();
+        assert_eq!(backup.result.unwrap().app_name, "HP Support Assistant");
+        assert_eq!(manager.get_removal_history().await.len(), 1);
+        assert!(manager.execute_removal_plan(&plan.id).await.is_err());
+        let _ = std::fs::remove_dir_all(&directory);
+    }
+
+    #[tokio::test]
+    async fn stops_before_removing_anything_when_the_uninstaller_fails() {
+        let mut responses = vec![respond("msiexec", "/x", 1603, "")];
+        responses.extend(hp_removal());
+        let (manager, runner, directory) = machine(responses);
+        let plan = manager.plan_removal(vec!["HP Support Assistant".to_string()], UninstallOptions::default()).await.unwrap();
+
+        let results = manager.execute_removal_plan(&plan.id).await.unwrap();
+
+        assert!(!results[0].success);
+        assert_eq!(results[0].errors, ["Windows Installer failed: HP Support Assistant: msiexec exited with code 1603 for {7D2F3870-D4A5-4AFC-9D21-5DC19F1F5E11}"]);
+        let entries = runner.transcript().entries();
+        assert!(entries.iter().all(|entry| !entry.command.to_string().contains("delete")), "cleanup ran after a failed uninstall");
+        let _ = std::fs::remove_dir_all(&directory);
+    }
+
+    #[tokio::test]
+    async fn keeps_the_errors_of_steps_a_fallback_replaced() {
+        let mut responses = vec![respond("winget", "winget uninstall", 1, "")];
+        responses.extend(hp_removal());
+        let (manager, _, directory) = machine(responses);
+        let plan = manager.plan_removal(vec!["HP Support Assistant".to_string()], UninstallOptions::default()).await.unwrap();
+        manager.plans.write().await.get_mut(&plan.id).unwrap().apps[0].uninstall_steps.insert(0, PlannedUninstall::Winget {
+            packages: vec![WingetPackage {
+                name: "HP Support Assistant".to_string(),
+                id: "HP.SupportAssistant".to_string(),
+                version: "9.11.62.0".to_string(),
+                available: None,
+                source: Some("winget".to_string()),
+            }],
+        });
+
+        let results = manager.execute_removal_plan(&plan.id).await.unwrap();
+
+        let result = &results[0];
+        assert!(result.success);
+        assert!(matches!(result.method_used, RemovalMethod::PowerShell));
+        assert_eq!(result.errors.len(), 1, "{:?}", result.errors);
+        assert!(result.errors[0].starts_with("Winget failed: "), "{}", result.errors[0]);
+        assert!(result.details.contains(&"Windows Installer removed {7D2F3870-D4A5-4AFC-9D21-5DC19F1F5E11}".to_string()), "{:?}", result.details);
+        let backup = RemovalBackup::load(&directory.join("removals"), result.backup_id.as_ref().unwrap()).await.unwrap();
+        assert_eq!(backup.result.unwrap().errors, result.errors);
+        let _ = std::fs::remove_dir_all(&directory);
+    }
+
+    #[tokio::test]
+    async fn uninstalls_inventory_entries_by_product_code() {
+        let (manager, runner, directory) = machine(vec![respond("msiexec", "/x", 3010, "")]);
+        let key = r"HKEY_LOCAL_MACHINE\SOFTWARE\WOW6432Node\Microsoft\Windows\CurrentVersion\Uninstall\{7D2F3870-D4A5-4AFC-9D21-5DC19F1F5E11}";
+
+        let result = manager.uninstall_installed(key).await.unwrap();
+
+        assert!(result.success, "{:?}", result.errors);
+        assert_eq!(result.details.last().unwrap(), "Windows Installer removed {7D2F3870-D4A5-4AFC-9D21-5DC19F1F5E11}; restart to finish");
+        let entries = runner.transcript().entries();
+        assert_eq!(entries.last().unwrap().command.args, ["/x", "{7D2F3870-D4A5-4AFC-9D21-5DC19F1F5E11}", "/qn", "/norestart"]);
+        let _ = std::fs::remove_dir_all(&directory);
+    }
+
+    #[tokio::test]
+    async fn uninstalls_inventory_entries_through_winget() {
+        let (manager, runner, directory) = machine(vec![respond("winget", "winget uninstall", 0, "Successfully uninstalled")]);
+        let key = r"HKEY_LOCAL_MACHINE\SOFTWARE\WOW6432Node\Microsoft\Windows\CurrentVersion\Uninstall\McAfee LiveSafe";
+
+        let result = manager.uninstall_installed(key).await.unwrap();
+
+        assert!(result.success, "{:?}", result.errors);
+        assert!(matches!(result.method_used, RemovalMethod::Winget));
+        let entries = runner.transcript().entries();
+        assert_eq!(&entries.last().unwrap().command.args[..3], ["uninstall", "--id", r"ARP\Machine\X86\McAfee LiveSafe"]);
+        let _ = std::fs::remove_dir_all(&directory);
+    }
+
+    #[tokio::test]
+    async fn never_deletes_the_uninstall_key_of_an_app_it_cannot_remove() {
+        let (manager, runner, directory) = machine(Vec::new());
+        let key = r"HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\Windows\CurrentVersion\Uninstall\Mozilla Firefox 128.0 (x64 en-US)";
+
+        let result = manager.uninstall_installed(key).await.unwrap();
+
+        assert!(!result.success);
+        assert_eq!(result.errors, ["Neither winget nor Windows Installer knows Mozilla Firefox (x64 en-US); run its own uninstaller"]);
+        assert!(runner.transcript().entries().iter().all(|entry| entry.command.program != "reg.exe"));
+        assert!(manager.uninstall_installed(r"HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\Windows\CurrentVersion\Run").await.is_err());
+        let _ = std::fs::remove_dir_all(&directory);
+    }
+}
@@ -0,0 +1,353 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use anyhow::{anyhow, Context, Result};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::bloatware::{BloatwareApp, BloatwareCategory, RemovalMethod};
+use crate::bloatware_matching::{AppIdentity, MatchEngine, MatchRule};
+use crate::path_resolver;
+
+/// Definition file format this build understands
+pub const SCHEMA_VERSION: u32 = 1;
+
+const BUILTIN_DEFINITIONS: &str = include_str!("../definitions/bloatware.json");
+
+/// One versioned file of bloatware definitions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DefinitionFile {
+    /// Editor hint pointing at bloatware.schema.json
+    #[serde(rename = "$schema", default, skip_serializing_if = "Option::is_none")]
+    pub schema: Option<String>,
+    pub schema_version: u32,
+    /// Version of the definitions themselves, reported back to the user
+    pub version: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub definitions: Vec<BloatwareDefinition>,
+    /// Names inherited from earlier layers that this layer drops
+    #[serde(default)]
+    pub remove: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BloatwareDefinition {
+    pub name: String,
+    #[serde(default)]
+    pub display_name: String,
+    /// How installed applications are recognized as this entry
+    pub rules: Vec<MatchRule>,
+    #[serde(default)]
+    pub publisher: String,
+    pub category: BloatwareCategory,
+    /// Defaults to the first file path
+    #[serde(default)]
+    pub install_location: Option<PathBuf>,
+    #[serde(default)]
+    pub size_mb: u64,
+    pub confidence_score: f32,
+    pub removal_methods: Vec<RemovalMethod>,
+    #[serde(default)]
+    pub registry_keys: Vec<String>,
+    #[serde(default)]
+    pub file_paths: Vec<PathBuf>,
+    #[serde(default)]
+    pub services: Vec<String>,
+    #[serde(default)]
+    pub scheduled_tasks: Vec<String>,
+    #[serde(default = "default_true")]
+    pub can_uninstall: bool,
+    #[serde(default)]
+    pub is_critical: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DefinitionLayer {
+    /// Shipped inside the application
+    Builtin,
+    /// Machine-wide file managed by the organization
+    Organization,
+    /// Per-user overrides, applied last
+    User,
+}
+
+/// How one layer contributed to the database
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoadedLayer {
+    pub layer: DefinitionLayer,
+    pub path: Option<PathBuf>,
+    pub version: String,
+    pub definitions: usize,
+    pub removed: usize,
+}
+
+/// Outcome of the last load, errors included
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DefinitionStatus {
+    pub layers: Vec<LoadedLayer>,
+    pub definition_count: usize,
+    /// Layers that failed validation are skipped as a whole
+    pub errors: Vec<String>,
+}
+
+/// Definitions by name and the compiled rules recognizing them
+#[derive(Default)]
+pub struct BloatwareDatabase {
+    pub apps: HashMap<String, BloatwareApp>,
+    pub matcher: MatchEngine,
+}
+
+/// Where the overriding layers are read from; missing files are skipped
+#[derive(Debug, Clone)]
+pub struct DefinitionSources {
+    pub organization: Option<PathBuf>,
+    pub user: Option<PathBuf>,
+}
+
+impl DefinitionSources {
+    /// `%ProgramData%` for the organization, `%APPDATA%` for the user
+    pub fn standard() -> Self {
+        let under = |variable: &str| {
+            std::env::var_os(variable).map(|root| PathBuf::from(root).join("WindowsOptimizer").join("bloatware.json"))
+        };
+        Self {
+            organization: under("ProgramData"),
+            user: under("APPDATA"),
+        }
+    }
+}
+
+/// Build the database from the built-in definitions and any override files
+pub fn load_database(sources: &DefinitionSources) -> (BloatwareDatabase, DefinitionStatus) {
+    let mut definitions = HashMap::new();
+    let mut status = DefinitionStatus::default();
+
+    match parse_definitions(BUILTIN_DEFINITIONS, "built-in definitions") {
+        Ok(file) => apply_layer(&mut definitions, &mut status, DefinitionLayer::Builtin, None, file),
+        Err(e) => status.errors.push(format!("{:#}", e)),
+    }
+
+    let overrides = [
+        (DefinitionLayer::Organization, &sources.organization),
+        (DefinitionLayer::User, &sources.user),
+    ];
+    for (layer, path) in overrides {
+        let Some(path) = path else { continue };
+        if !path.exists() {
+            continue;
+        }
+        match read_definitions(path) {
+            Ok(file) => apply_layer(&mut definitions, &mut status, layer, Some(path.clone()), file),
+            Err(e) => {
+                warn!("Skipping {:?} bloatware definitions: {:#}", layer, e);
+                status.errors.push(format!("{:#}", e));
+            }
+        }
+    }
+
+    let mut database = BloatwareDatabase::default();
+    for (name, definition) in definitions {
+        // Rules were validated with their file, so this only fails on a bug
+        if let Err(e) = database.matcher.add(&name, &definition.rules) {
+            status.errors.push(format!("Rules of {:?} failed to compile: {:#}", name, e));
+            continue;
+        }
+        database.apps.insert(name, definition.into_app());
+    }
+
+    status.definition_count = database.apps.len();
+    info!("Loaded {} bloatware definitions from {} layers", status.definition_count, status.layers.len());
+    (database, status)
+}
+
+pub fn read_definitions(path: &Path) -> Result<DefinitionFile> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Cannot read {}", path.display()))?;
+    parse_definitions(&content, &path.display().to_string())
+}
+
+/// Parse and validate a definition file; `origin` names it in errors
+pub fn parse_definitions(content: &str, origin: &str) -> Result<DefinitionFile> {
+    // Check the version first so a newer format is not reported as a field error
+    let header: serde_json::Value = serde_json::from_str(content)
+        .with_context(|| format!("{} is not valid JSON", origin))?;
+    match header.get("schema_version").and_then(|version| version.as_u64()) {
+        Some(version) if version == SCHEMA_VERSION as u64 => {}
+        Some(version) => {
+            return Err(anyhow!("{} uses schema version {}, this build reads version {}", origin, version, SCHEMA_VERSION));
+        }
+        None => return Err(anyhow!("{} has no numeric schema_version", origin)),
+    }
+
+    let file: DefinitionFile = serde_json::from_str(content)
+        .with_context(|| format!("{} does not match the definition schema", origin))?;
+    validate(&file).with_context(|| format!("{} is invalid", origin))?;
+    Ok(file)
+}
+
+fn validate(file: &DefinitionFile) -> Result<()> {
+    if file.version.trim().is_empty() {
+        return Err(anyhow!("version is empty"));
+    }
+
+    let mut names = HashSet::new();
+    for (index, definition) in file.definitions.iter().enumerate() {
+        let entry = format!("definition #{} ({:?})", index + 1, definition.name);
+        if definition.name.trim().is_empty() {
+            return Err(anyhow!("definition #{} has no name", index + 1));
+        }
+        if !names.insert(definition.name.as_str()) {
+            return Err(anyhow!("{} is defined more than once", entry));
+        }
+        if !(0.0..=1.0).contains(&definition.confidence_score) {
+            return Err(anyhow!("{}: confidence_score {} is outside 0.0-1.0", entry, definition.confidence_score));
+        }
+        if definition.rules.is_empty() {
+            return Err(anyhow!("{}: needs at least one match rule", entry));
+        }
+        for (rule_index, rule) in definition.rules.iter().enumerate() {
+            rule.validate().with_context(|| format!("{}: rule #{}", entry, rule_index + 1))?;
+        }
+        if definition.can_uninstall && definition.removal_methods.is_empty() {
+            return Err(anyhow!("{}: uninstallable entries need at least one removal method", entry));
+        }
+        // Cleanup deletes whatever these match, so each must name something specific
+        for path in &definition.file_paths {
+            path_resolver::check_anchored(&path.to_string_lossy()).with_context(|| format!("{}: file path", entry))?;
+        }
+        for key in &definition.registry_keys {
+            path_resolver::check_key_anchored(key).with_context(|| format!("{}: registry key", entry))?;
+        }
+    }
+
+    if let Some(name) = file.remove.iter().find(|name| names.contains(name.as_str())) {
+        return Err(anyhow!("{:?} is both defined and removed", name));
+    }
+    Ok(())
+}
+
+/// Later layers replace earlier entries by name
+fn apply_layer(
+    database: &mut HashMap<String, BloatwareDefinition>,
+    status: &mut DefinitionStatus,
+    layer: DefinitionLayer,
+    path: Option<PathBuf>,
+    file: DefinitionFile,
+) {
+    let removed = file.remove.iter().filter(|name| database.remove(name.as_str()).is_some()).count();
+    let definitions = file.definitions.len();
+    for definition in file.definitions {
+        database.insert(definition.name.clone(), definition);
+    }
+
+    status.layers.push(LoadedLayer {
+        layer,
+        path,
+        version: file.version,
+        definitions,
+        removed,
+    });
+}
+
+impl BloatwareDefinition {
+    fn into_app(self) -> BloatwareApp {
+        let display_name = if self.display_name.is_empty() { self.name.clone() } else { self.display_name };
+        BloatwareApp {
+            name: self.name,
+            display_name,
+            version: String::new(),
+            publisher: self.publisher,
+            install_location: self.install_location.or_else(|| self.file_paths.first().cloned()).unwrap_or_default(),
+            size_mb: self.size_mb,
+            category: self.category,
+            confidence_score: self.confidence_score,
+            removal_methods: self.removal_methods,
+            registry_keys: self.registry_keys,
+            file_paths: self.file_paths,
+            services: self.services,
+            scheduled_tasks: self.scheduled_tasks,
+            is_installed: false,
+            can_uninstall: self.can_uninstall,
+            is_critical: self.is_critical,
+            last_modified: Utc::now(),
+            identity: AppIdentity::default(),
+            matched_rule: None,
+            required_by: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layer(file_paths: &[&str], registry_keys: &[&str]) -> String {
+        serde_json::json!({
+            "schema_version": SCHEMA_VERSION,
+            "version": "2024.10-org",
+            "definitions": [{
+                "name": "Vendor Toolbar",
+                "rules": [{ "type": "publisher_name", "publisher": "Vendor", "display_name": "^Vendor Toolbar$" }],
+                "category": "Adware",
+                "confidence_score": 0.9,
+                "removal_methods": ["PowerShell"],
+                "file_paths": file_paths,
+                "registry_keys": registry_keys,
+            }],
+        }).to_string()
+    }
+
+    #[test]
+    fn builtin_definitions_are_valid() {
+        parse_definitions(BUILTIN_DEFINITIONS, "built-in definitions").unwrap();
+    }
+
+    #[test]
+    fn rejects_paths_matching_a_whole_profile_or_drive() {
+        for path in [r"%USERPROFILE%\*", "%LOCALAPPDATA%", r"C:\*", r"Vendor\Toolbar"] {
+            let error = parse_definitions(&layer(&[path], &[]), "org.json").unwrap_err();
+            assert!(format!("{:#}", error).contains(&format!("file path: {}", path)), "{:#}", error);
+        }
+        assert!(parse_definitions(&layer(&[r"%LOCALAPPDATA%\Vendor\Toolbar*"], &[r"HKEY_CURRENT_USER\Software\Vendor"]), "org.json").is_ok());
+    }
+
+    #[test]
+    fn rejects_registry_keys_outside_the_supported_hives() {
+        let error = parse_definitions(&layer(&[], &[r"HKEY_USERS\.DEFAULT\Software\Vendor"]), "org.json").unwrap_err();
+        assert!(format!("{:#}", error).contains("has no supported hive"), "{:#}", error);
+    }
+
+    #[test]
+    fn rejects_registry_keys_matching_every_vendor() {
+        for key in [r"HKEY_LOCAL_MACHINE\SOFTWARE", r"HKEY_CURRENT_USER\Software\*"] {
+            let error = parse_definitions(&layer(&[], &[key]), "user.json").unwrap_err();
+            assert!(format!("{:#}", error).contains(&format!("registry key: {}", key)), "{:#}", error);
+        }
+    }
+
+    #[test]
+    fn skips_an_invalid_override_layer_as_a_whole() {
+        let directory = std::env::temp_dir().join(format!("definitions-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let (organization, user) = (directory.join("organization.json"), directory.join("user.json"));
+        std::fs::write(&organization, layer(&[r"%LOCALAPPDATA%\Vendor"], &[])).unwrap();
+        std::fs::write(&user, layer(&[r"%USERPROFILE%\*"], &[])).unwrap();
+
+        let (database, status) = load_database(&DefinitionSources { organization: Some(organization), user: Some(user) });
+
+        let layers: Vec<DefinitionLayer> = status.layers.iter().map(|layer| layer.layer).collect();
+        assert_eq!(layers, [DefinitionLayer::Builtin, DefinitionLayer::Organization]);
+        assert_eq!(status.errors.len(), 1);
+        assert!(status.errors[0].contains("has no fixed folder below its root"), "{}", status.errors[0]);
+        assert_eq!(database.apps["Vendor Toolbar"].file_paths, [PathBuf::from(r"%LOCALAPPDATA%\Vendor")]);
+        let _ = std::fs::remove_dir_all(&directory);
+    }
+}
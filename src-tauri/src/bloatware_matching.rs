@@ -0,0 +1,466 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::sync::OnceLock;
+use anyhow::{anyhow, Result};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+/// Identifiers of an installed application beyond its display name and publisher
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AppIdentity {
+    /// Appx package family, e.g. `Microsoft.BingWeather_8wekyb3d8bbwe`
+    pub package_family_name: Option<String>,
+    /// MSI ProductCode GUID
+    pub product_code: Option<String>,
+    /// MSI UpgradeCode GUID, shared by every version of a product
+    pub upgrade_code: Option<String>,
+}
+
+/// The facts a rule is evaluated against
+pub struct MatchSubject<'a> {
+    pub display_name: &'a str,
+    pub publisher: &'a str,
+    pub version: &'a str,
+    pub identity: &'a AppIdentity,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MatchCondition {
+    /// Exact Appx package family name, case-insensitive
+    PackageFamily { family_name: String },
+    /// Normalized publisher equality plus a case-insensitive display-name regex
+    PublisherName { publisher: String, display_name: String },
+    MsiProductCode { code: String },
+    MsiUpgradeCode { code: String },
+}
+
+/// Inclusive lower and exclusive upper bound on the installed version
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VersionRange {
+    #[serde(default)]
+    pub from: Option<String>,
+    #[serde(default)]
+    pub before: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MatchRule {
+    #[serde(flatten)]
+    pub condition: MatchCondition,
+    /// Only installed versions inside the range match
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub versions: Option<VersionRange>,
+    /// Overrides the default confidence of the rule type
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<f32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MatchKind {
+    PackageFamily,
+    PublisherName,
+    MsiProductCode,
+    MsiUpgradeCode,
+}
+
+/// Why an installed application was identified as a known definition
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleMatch {
+    pub definition: String,
+    pub kind: MatchKind,
+    /// Human-readable form of the rule that fired
+    pub rule: String,
+    /// How sure the match is that the app is this definition
+    pub confidence: f32,
+}
+
+struct CompiledRule {
+    kind: MatchKind,
+    value: String,
+    display_name: Option<Regex>,
+    from: Option<Vec<u64>>,
+    before: Option<Vec<u64>>,
+    confidence: f32,
+    description: String,
+}
+
+/// Rules of every definition, compiled once per load
+#[derive(Default)]
+pub struct MatchEngine {
+    definitions: Vec<(String, Vec<CompiledRule>)>,
+}
+
+impl MatchEngine {
+    pub fn add(&mut self, definition: &str, rules: &[MatchRule]) -> Result<()> {
+        let compiled = rules.iter().map(MatchRule::compile).collect::<Result<Vec<_>>>()?;
+        self.definitions.push((definition.to_string(), compiled));
+        Ok(())
+    }
+
+    /// Lower-cased Appx package families the definition's rules name
+    pub fn package_families(&self, definition: &str) -> Vec<String> {
+        self.definitions
+            .iter()
+            .filter(|(name, _)| name == definition)
+            .flat_map(|(_, rules)| rules.iter())
+            .filter(|rule| rule.kind == MatchKind::PackageFamily)
+            .map(|rule| rule.value.clone())
+            .collect()
+    }
+
+    /// The most confident rule firing across all definitions
+    pub fn best_match(&self, subject: &MatchSubject) -> Option<RuleMatch> {
+        let mut best: Option<RuleMatch> = None;
+
+        for (definition, rules) in &self.definitions {
+            for rule in rules.iter().filter(|rule| rule.fires(subject)) {
+                if best.as_ref().is_some_and(|current| current.confidence >= rule.confidence) {
+                    continue;
+                }
+                best = Some(RuleMatch {
+                    definition: definition.clone(),
+                    kind: rule.kind,
+                    rule: rule.description.clone(),
+                    confidence: rule.confidence,
+                });
+            }
+        }
+
+        best
+    }
+}
+
+impl MatchRule {
+    /// Check the rule the way loading will, for clear errors in definition files
+    pub fn validate(&self) -> Result<()> {
+        self.compile().map(|_| ())
+    }
+
+    fn compile(&self) -> Result<CompiledRule> {
+        let (kind, value, display_name, default_confidence) = match &self.condition {
+            MatchCondition::PackageFamily { family_name } => {
+                if !family_name.contains('_') {
+                    return Err(anyhow!("package family {:?} lacks the _publisherId suffix", family_name));
+                }
+                (MatchKind::PackageFamily, family_name.to_lowercase(), None, 1.0)
+            }
+            MatchCondition::PublisherName { publisher, display_name } => {
+                if normalize_publisher(publisher).is_empty() {
+                    return Err(anyhow!("publisher rule needs a publisher"));
+                }
+                let regex = RegexBuilder::new(display_name)
+                    .case_insensitive(true)
+                    .build()
+                    .map_err(|e| anyhow!("display name pattern {:?} is not a valid regex: {}", display_name, e))?;
+                (MatchKind::PublisherName, normalize_publisher(publisher), Some(regex), 0.85)
+            }
+            MatchCondition::MsiProductCode { code } => (MatchKind::MsiProductCode, normalize_guid(code)?, None, 1.0),
+            MatchCondition::MsiUpgradeCode { code } => (MatchKind::MsiUpgradeCode, normalize_guid(code)?, None, 0.95),
+        };
+
+        let confidence = self.confidence.unwrap_or(default_confidence);
+        if !(0.0..=1.0).contains(&confidence) {
+            return Err(anyhow!("rule confidence {} is outside 0.0-1.0", confidence));
+        }
+
+        let range = self.versions.clone().unwrap_or_default();
+        let bound = |version: &Option<String>| -> Result<Option<Vec<u64>>> {
+            match version {
+                Some(text) => parse_version(text)
+                    .map(Some)
+                    .ok_or_else(|| anyhow!("version bound {:?} does not start with a number", text)),
+                None => Ok(None),
+            }
+        };
+        let (from, before) = (bound(&range.from)?, bound(&range.before)?);
+        if let (Some(from), Some(before)) = (&from, &before) {
+            if compare_versions(from, before) != Ordering::Less {
+                return Err(anyhow!("version range {}..{} is empty", range.from.unwrap_or_default(), range.before.unwrap_or_default()));
+            }
+        }
+
+        Ok(CompiledRule {
+            kind,
+            value,
+            display_name,
+            from,
+            before,
+            confidence,
+            description: self.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for MatchRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.condition {
+            MatchCondition::PackageFamily { family_name } => write!(f, "package family {}", family_name)?,
+            MatchCondition::PublisherName { publisher, display_name } => {
+                write!(f, "publisher \"{}\" with name /{}/", publisher, display_name)?
+            }
+            MatchCondition::MsiProductCode { code } => write!(f, "MSI product code {}", code)?,
+            MatchCondition::MsiUpgradeCode { code } => write!(f, "MSI upgrade code {}", code)?,
+        }
+        if let Some(range) = &self.versions {
+            write!(f, ", version {}..{}", range.from.as_deref().unwrap_or(""), range.before.as_deref().unwrap_or(""))?;
+        }
+        Ok(())
+    }
+}
+
+impl CompiledRule {
+    fn fires(&self, subject: &MatchSubject) -> bool {
+        let identity = subject.identity;
+        let matched = match self.kind {
+            MatchKind::PackageFamily => identity.package_family_name.as_deref()
+                .is_some_and(|family| family.to_lowercase() == self.value),
+            MatchKind::PublisherName => normalize_publisher(subject.publisher) == self.value
+                && self.display_name.as_ref().is_some_and(|regex| regex.is_match(subject.display_name.trim())),
+            MatchKind::MsiProductCode => identity.product_code.as_deref()
+                .is_some_and(|code| normalize_guid(code).is_ok_and(|code| code == self.value)),
+            MatchKind::MsiUpgradeCode => identity.upgrade_code.as_deref()
+                .is_some_and(|code| normalize_guid(code).is_ok_and(|code| code == self.value)),
+        };
+
+        matched && self.version_in_range(subject.version)
+    }
+
+    fn version_in_range(&self, version: &str) -> bool {
+        if self.from.is_none() && self.before.is_none() {
+            return true;
+        }
+        // A range cannot vouch for an app whose version is unknown
+        let Some(version) = parse_version(version) else { return false };
+
+        self.from.as_ref().is_none_or(|from| compare_versions(&version, from) != Ordering::Less)
+            && self.before.as_ref().is_none_or(|before| compare_versions(&version, before) == Ordering::Less)
+    }
+}
+
+/// "McAfee, LLC" and "McAfee LLC" compare equal
+fn normalize_publisher(publisher: &str) -> String {
+    publisher
+        .chars()
+        .filter(|c| !matches!(c, ',' | '.'))
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Braced, upper-case GUID
+fn normalize_guid(code: &str) -> Result<String> {
+    static GUID: OnceLock<Regex> = OnceLock::new();
+    let guid = GUID.get_or_init(|| {
+        Regex::new(r"^\{?[0-9A-Fa-f]{8}-[0-9A-Fa-f]{4}-[0-9A-Fa-f]{4}-[0-9A-Fa-f]{4}-[0-9A-Fa-f]{12}\}?$").unwrap()
+    });
+
+    let code = code.trim();
+    if !guid.is_match(code) {
+        return Err(anyhow!("{:?} is not an MSI GUID", code));
+    }
+    Ok(format!("{{{}}}", code.trim_matches(|c| c == '{' || c == '}').to_uppercase()))
+}
+
+/// Leading dotted numbers; "10.2.3-beta" reads as 10.2.3
+fn parse_version(version: &str) -> Option<Vec<u64>> {
+    let parts: Vec<u64> = version
+        .trim()
+        .split('.')
+        .map_while(|part| {
+            let digits: String = part.chars().take_while(|c| c.is_ascii_digit()).collect();
+            digits.parse().ok()
+        })
+        .collect();
+    if parts.is_empty() { None } else { Some(parts) }
+}
+
+/// Missing components count as zero, so 1.2 equals 1.2.0
+fn compare_versions(left: &[u64], right: &[u64]) -> Ordering {
+    (0..left.len().max(right.len()))
+        .map(|i| left.get(i).unwrap_or(&0).cmp(right.get(i).unwrap_or(&0)))
+        .find(|ordering| *ordering != Ordering::Equal)
+        .unwrap_or(Ordering::Equal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn rule(value: serde_json::Value) -> MatchRule {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn engine(definitions: &[(&str, Vec<MatchRule>)]) -> MatchEngine {
+        let mut engine = MatchEngine::default();
+        for (name, rules) in definitions {
+            engine.add(name, rules).unwrap();
+        }
+        engine
+    }
+
+    fn matched(engine: &MatchEngine, display_name: &str, publisher: &str, version: &str, identity: &AppIdentity) -> Option<RuleMatch> {
+        engine.best_match(&MatchSubject { display_name, publisher, version, identity })
+    }
+
+    fn hp_support_assistant() -> MatchEngine {
+        engine(&[("HP Support Assistant", vec![rule(json!({
+            "type": "publisher_name", "publisher": "HP Inc.", "display_name": "^HP Support Assistant$",
+        }))])])
+    }
+
+    #[test]
+    fn version_ranges_include_from_and_exclude_before() {
+        let engine = engine(&[("Old Agent", vec![rule(json!({
+            "type": "publisher_name", "publisher": "Vendor", "display_name": "^Agent$",
+            "versions": { "from": "2.0", "before": "3.1" },
+        }))])]);
+        let version = |version: &str| matched(&engine, "Agent", "Vendor", version, &AppIdentity::default()).is_some();
+
+        assert!(!version("1.9.9"));
+        assert!(version("2"));
+        assert!(version("2.0.0.0"));
+        assert!(version("3.0.17-beta"));
+        assert!(!version("3.1"));
+        assert!(!version("10.0"));
+        // A range cannot vouch for an app that reports no usable version
+        assert!(!version(""));
+        assert!(!version("unknown"));
+    }
+
+    #[test]
+    fn open_ended_ranges_bound_one_side_only() {
+        let engine = engine(&[
+            ("Legacy", vec![rule(json!({
+                "type": "publisher_name", "publisher": "Vendor", "display_name": "^Legacy$", "versions": { "before": "5" },
+            }))]),
+            ("Current", vec![rule(json!({
+                "type": "publisher_name", "publisher": "Vendor", "display_name": "^Current$", "versions": { "from": "5" },
+            }))]),
+        ]);
+        let version = |name: &str, version: &str| matched(&engine, name, "Vendor", version, &AppIdentity::default()).is_some();
+
+        assert!(version("Legacy", "0.1"));
+        assert!(!version("Legacy", "5.0"));
+        assert!(!version("Current", "4.99"));
+        assert!(version("Current", "123.4"));
+    }
+
+    #[test]
+    fn rejects_unusable_rules() {
+        let invalid = [
+            json!({ "type": "publisher_name", "publisher": "Vendor", "display_name": "^App$", "versions": { "from": "3", "before": "3.0" } }),
+            json!({ "type": "publisher_name", "publisher": "Vendor", "display_name": "^App$", "versions": { "from": "latest" } }),
+            json!({ "type": "publisher_name", "publisher": " , ", "display_name": "^App$" }),
+            json!({ "type": "publisher_name", "publisher": "Vendor", "display_name": "(" }),
+            json!({ "type": "package_family", "family_name": "Microsoft.BingWeather" }),
+            json!({ "type": "msi_product_code", "code": "not-a-guid" }),
+            json!({ "type": "msi_upgrade_code", "code": "{12345678-1234-1234-1234-12345678}" }),
+            json!({ "type": "msi_product_code", "code": "{12345678-1234-1234-1234-123456789ABC}", "confidence": 1.5 }),
+        ];
+
+        for value in invalid {
+            assert!(rule(value.clone()).validate().is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn normalizes_msi_codes_and_publishers() {
+        assert_eq!(
+            normalize_guid(" 6b8ec2e8-4c53-4f0a-8c1e-2a6b3c4d5e6f ").unwrap(),
+            "{6B8EC2E8-4C53-4F0A-8C1E-2A6B3C4D5E6F}"
+        );
+        assert_eq!(normalize_publisher("McAfee, LLC"), normalize_publisher("mcafee  LLC."));
+        assert_ne!(normalize_publisher("HP Inc."), normalize_publisher("HP"));
+
+        let engine = engine(&[("Agent", vec![rule(json!({
+            "type": "msi_product_code", "code": "{6B8EC2E8-4C53-4F0A-8C1E-2A6B3C4D5E6F}",
+        }))])]);
+        let product_code = |code: &str| AppIdentity { product_code: Some(code.to_string()), ..Default::default() };
+
+        assert!(matched(&engine, "", "", "", &product_code("6b8ec2e8-4c53-4f0a-8c1e-2a6b3c4d5e6f")).is_some());
+        assert!(matched(&engine, "", "", "", &product_code("{6b8ec2e8-4c53-4f0a-8c1e-2a6b3c4d5e6f}")).is_some());
+        assert!(matched(&engine, "", "", "", &product_code("{6B8EC2E8-4C53-4F0A-8C1E-2A6B3C4D5E60}")).is_none());
+        assert!(matched(&engine, "", "", "", &product_code("garbage")).is_none());
+
+        let hp = hp_support_assistant();
+        assert!(matched(&hp, " HP Support Assistant ", "hp inc", "", &AppIdentity::default()).is_some());
+        assert!(matched(&hp, "hp support assistant", "HP  Inc.", "", &AppIdentity::default()).is_some());
+    }
+
+    #[test]
+    fn each_rule_type_reports_its_kind_and_default_confidence() {
+        let engine = engine(&[
+            ("Weather", vec![rule(json!({ "type": "package_family", "family_name": "Microsoft.BingWeather_8wekyb3d8bbwe" }))]),
+            ("Product", vec![rule(json!({ "type": "msi_product_code", "code": "{11111111-2222-3333-4444-555555555555}" }))]),
+            ("Upgrade", vec![rule(json!({ "type": "msi_upgrade_code", "code": "{AAAAAAAA-BBBB-CCCC-DDDD-EEEEEEEEEEEE}" }))]),
+            ("Publisher", vec![rule(json!({ "type": "publisher_name", "publisher": "Vendor", "display_name": "^Agent" }))]),
+        ]);
+        let cases = [
+            (AppIdentity { package_family_name: Some("microsoft.bingweather_8wekyb3d8bbwe".to_string()), ..Default::default() }, "Weather", MatchKind::PackageFamily, 1.0),
+            (AppIdentity { product_code: Some("{11111111-2222-3333-4444-555555555555}".to_string()), ..Default::default() }, "Product", MatchKind::MsiProductCode, 1.0),
+            (AppIdentity { upgrade_code: Some("{aaaaaaaa-bbbb-cccc-dddd-eeeeeeeeeeee}".to_string()), ..Default::default() }, "Upgrade", MatchKind::MsiUpgradeCode, 0.95),
+        ];
+
+        for (identity, definition, kind, confidence) in cases {
+            let found = matched(&engine, "Something", "Someone", "1.0", &identity).unwrap();
+            assert_eq!((found.definition.as_str(), found.kind, found.confidence), (definition, kind, confidence));
+        }
+        let found = matched(&engine, "Agent Pro", "Vendor", "1.0", &AppIdentity::default()).unwrap();
+        assert_eq!((found.definition.as_str(), found.kind, found.confidence), ("Publisher", MatchKind::PublisherName, 0.85));
+        assert_eq!(found.rule, "publisher \"Vendor\" with name /^Agent/");
+
+        // Family names are not matched by prefix or with another publisher id
+        let family = |name: &str| AppIdentity { package_family_name: Some(name.to_string()), ..Default::default() };
+        assert!(matched(&engine, "", "", "", &family("Microsoft.BingWeather_8wekyb3d8bbwe2")).is_none());
+        assert!(matched(&engine, "", "", "", &family("Microsoft.BingWeather_0123456789abc")).is_none());
+        // A product code does not stand in for an upgrade code
+        assert!(matched(&engine, "", "", "", &AppIdentity {
+            product_code: Some("{AAAAAAAA-BBBB-CCCC-DDDD-EEEEEEEEEEEE}".to_string()),
+            ..Default::default()
+        }).is_none());
+    }
+
+    #[test]
+    fn best_match_prefers_the_most_confident_rule() {
+        let identity = AppIdentity { upgrade_code: Some("{AAAAAAAA-BBBB-CCCC-DDDD-EEEEEEEEEEEE}".to_string()), ..Default::default() };
+        let engine = engine(&[
+            ("By Name", vec![rule(json!({ "type": "publisher_name", "publisher": "Vendor", "display_name": "Agent" }))]),
+            ("By Upgrade Code", vec![rule(json!({ "type": "msi_upgrade_code", "code": "{AAAAAAAA-BBBB-CCCC-DDDD-EEEEEEEEEEEE}" }))]),
+            ("Tuned Down", vec![rule(json!({ "type": "publisher_name", "publisher": "Vendor", "display_name": "^Agent$", "confidence": 0.5 }))]),
+            ("Tied", vec![rule(json!({ "type": "msi_upgrade_code", "code": "{AAAAAAAA-BBBB-CCCC-DDDD-EEEEEEEEEEEE}" }))]),
+        ]);
+
+        let found = matched(&engine, "Agent", "Vendor", "1.0", &identity).unwrap();
+        // Ties keep the definition added first
+        assert_eq!(found.definition, "By Upgrade Code");
+
+        let found = matched(&engine, "Agent", "Vendor", "1.0", &AppIdentity::default()).unwrap();
+        assert_eq!((found.definition.as_str(), found.confidence), ("By Name", 0.85));
+    }
+
+    #[test]
+    fn near_miss_names_do_not_match() {
+        let hp = hp_support_assistant();
+        for (display_name, publisher) in [
+            ("HP", "HP Inc."),
+            ("HP Support Assistant", "HP"),
+            ("HP Support Assistant Plus", "HP Inc."),
+            ("My HP Support Assistant", "HP Inc."),
+            ("HP Support Assistant", "HPE"),
+        ] {
+            assert!(matched(&hp, display_name, publisher, "9.0", &AppIdentity::default()).is_none(), "{} by {}", display_name, publisher);
+        }
+
+        // A third-party app called Mail is not the inbox Mail and Calendar package
+        let mail = engine(&[("Mail and Calendar", vec![rule(json!({
+            "type": "package_family", "family_name": "microsoft.windowscommunicationsapps_8wekyb3d8bbwe",
+        }))])]);
+        let other = AppIdentity { package_family_name: Some("Contoso.Mail_1a2b3c4d5e6f7".to_string()), ..Default::default() };
+        assert!(matched(&mail, "Mail", "Microsoft Corporation", "16005.1", &AppIdentity::default()).is_none());
+        assert!(matched(&mail, "Mail", "Contoso", "1.0", &other).is_none());
+    }
+}
@@ -0,0 +1,534 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use walkdir::WalkDir;
+
+use crate::path_resolver;
+use crate::platform;
+use crate::protection::{self, FileProtection};
+use crate::space_accounting::{self, SpaceAccounting, SpaceMeter};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum BrowserKind {
+    Chrome,
+    Edge,
+    Brave,
+    Firefox,
+}
+
+impl BrowserKind {
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            BrowserKind::Chrome => "Google Chrome",
+            BrowserKind::Edge => "Microsoft Edge",
+            BrowserKind::Brave => "Brave",
+            BrowserKind::Firefox => "Mozilla Firefox",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[allow(clippy::enum_variant_names)]
+pub enum BrowserCacheCategory {
+    HttpCache,
+    CodeCache,
+    GpuCache,
+    ServiceWorkerCache,
+}
+
+const CACHE_CATEGORIES: [BrowserCacheCategory; 4] = [
+    BrowserCacheCategory::HttpCache,
+    BrowserCacheCategory::CodeCache,
+    BrowserCacheCategory::GpuCache,
+    BrowserCacheCategory::ServiceWorkerCache,
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BrowserCacheUsage {
+    pub category: BrowserCacheCategory,
+    pub paths: Vec<PathBuf>,
+    pub size: u64,
+    pub file_count: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BrowserProfile {
+    /// Stable identifier, `<browser>:<profile directory name>`
+    pub id: String,
+    pub browser: BrowserKind,
+    pub name: String,
+    pub path: PathBuf,
+    /// Where the caches live; differs from `path` for Firefox's local profile folder
+    pub cache_path: PathBuf,
+    pub is_default: bool,
+    /// The browser is running and holds the profile lock
+    pub locked: bool,
+    pub caches: Vec<BrowserCacheUsage>,
+    pub total_cache_size: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BrowserScanResult {
+    pub profiles: Vec<BrowserProfile>,
+    pub total_cache_size: u64,
+    pub scan_duration_ms: u64,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BrowserCleanResult {
+    pub profiles_cleaned: Vec<String>,
+    /// Profiles left untouched because the browser was running
+    pub profiles_locked: Vec<String>,
+    pub files_removed: usize,
+    pub space_freed: u64,
+    pub errors: Vec<String>,
+    /// Measured counterpart of `space_freed`
+    #[serde(default)]
+    pub accounting: SpaceAccounting,
+}
+
+/// Where a browser keeps its profiles on this machine
+struct BrowserInstallation {
+    kind: BrowserKind,
+    /// Chromium "User Data" directory or Firefox application directory
+    data_directory: String,
+    /// Separate directory holding the profiles' caches, e.g. Firefox's local application directory
+    local_directory: Option<String>,
+}
+
+impl BrowserInstallation {
+    fn new(kind: BrowserKind, data_directory: &str, local_directory: Option<&str>) -> Self {
+        Self { kind, data_directory: data_directory.to_string(), local_directory: local_directory.map(str::to_string) }
+    }
+}
+
+/// Profile roots of the supported browsers on Windows
+#[cfg(windows)]
+fn installations() -> Vec<BrowserInstallation> {
+    vec![
+        BrowserInstallation::new(BrowserKind::Chrome, r"%LOCALAPPDATA%\Google\Chrome\User Data", None),
+        BrowserInstallation::new(BrowserKind::Edge, r"%LOCALAPPDATA%\Microsoft\Edge\User Data", None),
+        BrowserInstallation::new(BrowserKind::Brave, r"%LOCALAPPDATA%\BraveSoftware\Brave-Browser\User Data", None),
+        BrowserInstallation::new(BrowserKind::Firefox, r"%APPDATA%\Mozilla\Firefox", Some(r"%LOCALAPPDATA%\Mozilla\Firefox")),
+    ]
+}
+
+/// Profile roots of the supported browsers on Linux, caches kept under `~/.cache`
+#[cfg(not(windows))]
+fn installations() -> Vec<BrowserInstallation> {
+    vec![
+        BrowserInstallation::new(BrowserKind::Chrome, "%HOME%/.config/google-chrome", Some("%HOME%/.cache/google-chrome")),
+        BrowserInstallation::new(BrowserKind::Edge, "%HOME%/.config/microsoft-edge", Some("%HOME%/.cache/microsoft-edge")),
+        BrowserInstallation::new(BrowserKind::Brave, "%HOME%/.config/BraveSoftware/Brave-Browser", Some("%HOME%/.cache/BraveSoftware/Brave-Browser")),
+        BrowserInstallation::new(BrowserKind::Firefox, "%HOME%/.mozilla/firefox", Some("%HOME%/.cache/mozilla/firefox")),
+    ]
+}
+
+pub struct BrowserManager {
+    installations: Vec<BrowserInstallation>,
+    /// Holds the protection policy cache cleanup honors
+    backup_directory: PathBuf,
+}
+
+impl BrowserManager {
+    pub fn new(backup_dir: PathBuf) -> Self {
+        Self { installations: installations(), backup_directory: backup_dir }
+    }
+
+    /// Discover every browser profile and measure its caches
+    pub async fn scan_profiles(&self) -> Result<BrowserScanResult> {
+        let start_time = std::time::Instant::now();
+        let mut result = BrowserScanResult {
+            profiles: Vec::new(),
+            total_cache_size: 0,
+            scan_duration_ms: 0,
+            errors: Vec::new(),
+        };
+
+        info!("Starting browser profile scan");
+
+        for mut profile in self.discover_profiles(&mut result.errors) {
+            profile.caches = CACHE_CATEGORIES
+                .iter()
+                .map(|category| measure_cache(*category, cache_directories(&profile, *category)))
+                .collect();
+            profile.total_cache_size = profile.caches.iter().map(|cache| cache.size).sum();
+            result.total_cache_size += profile.total_cache_size;
+            result.profiles.push(profile);
+        }
+
+        result.scan_duration_ms = start_time.elapsed().as_millis() as u64;
+
+        info!("Browser scan completed: {} profiles, {} bytes of cache, {}ms",
+              result.profiles.len(), result.total_cache_size, result.scan_duration_ms);
+
+        Ok(result)
+    }
+
+    /// Empty the selected cache categories of the given profiles, skipping running browsers
+    pub async fn clean_caches(&self, profile_ids: Vec<String>, categories: Vec<BrowserCacheCategory>) -> Result<BrowserCleanResult> {
+        let mut result = BrowserCleanResult {
+            profiles_cleaned: Vec::new(),
+            profiles_locked: Vec::new(),
+            files_removed: 0,
+            space_freed: 0,
+            errors: Vec::new(),
+            accounting: SpaceAccounting::default(),
+        };
+
+        let profiles: HashMap<String, BrowserProfile> = self.discover_profiles(&mut result.errors)
+            .into_iter()
+            .map(|profile| (profile.id.clone(), profile))
+            .collect();
+
+        let mut meter = SpaceMeter::start(profiles.values().map(|profile| &profile.path));
+        let mut directories = Vec::new();
+
+        for profile_id in profile_ids {
+            let Some(profile) = profiles.get(&profile_id) else {
+                result.errors.push(format!("Browser profile not found: {}", profile_id));
+                continue;
+            };
+
+            // Discovery just re-checked the lock; a running browser rewrites its caches underneath us
+            if profile.locked {
+                warn!("Refusing to clean {}: {} is running", profile.id, profile.browser.display_name());
+                result.profiles_locked.push(profile.id.clone());
+                continue;
+            }
+
+            for category in &categories {
+                directories.extend(cache_directories(profile, *category));
+            }
+            result.profiles_cleaned.push(profile.id.clone());
+        }
+
+        // Walking and deleting is blocking I/O, keep it off the async runtime
+        let policy_path = self.backup_directory.join(protection::POLICY_FILE);
+        let result = tokio::task::spawn_blocking(move || {
+            let protection = FileProtection::load(policy_path, platform::current().critical_directories);
+            for directory in &directories {
+                empty_directory(directory, &protection, &mut result, &mut meter);
+            }
+            result.accounting = meter.finish();
+            result
+        }).await?;
+
+        info!("Browser cache cleanup completed: {} files removed, {} bytes estimated, {} bytes measured",
+              result.files_removed, result.space_freed, result.accounting.measured_bytes);
+
+        Ok(result)
+    }
+
+    fn discover_profiles(&self, errors: &mut Vec<String>) -> Vec<BrowserProfile> {
+        let mut profiles = Vec::new();
+
+        for installation in &self.installations {
+            for data_directory in path_resolver::resolve_path_pattern(&installation.data_directory) {
+                let local_directory = installation.local_directory.as_deref()
+                    .and_then(|pattern| path_resolver::resolve_path_pattern(pattern).into_iter().next());
+                let discovered = match installation.kind {
+                    BrowserKind::Firefox => firefox_profiles(&data_directory, local_directory.as_deref()),
+                    kind => chromium_profiles(kind, &data_directory, local_directory.as_deref()),
+                };
+
+                match discovered {
+                    Ok(found) => profiles.extend(found),
+                    Err(e) => errors.push(format!("Failed to read {} profiles in {}: {}",
+                                                  installation.kind.display_name(), data_directory.display(), e)),
+                }
+            }
+        }
+
+        profiles
+    }
+}
+
+/// Chromium profiles from `Local State`, falling back to the profile folder layout
+fn chromium_profiles(kind: BrowserKind, user_data: &Path, cache_root: Option<&Path>) -> Result<Vec<BrowserProfile>> {
+    let local_state: serde_json::Value = match std::fs::read(user_data.join("Local State")) {
+        Ok(content) => serde_json::from_slice(&content)?,
+        Err(_) => serde_json::Value::Null,
+    };
+    let last_used = local_state["profile"]["last_used"].as_str().unwrap_or("Default").to_string();
+
+    let mut directories: Vec<(String, String)> = local_state["profile"]["info_cache"]
+        .as_object()
+        .map(|cache| cache.iter()
+            .map(|(directory, info)| (directory.clone(), info["name"].as_str().unwrap_or(directory).to_string()))
+            .collect())
+        .unwrap_or_default();
+
+    if directories.is_empty() {
+        for entry in std::fs::read_dir(user_data)?.filter_map(|e| e.ok()) {
+            let directory = entry.file_name().to_string_lossy().to_string();
+            if (directory == "Default" || directory.starts_with("Profile ")) && entry.path().join("Preferences").is_file() {
+                directories.push((directory.clone(), directory));
+            }
+        }
+    }
+    directories.sort();
+
+    // One lock covers every profile of the user data directory
+    let locked = chromium_locked(user_data);
+
+    Ok(directories
+        .into_iter()
+        .map(|(directory, name)| (user_data.join(&directory), directory, name))
+        .filter(|(path, _, _)| path.is_dir())
+        .map(|(path, directory, name)| BrowserProfile {
+            id: format!("{:?}:{}", kind, directory),
+            browser: kind,
+            name,
+            cache_path: cache_root.map(|root| root.join(&directory)).unwrap_or_else(|| path.clone()),
+            path,
+            is_default: directory == last_used,
+            locked,
+            caches: Vec::new(),
+            total_cache_size: 0,
+        })
+        .collect())
+}
+
+/// Firefox profiles from `profiles.ini`
+fn firefox_profiles(app_directory: &Path, local_directory: Option<&Path>) -> Result<Vec<BrowserProfile>> {
+    let content = std::fs::read_to_string(app_directory.join("profiles.ini"))?;
+    let sections = parse_ini(&content);
+
+    // Newer releases record the default per installation in [Install...] sections
+    let install_defaults: Vec<&str> = sections.iter()
+        .filter(|(section, _)| section.starts_with("Install"))
+        .filter_map(|(_, values)| values.get("Default").map(|value| value.as_str()))
+        .collect();
+
+    let mut profiles = Vec::new();
+    for (section, values) in &sections {
+        if !section.starts_with("Profile") {
+            continue;
+        }
+        let Some(relative) = values.get("Path") else { continue };
+
+        let is_relative = values.get("IsRelative").map(|value| value == "1").unwrap_or(true);
+        let (path, cache_path) = if is_relative {
+            let cache_root = local_directory.unwrap_or(app_directory);
+            let native: PathBuf = relative.split('/').collect();
+            (app_directory.join(&native), cache_root.join(&native))
+        } else {
+            (PathBuf::from(relative), PathBuf::from(relative))
+        };
+        if !path.is_dir() {
+            continue;
+        }
+
+        let directory = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+        let is_default = if install_defaults.is_empty() {
+            values.get("Default").map(|value| value == "1").unwrap_or(false)
+        } else {
+            install_defaults.contains(&relative.as_str())
+        };
+
+        profiles.push(BrowserProfile {
+            id: format!("{:?}:{}", BrowserKind::Firefox, directory),
+            browser: BrowserKind::Firefox,
+            name: values.get("Name").cloned().unwrap_or_else(|| directory.clone()),
+            locked: firefox_locked(&path),
+            path,
+            cache_path,
+            is_default,
+            caches: Vec::new(),
+            total_cache_size: 0,
+        });
+    }
+
+    Ok(profiles)
+}
+
+/// Minimal INI reader: section name to key/value pairs, in file order
+fn parse_ini(content: &str) -> Vec<(String, HashMap<String, String>)> {
+    let mut sections: Vec<(String, HashMap<String, String>)> = Vec::new();
+
+    for line in content.lines().map(|line| line.trim()) {
+        if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+            continue;
+        }
+        if let Some(section) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+            sections.push((section.to_string(), HashMap::new()));
+        } else if let (Some((key, value)), Some((_, values))) = (line.split_once('='), sections.last_mut()) {
+            values.insert(key.trim().to_string(), value.trim().to_string());
+        }
+    }
+
+    sections
+}
+
+/// Existing cache directories of one category
+fn cache_directories(profile: &BrowserProfile, category: BrowserCacheCategory) -> Vec<PathBuf> {
+    let patterns: &[&str] = match (profile.browser, category) {
+        (BrowserKind::Firefox, BrowserCacheCategory::HttpCache) => &["cache2"],
+        (BrowserKind::Firefox, BrowserCacheCategory::CodeCache) => &["startupCache"],
+        (BrowserKind::Firefox, BrowserCacheCategory::GpuCache) => &["shader-cache"],
+        (BrowserKind::Firefox, BrowserCacheCategory::ServiceWorkerCache) => &[r"storage\default\*\cache"],
+        (_, BrowserCacheCategory::HttpCache) => &["Cache"],
+        (_, BrowserCacheCategory::CodeCache) => &["Code Cache"],
+        (_, BrowserCacheCategory::GpuCache) => &["GPUCache", "DawnCache"],
+        (_, BrowserCacheCategory::ServiceWorkerCache) => &[r"Service Worker\CacheStorage", r"Service Worker\ScriptCache"],
+    };
+
+    // Cache API storage sits with the profile, as do Chromium's GPU caches; the rest in the cache directory
+    let with_profile = match profile.browser {
+        BrowserKind::Firefox => category == BrowserCacheCategory::ServiceWorkerCache,
+        _ => matches!(category, BrowserCacheCategory::GpuCache | BrowserCacheCategory::ServiceWorkerCache),
+    };
+    let base = if with_profile {
+        &profile.path
+    } else {
+        &profile.cache_path
+    };
+
+    patterns
+        .iter()
+        .flat_map(|pattern| path_resolver::resolve_path_pattern(&format!("{}\\{}", base.display(), pattern)))
+        .filter(|path| path.is_dir())
+        .collect()
+}
+
+fn measure_cache(category: BrowserCacheCategory, paths: Vec<PathBuf>) -> BrowserCacheUsage {
+    let mut usage = BrowserCacheUsage { category, paths, size: 0, file_count: 0 };
+
+    for path in &usage.paths {
+        for entry in WalkDir::new(path).follow_links(false).into_iter().filter_map(|e| e.ok()) {
+            if entry.file_type().is_file() {
+                usage.size += entry.metadata().map(|m| m.len()).unwrap_or(0);
+                usage.file_count += 1;
+            }
+        }
+    }
+
+    usage
+}
+
+/// Delete everything below a cache directory that `protection` allows, keeping the directory itself
+fn empty_directory(directory: &Path, protection: &FileProtection, result: &mut BrowserCleanResult, meter: &mut SpaceMeter) {
+    for entry in WalkDir::new(directory).min_depth(1).contents_first(true).follow_links(false).into_iter().filter_map(|e| e.ok()) {
+        let path = entry.path();
+        let protected = protection.check(path);
+        if entry.file_type().is_dir() {
+            // Directories that still hold files we failed or refused to delete stay put
+            if protected.is_ok() {
+                let _ = std::fs::remove_dir(path);
+            }
+            continue;
+        }
+        if let Err(refusal) = protected {
+            result.errors.push(format!("Skipped {}: {}", path.display(), refusal));
+            continue;
+        }
+
+        let allocation = space_accounting::file_allocation(path).ok();
+        match std::fs::remove_file(path) {
+            Ok(_) => {
+                result.files_removed += 1;
+                if let Some(allocation) = allocation {
+                    result.space_freed += allocation.logical;
+                    meter.record(allocation);
+                }
+            }
+            Err(e) => result.errors.push(format!("Failed to delete {}: {}", path.display(), e)),
+        }
+    }
+}
+
+/// Chromium holds `lockfile` open exclusively while running
+#[cfg(windows)]
+fn chromium_locked(user_data: &Path) -> bool {
+    file_held_open(&user_data.join("lockfile"))
+}
+
+/// Chromium points `SingletonLock` at `<host>-<pid>` while running
+#[cfg(not(windows))]
+fn chromium_locked(user_data: &Path) -> bool {
+    symlink_owner_alive(&user_data.join("SingletonLock"), '-')
+}
+
+/// Firefox holds `parent.lock` open exclusively while running
+#[cfg(windows)]
+fn firefox_locked(profile: &Path) -> bool {
+    file_held_open(&profile.join("parent.lock"))
+}
+
+/// Firefox points `lock` at `<address>:+<pid>` while running
+#[cfg(not(windows))]
+fn firefox_locked(profile: &Path) -> bool {
+    symlink_owner_alive(&profile.join("lock"), '+')
+}
+
+/// A lock file that exists but cannot be opened is held by a running process
+#[cfg(windows)]
+fn file_held_open(lock_file: &Path) -> bool {
+    match std::fs::OpenOptions::new().read(true).write(true).open(lock_file) {
+        Ok(_) => false,
+        Err(e) => e.kind() != std::io::ErrorKind::NotFound,
+    }
+}
+
+#[cfg(not(windows))]
+fn symlink_owner_alive(lock_link: &Path, separator: char) -> bool {
+    let Ok(target) = std::fs::read_link(lock_link) else {
+        return lock_link.exists();
+    };
+
+    match target.to_string_lossy().rsplit(separator).next().and_then(|pid| pid.parse::<u32>().ok()) {
+        Some(pid) => Path::new("/proc").join(pid.to_string()).exists(),
+        // Unknown format: err on the side of leaving the profile alone
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protection::{ProtectionKind, ProtectionPolicy, ProtectionRule};
+
+    #[test]
+    fn empties_caches_except_what_the_policy_protects() {
+        let directory = std::env::temp_dir().join(format!("browser-test-{}", uuid::Uuid::new_v4()));
+        let cache = directory.join("Cache").join("Cache_Data");
+        for file in ["data_0", "f_000001", "index-dir/the-real-index", "offline/page.html"] {
+            std::fs::create_dir_all(cache.join(file).parent().unwrap()).unwrap();
+            std::fs::write(cache.join(file), b"cached").unwrap();
+        }
+        let mut protection = FileProtection::load(directory.join(protection::POLICY_FILE), Vec::new());
+        protection.update(ProtectionPolicy {
+            rules: vec![ProtectionRule {
+                id: "offline-pages".to_string(),
+                kind: ProtectionKind::UserFolder,
+                description: "Pages saved for offline reading".to_string(),
+                locations: vec![cache.join("offline").to_string_lossy().to_string()],
+                extensions: Vec::new(),
+            }],
+            allow_list: Vec::new(),
+        }).unwrap();
+        let mut result = BrowserCleanResult {
+            profiles_cleaned: Vec::new(),
+            profiles_locked: Vec::new(),
+            files_removed: 0,
+            space_freed: 0,
+            errors: Vec::new(),
+            accounting: SpaceAccounting::default(),
+        };
+        let mut meter = SpaceMeter::start([&directory]);
+
+        empty_directory(&cache, &protection, &mut result, &mut meter);
+
+        assert_eq!(result.files_removed, 3);
+        assert_eq!(result.errors.len(), 1, "{:?}", result.errors);
+        assert!(result.errors[0].contains("offline-pages"), "{}", result.errors[0]);
+        assert!(cache.join("offline").join("page.html").exists());
+        assert!(!cache.join("index-dir").exists());
+        assert!(cache.exists());
+        let _ = std::fs::remove_dir_all(&directory);
+    }
+}
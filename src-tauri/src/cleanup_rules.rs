@@ -0,0 +1,294 @@
+use std::path::{Path, PathBuf};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::file_manager::FileInfo;
+use crate::path_resolver;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum RiskLevel {
+    Low,
+    Medium,
+    High,
+}
+
+/// Declarative description of something safe to clean
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CleanupRule {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    /// Target directories; `%VAR%` references and `*`/`?` wildcards are expanded
+    pub locations: Vec<String>,
+    /// Glob matched against file names; all files when absent
+    pub file_pattern: Option<String>,
+    /// Only files not modified for this many days are candidates
+    pub min_age_days: u32,
+    pub recursive: bool,
+    pub risk: RiskLevel,
+    pub enabled: bool,
+    #[serde(default)]
+    pub builtin: bool,
+}
+
+impl CleanupRule {
+    /// Existing directories this rule targets on this machine
+    pub fn resolve_locations(&self) -> Vec<PathBuf> {
+        self.locations
+            .iter()
+            .flat_map(|location| path_resolver::resolve_path_pattern(location))
+            .filter(|path| path.is_dir())
+            .collect()
+    }
+
+    /// Check a collected file against the rule's pattern and age threshold
+    pub fn matches(&self, file: &FileInfo, now: DateTime<Utc>) -> bool {
+        if file.modified > now - chrono::Duration::days(self.min_age_days as i64) {
+            return false;
+        }
+
+        match &self.file_pattern {
+            Some(pattern) => file.path.file_name()
+                .map(|name| path_resolver::glob_match(pattern, &name.to_string_lossy()))
+                .unwrap_or(false),
+            None => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleScanMatch {
+    pub rule_id: String,
+    pub rule_name: String,
+    pub risk: RiskLevel,
+    pub locations: Vec<PathBuf>,
+    pub files: Vec<FileInfo>,
+    pub total_size: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleScanResult {
+    pub matches: Vec<RuleScanMatch>,
+    pub total_files: usize,
+    pub total_size: u64,
+    pub scan_duration_ms: u64,
+    pub errors: Vec<String>,
+}
+
+/// Built-in rules plus user rules persisted next to the backups
+pub struct CleanupRuleSet {
+    builtin: Vec<CleanupRule>,
+    user: Vec<CleanupRule>,
+    user_rules_path: PathBuf,
+}
+
+impl CleanupRuleSet {
+    pub fn load(user_rules_path: PathBuf) -> Self {
+        let user = match std::fs::read(&user_rules_path) {
+            Ok(content) => serde_json::from_slice::<Vec<CleanupRule>>(&content).unwrap_or_else(|e| {
+                warn!("Ignoring unreadable cleanup rules {}: {}", user_rules_path.display(), e);
+                Vec::new()
+            }),
+            Err(_) => Vec::new(),
+        };
+
+        Self {
+            builtin: builtin_rules(),
+            user,
+            user_rules_path,
+        }
+    }
+
+    /// All rules, built-in first
+    pub fn rules(&self) -> Vec<CleanupRule> {
+        self.builtin.iter().chain(self.user.iter()).cloned().collect()
+    }
+
+    /// Look up rules by id, or every enabled rule at or below `max_risk` when no ids are given
+    pub fn select(&self, rule_ids: Option<&[String]>, max_risk: RiskLevel) -> Vec<CleanupRule> {
+        match rule_ids {
+            Some(ids) => self.rules().into_iter().filter(|rule| ids.contains(&rule.id)).collect(),
+            None => self.rules().into_iter().filter(|rule| rule.enabled && rule.risk <= max_risk).collect(),
+        }
+    }
+
+    /// Add or replace a user rule and persist the user rule file
+    pub fn save_user_rule(&mut self, mut rule: CleanupRule) -> Result<()> {
+        validate_rule(&rule)?;
+        if self.builtin.iter().any(|builtin| builtin.id == rule.id) {
+            return Err(anyhow!("Rule id {} is reserved by a built-in rule", rule.id));
+        }
+
+        rule.builtin = false;
+        match self.user.iter_mut().find(|existing| existing.id == rule.id) {
+            Some(existing) => *existing = rule,
+            None => self.user.push(rule),
+        }
+
+        self.persist()
+    }
+
+    /// Remove a user rule and persist the user rule file
+    pub fn remove_user_rule(&mut self, rule_id: &str) -> Result<()> {
+        let before = self.user.len();
+        self.user.retain(|rule| rule.id != rule_id);
+        if self.user.len() == before {
+            return Err(anyhow!("User rule not found: {}", rule_id));
+        }
+
+        self.persist()
+    }
+
+    fn persist(&self) -> Result<()> {
+        if let Some(parent) = self.user_rules_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&self.user_rules_path, serde_json::to_vec_pretty(&self.user)?)?;
+        Ok(())
+    }
+}
+
+/// Reject rules that are incomplete or aim at whole drives or profile roots
+fn validate_rule(rule: &CleanupRule) -> Result<()> {
+    if rule.id.trim().is_empty() || rule.name.trim().is_empty() {
+        return Err(anyhow!("Rule id and name are required"));
+    }
+    if rule.locations.is_empty() {
+        return Err(anyhow!("Rule {} has no locations", rule.id));
+    }
+
+    for location in &rule.locations {
+        let expanded = path_resolver::expand_environment_strings(location);
+        let fixed_depth = Path::new(&expanded)
+            .components()
+            .take_while(|component| !path_resolver::has_wildcards(&component.as_os_str().to_string_lossy()))
+            .filter(|component| matches!(component, std::path::Component::Normal(_)))
+            .count();
+        if fixed_depth < 2 {
+            return Err(anyhow!("Location {} of rule {} is too broad", location, rule.id));
+        }
+    }
+
+    if let Some(pattern) = &rule.file_pattern {
+        if pattern.contains('/') || pattern.contains('\\') {
+            return Err(anyhow!("File pattern of rule {} must not contain path separators", rule.id));
+        }
+    }
+
+    Ok(())
+}
+
+fn rule(
+    id: &str,
+    name: &str,
+    description: &str,
+    locations: &[&str],
+    file_pattern: Option<&str>,
+    min_age_days: u32,
+    risk: RiskLevel,
+) -> CleanupRule {
+    CleanupRule {
+        id: id.to_string(),
+        name: name.to_string(),
+        description: description.to_string(),
+        locations: locations.iter().map(|location| location.to_string()).collect(),
+        file_pattern: file_pattern.map(|pattern| pattern.to_string()),
+        min_age_days,
+        recursive: true,
+        risk,
+        enabled: true,
+        builtin: true,
+    }
+}
+
+/// Rules shipped with the optimizer
+#[cfg(windows)]
+fn builtin_rules() -> Vec<CleanupRule> {
+    let mut rules = vec![
+        rule("user-temp", "User temporary files",
+             "Files left behind in the user's temp directory",
+             &["%TEMP%"], None, 7, RiskLevel::Low),
+        rule("windows-temp", "Windows temporary files",
+             "Files left behind in the system temp directory",
+             &[r"%SystemRoot%\Temp"], None, 7, RiskLevel::Low),
+        rule("prefetch", "Prefetch traces",
+             "Launch traces Windows rebuilds on demand; removing them briefly slows application start",
+             &[r"%SystemRoot%\Prefetch"], Some("*.pf"), 7, RiskLevel::Medium),
+        rule("windows-update-downloads", "Windows Update downloads",
+             "Update packages that were already downloaded and installed",
+             &[r"%SystemRoot%\SoftwareDistribution\Download"], None, 7, RiskLevel::Medium),
+        rule("delivery-optimization", "Delivery Optimization cache",
+             "Update content cached for peer-to-peer distribution",
+             &[r"%SystemRoot%\ServiceProfiles\NetworkService\AppData\Local\Microsoft\Windows\DeliveryOptimization\Cache"],
+             None, 3, RiskLevel::Low),
+        rule("internet-cache", "Windows internet cache",
+             "Legacy WinINet cache used by Explorer and embedded browsers",
+             &[r"%LOCALAPPDATA%\Microsoft\Windows\INetCache"], None, 7, RiskLevel::Low),
+        rule("shader-cache", "DirectX shader cache",
+             "Compiled shaders that are regenerated when needed",
+             &[r"%LOCALAPPDATA%\D3DSCache"], None, 7, RiskLevel::Low),
+        rule("crash-dumps", "Crash dumps",
+             "Application and system crash dumps, only needed for diagnosing crashes",
+             &[r"%LOCALAPPDATA%\CrashDumps", r"%SystemRoot%\Minidump"], Some("*.dmp"), 14, RiskLevel::Medium),
+        rule("error-reports", "Windows Error Reporting archives",
+             "Reports already queued for or sent to Microsoft",
+             &[r"%ProgramData%\Microsoft\Windows\WER\ReportArchive", r"%ProgramData%\Microsoft\Windows\WER\ReportQueue",
+               r"%LOCALAPPDATA%\Microsoft\Windows\WER\ReportArchive"],
+             None, 14, RiskLevel::Low),
+        rule("system-logs", "Servicing log files",
+             "CBS and DISM logs kept after servicing operations",
+             &[r"%SystemRoot%\Logs\CBS", r"%SystemRoot%\Logs\DISM"], Some("*.log"), 30, RiskLevel::Medium),
+        rule("teams-cache", "Microsoft Teams cache",
+             "Chromium caches of classic and new Teams",
+             &[r"%APPDATA%\Microsoft\Teams\Cache", r"%APPDATA%\Microsoft\Teams\Code Cache",
+               r"%APPDATA%\Microsoft\Teams\GPUCache", r"%APPDATA%\Microsoft\Teams\Service Worker\CacheStorage",
+               r"%LOCALAPPDATA%\Packages\MSTeams_*\LocalCache\Microsoft\MSTeams\EBWebView\*\Cache",
+               r"%LOCALAPPDATA%\Packages\MSTeams_*\LocalCache\Microsoft\MSTeams\EBWebView\*\Code Cache"],
+             None, 2, RiskLevel::Low),
+        rule("slack-cache", "Slack cache",
+             "Chromium caches of the Slack desktop app",
+             &[r"%APPDATA%\Slack\Cache", r"%APPDATA%\Slack\Code Cache", r"%APPDATA%\Slack\GPUCache",
+               r"%APPDATA%\Slack\Service Worker\CacheStorage"],
+             None, 2, RiskLevel::Low),
+        rule("discord-cache", "Discord cache",
+             "Chromium caches of the Discord desktop app",
+             &[r"%APPDATA%\discord\Cache", r"%APPDATA%\discord\Code Cache", r"%APPDATA%\discord\GPUCache"],
+             None, 2, RiskLevel::Low),
+    ];
+
+    // The memory dump sits directly in the Windows directory, never recurse there
+    let mut memory_dump = rule("memory-dump", "Kernel memory dump",
+                               "Full memory dump written after a system crash",
+                               &["%SystemRoot%"], Some("MEMORY.DMP"), 14, RiskLevel::Medium);
+    memory_dump.recursive = false;
+    rules.push(memory_dump);
+
+    rules
+}
+
+/// Rules shipped with the optimizer
+#[cfg(not(windows))]
+fn builtin_rules() -> Vec<CleanupRule> {
+    vec![
+        rule("tmp", "Temporary files",
+             "Files left behind in /tmp, using the systemd-tmpfiles age limit",
+             &["/tmp"], None, 10, RiskLevel::Low),
+        rule("var-tmp", "Persistent temporary files",
+             "Files left behind in /var/tmp, which survives reboots",
+             &["/var/tmp"], None, 30, RiskLevel::Low),
+        rule("thumbnails", "Thumbnail cache",
+             "Image previews desktop environments regenerate when needed",
+             &["%HOME%/.cache/thumbnails"], None, 7, RiskLevel::Low),
+        rule("user-cache", "User cache",
+             "Application caches under ~/.cache; running applications may still use recent entries",
+             &["%HOME%/.cache"], None, 30, RiskLevel::Medium),
+        rule("journal", "Archived journal files",
+             "Rotated systemd journal files; the active journal is never matched",
+             &["/var/log/journal"], Some("*@*.journal*"), 30, RiskLevel::Medium),
+        rule("trash", "Trash",
+             "Files moved to the desktop trash and their trash info records",
+             &["%HOME%/.local/share/Trash/files", "%HOME%/.local/share/Trash/info"], None, 30, RiskLevel::Medium),
+    ]
+}
@@ -0,0 +1,335 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::future::Future;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::{Arc, OnceLock};
+use std::time::Instant;
+use anyhow::{anyhow, Result};
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+/// Invocations kept for the transcript; older ones are dropped
+const TRANSCRIPT_LIMIT: usize = 500;
+
+pub type CommandFuture<'a> = Pin<Box<dyn Future<Output = Result<CommandOutput>> + Send + 'a>>;
+
+/// A program and its arguments, handed to the OS as separate strings without a shell
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CommandLine {
+    pub program: String,
+    pub args: Vec<String>,
+    /// Readable form of an encoded PowerShell command, for transcripts and fakes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub script: Option<String>,
+}
+
+/// A value bound to a PowerShell script parameter
+#[derive(Debug, Clone, PartialEq)]
+pub enum PowerShellValue {
+    Text(String),
+    List(Vec<String>),
+}
+
+impl From<&str> for PowerShellValue {
+    fn from(value: &str) -> Self {
+        PowerShellValue::Text(value.to_string())
+    }
+}
+
+impl From<String> for PowerShellValue {
+    fn from(value: String) -> Self {
+        PowerShellValue::Text(value)
+    }
+}
+
+impl From<&Path> for PowerShellValue {
+    fn from(value: &Path) -> Self {
+        PowerShellValue::Text(value.to_string_lossy().to_string())
+    }
+}
+
+impl From<&[String]> for PowerShellValue {
+    fn from(values: &[String]) -> Self {
+        PowerShellValue::List(values.to_vec())
+    }
+}
+
+impl CommandLine {
+    pub fn new<I, S>(program: &str, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            program: program.to_string(),
+            args: args.into_iter().map(Into::into).collect(),
+            script: None,
+        }
+    }
+
+    /// Run `script` with its `param()` block bound to `parameters`.
+    /// Values travel as quoted literals inside `-EncodedCommand`, so nothing in them is parsed as code.
+    pub fn powershell(script: &str, parameters: &[(&str, PowerShellValue)]) -> Self {
+        let names: Vec<String> = parameters.iter().map(|(name, _)| format!("${}", name)).collect();
+        let bound: Vec<String> = parameters.iter().map(|(name, value)| {
+            debug_assert!(name.chars().all(|c| c.is_ascii_alphanumeric()), "invalid parameter name {}", name);
+            format!("-{} {}", name, powershell_literal(value))
+        }).collect();
+        let script = format!("& {{\nparam({})\n{}\n}} {}", names.join(", "), script.trim(), bound.join(" "));
+
+        let utf16: Vec<u8> = script.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        let encoded = base64::engine::general_purpose::STANDARD.encode(utf16);
+        Self {
+            program: "powershell.exe".to_string(),
+            args: ["-NoProfile", "-NonInteractive", "-ExecutionPolicy", "Bypass", "-EncodedCommand"]
+                .into_iter()
+                .map(String::from)
+                .chain([encoded])
+                .collect(),
+            script: Some(script),
+        }
+    }
+}
+
+impl fmt::Display for CommandLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.script {
+            Some(script) => write!(f, "{} {}", self.program, script),
+            None => write!(f, "{} {}", self.program, self.args.join(" ")),
+        }
+    }
+}
+
+/// Single-quoted PowerShell string; PowerShell also ends such strings at the typographic quotes
+pub fn quote_powershell(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('\'');
+    for c in value.chars() {
+        if matches!(c, '\'' | '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}') {
+            quoted.push(c);
+        }
+        quoted.push(c);
+    }
+    quoted.push('\'');
+    quoted
+}
+
+fn powershell_literal(value: &PowerShellValue) -> String {
+    match value {
+        PowerShellValue::Text(text) => quote_powershell(text),
+        PowerShellValue::List(items) => {
+            format!("@({})", items.iter().map(|item| quote_powershell(item)).collect::<Vec<_>>().join(", "))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CommandOutput {
+    /// None when the process was terminated without an exit code
+    pub exit_code: Option<i32>,
+    #[serde(default)]
+    pub stdout: String,
+    #[serde(default)]
+    pub stderr: String,
+}
+
+impl CommandOutput {
+    pub fn success(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+}
+
+/// One invocation as it was run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscriptEntry {
+    pub started: DateTime<Utc>,
+    pub duration_ms: u64,
+    pub command: CommandLine,
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+    /// Set when the process could not be started at all
+    pub error: Option<String>,
+}
+
+#[derive(Default)]
+pub struct Transcript {
+    entries: Mutex<VecDeque<TranscriptEntry>>,
+}
+
+impl Transcript {
+    fn record(&self, command: &CommandLine, started: DateTime<Utc>, elapsed: Instant, outcome: &Result<CommandOutput>) {
+        let (exit_code, stdout, stderr, error) = match outcome {
+            Ok(output) => (output.exit_code, output.stdout.clone(), output.stderr.clone(), None),
+            Err(e) => (None, String::new(), String::new(), Some(e.to_string())),
+        };
+        let mut entries = self.entries.lock();
+        if entries.len() == TRANSCRIPT_LIMIT {
+            entries.pop_front();
+        }
+        entries.push_back(TranscriptEntry {
+            started,
+            duration_ms: elapsed.elapsed().as_millis() as u64,
+            command: command.clone(),
+            exit_code,
+            stdout,
+            stderr,
+            error,
+        });
+    }
+
+    pub fn entries(&self) -> Vec<TranscriptEntry> {
+        self.entries.lock().iter().cloned().collect()
+    }
+}
+
+/// Runs external programs; every implementation keeps a transcript
+pub trait CommandRunner: Send + Sync {
+    fn run<'a>(&'a self, command: &'a CommandLine) -> CommandFuture<'a>;
+
+    fn transcript(&self) -> &Transcript;
+}
+
+impl<'r> dyn CommandRunner + 'r {
+    /// Stdout of a successful run; a non-zero exit becomes an error carrying stderr
+    pub async fn checked(&self, command: &CommandLine) -> Result<String> {
+        let output = self.run(command).await?;
+        if output.success() {
+            return Ok(output.stdout);
+        }
+        let message = if output.stderr.trim().is_empty() { output.stdout.trim() } else { output.stderr.trim() };
+        match output.exit_code {
+            Some(code) => Err(anyhow!("{} exited with code {}: {}", command.program, code, message)),
+            None => Err(anyhow!("{} was terminated: {}", command.program, message)),
+        }
+    }
+}
+
+/// The runner used throughout the app; debug builds replay `WINDOWS_OPTIMIZER_COMMAND_SCRIPT` instead when it is set
+pub fn shared() -> Arc<dyn CommandRunner> {
+    static SHARED: OnceLock<Arc<dyn CommandRunner>> = OnceLock::new();
+    SHARED.get_or_init(|| {
+        // A script reports success for commands that never ran, so release builds cannot be pointed at one
+        #[cfg(debug_assertions)]
+        if let Some(path) = std::env::var_os("WINDOWS_OPTIMIZER_COMMAND_SCRIPT") {
+            match ScriptedRunner::load(Path::new(&path)) {
+                Ok(runner) => return Arc::new(runner),
+                Err(e) => tracing::error!("Ignoring command script: {}", e),
+            }
+        }
+        Arc::new(SystemRunner::default())
+    }).clone()
+}
+
+/// Starts real processes
+#[derive(Default)]
+pub struct SystemRunner {
+    transcript: Transcript,
+}
+
+impl CommandRunner for SystemRunner {
+    fn run<'a>(&'a self, command: &'a CommandLine) -> CommandFuture<'a> {
+        Box::pin(async move {
+            let started = Utc::now();
+            let elapsed = Instant::now();
+            let outcome = tokio::process::Command::new(&command.program)
+                .args(&command.args)
+                .output()
+                .await
+                .map(|output| CommandOutput {
+                    exit_code: output.status.code(),
+                    stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+                    stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+                })
+                .map_err(|e| anyhow!("Cannot start {}: {}", command.program, e));
+            self.transcript.record(command, started, elapsed, &outcome);
+            outcome
+        })
+    }
+
+    fn transcript(&self) -> &Transcript {
+        &self.transcript
+    }
+}
+
+/// A canned reply for invocations of `program` whose readable form contains `contains`
+#[cfg(debug_assertions)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScriptedResponse {
+    pub program: String,
+    #[serde(default)]
+    pub contains: Option<String>,
+    #[serde(default)]
+    pub exit_code: i32,
+    #[serde(default)]
+    pub stdout: String,
+    #[serde(default)]
+    pub stderr: String,
+}
+
+#[cfg(debug_assertions)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CommandScript {
+    pub responses: Vec<ScriptedResponse>,
+}
+
+/// Answers from a script instead of running anything, so removal flows can be replayed on any OS.
+/// The first matching response wins; unmatched invocations fail. Only debug builds have it.
+#[cfg(debug_assertions)]
+#[derive(Default)]
+pub struct ScriptedRunner {
+    script: CommandScript,
+    transcript: Transcript,
+}
+
+#[cfg(debug_assertions)]
+impl ScriptedRunner {
+    pub fn new(script: CommandScript) -> Self {
+        Self { script, transcript: Transcript::default() }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("Cannot read command script {}: {}", path.display(), e))?;
+        let script = serde_json::from_str(&content)
+            .map_err(|e| anyhow!("Invalid command script {}: {}", path.display(), e))?;
+        Ok(Self::new(script))
+    }
+
+    fn respond(&self, command: &CommandLine) -> Result<CommandOutput> {
+        let program = Path::new(&command.program).file_stem().and_then(|stem| stem.to_str()).unwrap_or(&command.program);
+        let readable = command.to_string();
+        self.script.responses
+            .iter()
+            .find(|response| {
+                response.program.trim_end_matches(".exe").eq_ignore_ascii_case(program)
+                    && response.contains.as_deref().is_none_or(|needle| readable.contains(needle))
+            })
+            .map(|response| CommandOutput {
+                exit_code: Some(response.exit_code),
+                stdout: response.stdout.clone(),
+                stderr: response.stderr.clone(),
+            })
+            .ok_or_else(|| anyhow!("No scripted response for {}", readable))
+    }
+}
+
+#[cfg(debug_assertions)]
+impl CommandRunner for ScriptedRunner {
+    fn run<'a>(&'a self, command: &'a CommandLine) -> CommandFuture<'a> {
+        Box::pin(async move {
+            let started = Utc::now();
+            let elapsed = Instant::now();
+            let outcome = self.respond(command);
+            self.transcript.record(command, started, elapsed, &outcome);
+            outcome
+        })
+    }
+
+    fn transcript(&self) -> &Transcript {
+        &self.transcript
+    }
+}
@@ -0,0 +1,309 @@
+use std::collections::BTreeMap;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::bloatware_matching::{MatchEngine, MatchRule};
+use crate::inventory::{InstalledApplication, Inventory};
+use crate::removal_plan::{AppRemovalPlan, PlannedUninstall};
+
+pub const SCHEMA_VERSION: u32 = 1;
+pub const BUILTIN_COMPONENTS: &str = include_str!("../definitions/dependencies.json");
+
+/// Name this app appears under when it is one of the dependents
+const OPTIMIZER_NAME: &str = "Windows System Optimizer";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ComponentKind {
+    Runtime,
+    /// Appx framework package, e.g. VCLibs or UI.Xaml
+    Framework,
+    /// Appx package another package declares as a dependency
+    Package,
+    SharedService,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DependencyPolicy {
+    /// Removal goes ahead after the user saw who relies on the component
+    Warn,
+    /// Removal is refused while installed software relies on the component
+    Block,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ComponentFile {
+    schema_version: u32,
+    #[allow(dead_code)]
+    version: String,
+    #[serde(default)]
+    #[allow(dead_code)]
+    description: Option<String>,
+    components: Vec<ComponentDefinition>,
+}
+
+/// A shared component and how to recognize it and the software that needs it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ComponentDefinition {
+    pub id: String,
+    pub name: String,
+    pub kind: ComponentKind,
+    pub policy: DependencyPolicy,
+    /// Recognize the component among installed applications
+    pub rules: Vec<MatchRule>,
+    #[serde(default)]
+    pub services: Vec<String>,
+    #[serde(default)]
+    pub file_paths: Vec<String>,
+    #[serde(default)]
+    pub registry_keys: Vec<String>,
+    /// Recognize installed applications known to need the component
+    #[serde(default)]
+    pub dependent_rules: Vec<MatchRule>,
+    /// Files or folders in an install location showing that the app loads the component
+    #[serde(default)]
+    pub marker_files: Vec<String>,
+    /// This app itself renders through the component
+    #[serde(default)]
+    pub required_by_optimizer: bool,
+    /// What breaks, for components whose dependents cannot be detected
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Dependent {
+    pub name: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComponentNode {
+    pub id: String,
+    pub name: String,
+    pub kind: ComponentKind,
+    pub policy: DependencyPolicy,
+    /// Installed applications that make up the component
+    pub installed: Vec<String>,
+    /// Lower-cased Appx families of the component
+    pub package_families: Vec<String>,
+    /// Windows Installer product codes of the component
+    #[serde(default)]
+    pub product_codes: Vec<String>,
+    pub services: Vec<String>,
+    pub file_paths: Vec<String>,
+    pub registry_keys: Vec<String>,
+    pub dependents: Vec<Dependent>,
+    pub note: Option<String>,
+}
+
+/// Shared components installed on this machine and the software relying on each
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DependencyGraph {
+    pub components: Vec<ComponentNode>,
+}
+
+/// What removing an app would do to a component other software relies on
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DependencyImpact {
+    pub component: String,
+    pub policy: DependencyPolicy,
+    /// Installed software that would stop working
+    pub affected: Vec<Dependent>,
+    pub explanation: String,
+}
+
+/// The built-in component definitions
+pub fn builtin_components() -> Result<Vec<ComponentDefinition>> {
+    parse_components(BUILTIN_COMPONENTS, "built-in dependencies")
+}
+
+pub fn parse_components(content: &str, origin: &str) -> Result<Vec<ComponentDefinition>> {
+    let file: ComponentFile = serde_json::from_str(content)
+        .map_err(|e| anyhow!("{}: {}", origin, e))?;
+    if file.schema_version != SCHEMA_VERSION {
+        return Err(anyhow!("{}: schema version {} is not supported (expected {})", origin, file.schema_version, SCHEMA_VERSION));
+    }
+    for component in &file.components {
+        if component.rules.is_empty() {
+            return Err(anyhow!("{}: component {} needs at least one rule", origin, component.id));
+        }
+        for (index, rule) in component.rules.iter().chain(&component.dependent_rules).enumerate() {
+            rule.validate().map_err(|e| anyhow!("{}: component {} rule #{}: {}", origin, component.id, index + 1, e))?;
+        }
+    }
+    Ok(file.components)
+}
+
+impl DependencyGraph {
+    pub fn build(definitions: &[ComponentDefinition], inventory: &Inventory) -> Result<Self> {
+        let mut components = Vec::new();
+
+        for definition in definitions {
+            let mut recognizer = MatchEngine::default();
+            recognizer.add(&definition.id, &definition.rules)?;
+            let mut dependent_recognizer = MatchEngine::default();
+            dependent_recognizer.add(&definition.id, &definition.dependent_rules)?;
+
+            let installed: Vec<&InstalledApplication> = inventory.applications.iter()
+                .filter(|app| recognizer.best_match(&app.match_subject()).is_some())
+                .collect();
+            if installed.is_empty() {
+                continue;
+            }
+
+            let mut dependents = Vec::new();
+            if definition.required_by_optimizer {
+                dependents.push(Dependent {
+                    name: OPTIMIZER_NAME.to_string(),
+                    reason: format!("this app runs on {}", definition.name),
+                });
+            }
+            for app in inventory.applications.iter().filter(|app| !installed.iter().any(|own| std::ptr::eq(*own, *app))) {
+                if let Some(found) = dependent_recognizer.best_match(&app.match_subject()) {
+                    dependents.push(Dependent { name: app.display_name.clone(), reason: format!("known to need it ({})", found.rule) });
+                } else if let Some(marker) = marker_in(app, &definition.marker_files) {
+                    dependents.push(Dependent { name: app.display_name.clone(), reason: format!("ships {}", marker) });
+                }
+            }
+
+            components.push(ComponentNode {
+                id: definition.id.clone(),
+                name: definition.name.clone(),
+                kind: definition.kind,
+                policy: definition.policy,
+                installed: installed.iter().map(|app| app.display_name.clone()).collect(),
+                package_families: installed.iter().filter_map(|app| app.identity.package_family_name.as_ref().map(|family| family.to_lowercase())).collect(),
+                product_codes: installed.iter().filter_map(|app| app.identity.product_code.clone()).collect(),
+                services: definition.services.clone(),
+                file_paths: definition.file_paths.clone(),
+                registry_keys: definition.registry_keys.clone(),
+                dependents,
+                note: definition.note.clone(),
+            });
+        }
+
+        components.extend(appx_dependencies(inventory));
+        Ok(Self { components })
+    }
+
+    /// The component an installed application belongs to, if any
+    pub fn component_of(&self, app: &InstalledApplication) -> Option<&ComponentNode> {
+        let family = app.identity.package_family_name.as_ref().map(|family| family.to_lowercase());
+        self.components.iter().find(|component| {
+            component.installed.iter().any(|name| name == &app.display_name)
+                || family.as_ref().is_some_and(|family| component.package_families.contains(family))
+        })
+    }
+
+    /// Components the planned removal would take away, and who relies on them.
+    /// Dependents removed by the same plan do not count.
+    pub fn impact(&self, app: &AppRemovalPlan, removed_with_it: &[String]) -> Vec<DependencyImpact> {
+        let mut impacts = Vec::new();
+
+        for component in &self.components {
+            let Some(how) = touches(component, app) else { continue };
+            let affected: Vec<Dependent> = component.dependents.iter()
+                .filter(|dependent| !removed_with_it.iter().any(|name| name.eq_ignore_ascii_case(&dependent.name)))
+                .cloned()
+                .collect();
+            if affected.is_empty() && component.note.is_none() {
+                continue;
+            }
+
+            let mut explanation = if app.display_name.eq_ignore_ascii_case(&component.name) {
+                format!("Removing {}", component.name)
+            } else {
+                format!("Removing {} {} {}", app.display_name, how, component.name)
+            };
+            if !affected.is_empty() {
+                let names: Vec<&str> = affected.iter().map(|dependent| dependent.name.as_str()).collect();
+                explanation.push_str(&format!(", which {} still rel{}", names.join(", "), if names.len() == 1 { "ies on" } else { "y on" }));
+            }
+            if let Some(note) = &component.note {
+                explanation.push_str(&format!(". {}", note));
+            }
+
+            impacts.push(DependencyImpact {
+                component: component.name.clone(),
+                // Without known dependents there is nothing to protect, only something to mention
+                policy: if affected.is_empty() { DependencyPolicy::Warn } else { component.policy },
+                affected,
+                explanation,
+            });
+        }
+
+        impacts
+    }
+}
+
+/// How the plan reaches into the component, if it does
+fn touches(component: &ComponentNode, app: &AppRemovalPlan) -> Option<&'static str> {
+    for step in &app.uninstall_steps {
+        let hit = match step {
+            PlannedUninstall::Appx { families, .. } => families.iter().any(|family| component.package_families.contains(family)),
+            PlannedUninstall::Msi { products } => products.iter().any(|product| {
+                component.product_codes.iter().any(|code| code.eq_ignore_ascii_case(&product.product_code))
+            }),
+            PlannedUninstall::Winget { packages } => packages.iter().any(|package| {
+                component.installed.iter().any(|name| name.eq_ignore_ascii_case(&package.name))
+            }),
+        };
+        if hit {
+            return Some("uninstalls");
+        }
+    }
+
+    let same = |planned: &str, owned: &str| planned.eq_ignore_ascii_case(owned);
+    let nested = |planned: &str, owned: &str| {
+        let (planned, owned) = (planned.to_lowercase(), owned.to_lowercase());
+        planned == owned || planned.starts_with(&format!("{}\\", owned)) || owned.starts_with(&format!("{}\\", planned))
+    };
+    if app.services.iter().any(|service| component.services.iter().any(|owned| same(service, owned)))
+        || app.files.iter().any(|file| component.file_paths.iter().any(|owned| nested(&file.path.to_string_lossy(), owned)))
+        || app.registry_keys.iter().any(|key| component.registry_keys.iter().any(|owned| nested(key, owned)))
+    {
+        return Some("deletes parts of");
+    }
+    None
+}
+
+/// Appx packages other installed packages declare as dependencies
+fn appx_dependencies(inventory: &Inventory) -> Vec<ComponentNode> {
+    let mut dependents: BTreeMap<String, Vec<Dependent>> = BTreeMap::new();
+    for app in &inventory.applications {
+        for family in &app.dependencies {
+            dependents.entry(family.to_lowercase()).or_default().push(Dependent {
+                name: app.display_name.clone(),
+                reason: "declares the package as a dependency".to_string(),
+            });
+        }
+    }
+
+    inventory.applications.iter().filter_map(|app| {
+        let family = app.identity.package_family_name.as_ref()?.to_lowercase();
+        let dependents = dependents.get(&family)?.clone();
+        Some(ComponentNode {
+            id: family.clone(),
+            name: app.display_name.clone(),
+            kind: if app.is_framework { ComponentKind::Framework } else { ComponentKind::Package },
+            policy: DependencyPolicy::Block,
+            installed: vec![app.display_name.clone()],
+            package_families: vec![family],
+            product_codes: Vec::new(),
+            services: Vec::new(),
+            file_paths: Vec::new(),
+            registry_keys: Vec::new(),
+            dependents,
+            note: None,
+        })
+    }).collect()
+}
+
+fn marker_in(app: &InstalledApplication, markers: &[String]) -> Option<String> {
+    let location = app.install_location.as_ref()?;
+    markers.iter().find(|marker| location.join(marker).exists()).cloned()
+}
@@ -0,0 +1,269 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::file_manager::FileInfo;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DiskUsageOptions {
+    /// Number of largest files to report
+    pub top_files: usize,
+    /// Number of extensions to report, the rest are folded into "other"
+    pub top_extensions: usize,
+    /// Depth of the returned directory tree below the root
+    pub max_tree_depth: usize,
+    /// Directories smaller than this are left out of the tree
+    pub min_node_size: u64,
+}
+
+impl Default for DiskUsageOptions {
+    fn default() -> Self {
+        Self {
+            top_files: 50,
+            top_extensions: 25,
+            max_tree_depth: 6,
+            min_node_size: 1024 * 1024, // 1MB
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AgeBucket {
+    LastWeek,
+    LastMonth,
+    LastYear,
+    OneToThreeYears,
+    OlderThanThreeYears,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DirectoryUsage {
+    pub name: String,
+    pub path: PathBuf,
+    pub size: u64,
+    pub file_count: usize,
+    pub directory_count: usize,
+    pub children: Vec<DirectoryUsage>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExtensionUsage {
+    pub extension: String,
+    pub size: u64,
+    pub file_count: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgeBucketUsage {
+    pub bucket: AgeBucket,
+    pub size: u64,
+    pub file_count: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiskUsageReport {
+    pub root: PathBuf,
+    pub total_size: u64,
+    pub total_files: usize,
+    pub total_directories: usize,
+    pub tree: DirectoryUsage,
+    pub largest_files: Vec<FileInfo>,
+    pub by_extension: Vec<ExtensionUsage>,
+    pub by_age: Vec<AgeBucketUsage>,
+    pub scan_duration_ms: u64,
+    pub errors: Vec<String>,
+}
+
+/// Intermediate directory node keyed by child name
+#[derive(Default)]
+struct UsageNode {
+    size: u64,
+    file_count: usize,
+    children: BTreeMap<OsString, UsageNode>,
+}
+
+/// Incrementally aggregates files into a usage report
+pub struct DiskUsageBuilder {
+    root: PathBuf,
+    options: DiskUsageOptions,
+    now: DateTime<Utc>,
+    tree: UsageNode,
+    largest_files: Vec<FileInfo>,
+    by_extension: HashMap<String, (u64, usize)>,
+    by_age: HashMap<AgeBucket, (u64, usize)>,
+}
+
+impl DiskUsageBuilder {
+    pub fn new(root: PathBuf, options: DiskUsageOptions) -> Self {
+        Self {
+            root,
+            options,
+            now: Utc::now(),
+            tree: UsageNode::default(),
+            largest_files: Vec::new(),
+            by_extension: HashMap::new(),
+            by_age: HashMap::new(),
+        }
+    }
+
+    /// Register a directory so empty ones still show up in the counts
+    pub fn add_directory(&mut self, path: &Path) {
+        let Ok(relative) = path.strip_prefix(&self.root) else { return };
+        if relative.as_os_str().is_empty() {
+            return;
+        }
+
+        let mut node = &mut self.tree;
+        for component in relative.components() {
+            node = node.children.entry(component.as_os_str().to_os_string()).or_default();
+        }
+    }
+
+    /// Add a file's size to every directory between it and the root
+    pub fn add_file(&mut self, file: FileInfo) {
+        let Ok(relative) = file.path.strip_prefix(&self.root) else { return };
+
+        let mut node = &mut self.tree;
+        node.size += file.size;
+        node.file_count += 1;
+        if let Some(parent) = relative.parent() {
+            for component in parent.components() {
+                node = node.children.entry(component.as_os_str().to_os_string()).or_default();
+                node.size += file.size;
+                node.file_count += 1;
+            }
+        }
+
+        let extension = self.by_extension.entry(file.file_type.clone()).or_insert((0, 0));
+        extension.0 += file.size;
+        extension.1 += 1;
+
+        let age = self.by_age.entry(self.age_bucket(file.modified)).or_insert((0, 0));
+        age.0 += file.size;
+        age.1 += 1;
+
+        self.track_largest(file);
+    }
+
+    /// Finish aggregation and build the serializable report
+    pub fn finish(self, scan_duration_ms: u64, errors: Vec<String>) -> DiskUsageReport {
+        let total_directories = count_directories(&self.tree);
+        let root_name = self.root.file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| self.root.display().to_string());
+
+        let tree = build_tree(root_name, self.root.clone(), &self.tree, 0, &self.options);
+
+        let mut largest_files = self.largest_files;
+        largest_files.sort_by_key(|file| Reverse(file.size));
+        largest_files.truncate(self.options.top_files);
+
+        let mut by_extension: Vec<ExtensionUsage> = self.by_extension
+            .into_iter()
+            .map(|(extension, (size, file_count))| ExtensionUsage { extension, size, file_count })
+            .collect();
+        by_extension.sort_by_key(|extension| Reverse(extension.size));
+        if by_extension.len() > self.options.top_extensions {
+            let rest = by_extension.split_off(self.options.top_extensions);
+            by_extension.push(ExtensionUsage {
+                extension: "other".to_string(),
+                size: rest.iter().map(|e| e.size).sum(),
+                file_count: rest.iter().map(|e| e.file_count).sum(),
+            });
+        }
+
+        let by_age = [
+            AgeBucket::LastWeek,
+            AgeBucket::LastMonth,
+            AgeBucket::LastYear,
+            AgeBucket::OneToThreeYears,
+            AgeBucket::OlderThanThreeYears,
+        ]
+        .into_iter()
+        .map(|bucket| {
+            let (size, file_count) = self.by_age.get(&bucket).copied().unwrap_or((0, 0));
+            AgeBucketUsage { bucket, size, file_count }
+        })
+        .collect();
+
+        DiskUsageReport {
+            root: self.root,
+            total_size: tree.size,
+            total_files: tree.file_count,
+            total_directories,
+            tree,
+            largest_files,
+            by_extension,
+            by_age,
+            scan_duration_ms,
+            errors,
+        }
+    }
+
+    /// Keep a bounded candidate list for the largest files
+    fn track_largest(&mut self, file: FileInfo) {
+        let limit = self.options.top_files;
+        if limit == 0 {
+            return;
+        }
+
+        self.largest_files.push(file);
+        if self.largest_files.len() >= limit * 2 {
+            self.largest_files.sort_by_key(|file| Reverse(file.size));
+            self.largest_files.truncate(limit);
+        }
+    }
+
+    fn age_bucket(&self, modified: DateTime<Utc>) -> AgeBucket {
+        let age = self.now - modified;
+        if age < Duration::days(7) {
+            AgeBucket::LastWeek
+        } else if age < Duration::days(30) {
+            AgeBucket::LastMonth
+        } else if age < Duration::days(365) {
+            AgeBucket::LastYear
+        } else if age < Duration::days(3 * 365) {
+            AgeBucket::OneToThreeYears
+        } else {
+            AgeBucket::OlderThanThreeYears
+        }
+    }
+}
+
+fn count_directories(node: &UsageNode) -> usize {
+    node.children.len() + node.children.values().map(count_directories).sum::<usize>()
+}
+
+/// Convert the intermediate tree, largest children first, pruned for the UI
+fn build_tree(name: String, path: PathBuf, node: &UsageNode, depth: usize, options: &DiskUsageOptions) -> DirectoryUsage {
+    let mut children = Vec::new();
+
+    if depth < options.max_tree_depth {
+        for (child_name, child) in &node.children {
+            if child.size < options.min_node_size {
+                continue;
+            }
+            children.push(build_tree(
+                child_name.to_string_lossy().to_string(),
+                path.join(child_name),
+                child,
+                depth + 1,
+                options,
+            ));
+        }
+        children.sort_by_key(|child| Reverse(child.size));
+    }
+
+    DirectoryUsage {
+        name,
+        path,
+        size: node.size,
+        file_count: node.file_count,
+        directory_count: node.children.len(),
+        children,
+    }
+}
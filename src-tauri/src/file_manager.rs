@@ -1,4 +1,5 @@
-use std::collections::HashMap;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use anyhow::{anyhow, Result};
@@ -8,8 +9,21 @@ use tokio::sync::RwLock;
 use rayon::prelude::*;
 use walkdir::WalkDir;
 use sha2::{Sha256, Digest};
-use tracing::{info, warn, error};
-use dashmap::DashMap;
+use tokio::io::AsyncReadExt;
+use tracing::{info, warn};
+
+use crate::archive_scan::{self, ArchiveDuplicateGroup, ArchiveMember};
+use crate::cleanup_rules::{CleanupRule, CleanupRuleSet, RiskLevel, RuleScanMatch, RuleScanResult};
+use crate::disk_usage::{DiskUsageBuilder, DiskUsageOptions, DiskUsageReport};
+use crate::folder_duplicates::{self, DuplicateFolderGroup, FolderFile, FolderRelation, FolderScanResult};
+use crate::hygiene::{self, HygieneAction, HygieneCategory, HygieneFinding, HygieneScanResult};
+use crate::image_similarity::{self, ImageScanOptions, ImageSimilarityResult};
+use crate::keeper::{self, FileIdentity, KeeperDecision, KeeperPreference, KeeperRules};
+use crate::platform::{self, PlatformProfile};
+use crate::protection::{self, FileProtection, ProtectionPolicy};
+use crate::space_accounting::{self, FileAllocation, SpaceAccounting, SpaceMeter};
+use crate::scan_sessions::{self, DirectorySnapshot, ScanDiff, ScanSession, ScanSessionStore, ScanSessionSummary, TreeSnapshot};
+use crate::command_runner::{self, CommandLine, CommandRunner};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileInfo {
@@ -33,21 +47,40 @@ pub struct DuplicateGroup {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ScanResult {
+    /// Session the result was saved under
+    #[serde(default)]
+    pub session_id: Option<String>,
     pub total_files: usize,
     pub total_size: u64,
     pub duplicate_groups: Vec<DuplicateGroup>,
+    /// Matches involving ZIP members, report-only
+    #[serde(default)]
+    pub archive_duplicate_groups: Vec<ArchiveDuplicateGroup>,
     pub scan_duration_ms: u64,
     pub scanned_directories: Vec<PathBuf>,
     pub errors: Vec<String>,
 }
 
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DuplicateScanOptions {
+    /// Also hash the members of ZIP archives and report matches against them
+    pub scan_archives: bool,
+}
+
+pub type DuplicateScanSession = ScanSession<ScanResult, DuplicateScanOptions>;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CleanupResult {
     pub files_removed: usize,
+    pub files_renamed: usize,
     pub space_freed: u64,
     pub errors: Vec<String>,
     pub backup_created: bool,
     pub backup_path: Option<PathBuf>,
+    /// Measured counterpart of `space_freed`
+    #[serde(default)]
+    pub accounting: SpaceAccounting,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -59,30 +92,34 @@ pub struct ScanProgress {
     pub percentage: f32,
 }
 
+#[derive(Clone)]
 pub struct FileManager {
-    file_cache: Arc<DashMap<String, FileInfo>>,
-    scan_results: Arc<RwLock<HashMap<String, ScanResult>>>,
+    sessions: Arc<RwLock<ScanSessionStore>>,
     backup_directory: PathBuf,
-    excluded_paths: Vec<PathBuf>,
+    platform: PlatformProfile,
+    protection: Arc<parking_lot::RwLock<FileProtection>>,
     max_file_size: u64, // Maximum file size to scan (e.g., 100MB)
+    cleanup_rules: Arc<RwLock<CleanupRuleSet>>,
+    runner: Arc<dyn CommandRunner>,
 }
 
 impl FileManager {
     pub fn new(backup_dir: PathBuf) -> Self {
-        let excluded_paths = vec![
-            PathBuf::from("C:\\Windows\\System32"),
-            PathBuf::from("C:\\Windows\\SysWOW64"),
-            PathBuf::from("C:\\Program Files\\WindowsApps"),
-            PathBuf::from("C:\\$Recycle.Bin"),
-            PathBuf::from("C:\\System Volume Information"),
-        ];
-        
+        Self::with_runner(backup_dir, command_runner::shared())
+    }
+
+    /// Delete files through `runner` instead of the shared runner
+    pub fn with_runner(backup_dir: PathBuf, runner: Arc<dyn CommandRunner>) -> Self {
+        let platform = platform::current();
+
         Self {
-            file_cache: Arc::new(DashMap::new()),
-            scan_results: Arc::new(RwLock::new(HashMap::new())),
+            sessions: Arc::new(RwLock::new(ScanSessionStore::new(backup_dir.join("sessions").join("duplicates")))),
+            cleanup_rules: Arc::new(RwLock::new(CleanupRuleSet::load(backup_dir.join("cleanup_rules.json")))),
+            protection: Arc::new(parking_lot::RwLock::new(FileProtection::load(backup_dir.join(protection::POLICY_FILE), platform.critical_directories.clone()))),
             backup_directory: backup_dir,
-            excluded_paths,
+            platform,
             max_file_size: 100 * 1024 * 1024, // 100MB
+            runner,
         }
     }
 
@@ -90,35 +127,82 @@ impl FileManager {
     pub async fn scan_duplicates(
         &self,
         directories: Vec<PathBuf>,
+        options: DuplicateScanOptions,
+        progress_callback: Option<Box<dyn Fn(ScanProgress) + Send + Sync>>
+    ) -> Result<ScanResult> {
+        self.run_duplicate_scan(directories, options, None, progress_callback).await
+    }
+
+    /// Rescan a saved session (the latest by default), revisiting only directories that changed
+    pub async fn rescan_duplicates(&self, session_id: Option<String>) -> Result<ScanResult> {
+        let (session, snapshot) = {
+            let sessions = self.sessions.read().await;
+            let session_id = session_id
+                .or_else(|| sessions.latest_id())
+                .ok_or_else(|| anyhow!("No saved duplicate scan to rescan"))?;
+            let session: DuplicateScanSession = sessions.load(&session_id)?;
+            let snapshot = sessions.load_snapshot(&session_id);
+            (session, snapshot)
+        };
+
+        info!("Rescanning duplicate session {}", session.id);
+        let directories = session.result.scanned_directories.clone();
+        self.run_duplicate_scan(directories, session.options, Some((session.id, snapshot)), None).await
+    }
+
+    async fn run_duplicate_scan(
+        &self,
+        directories: Vec<PathBuf>,
+        options: DuplicateScanOptions,
+        previous: Option<(String, TreeSnapshot)>,
         progress_callback: Option<Box<dyn Fn(ScanProgress) + Send + Sync>>
     ) -> Result<ScanResult> {
         let start_time = std::time::Instant::now();
         let mut result = ScanResult {
+            session_id: None,
             total_files: 0,
             total_size: 0,
             duplicate_groups: Vec::new(),
+            archive_duplicate_groups: Vec::new(),
             scan_duration_ms: 0,
             scanned_directories: directories.clone(),
             errors: Vec::new(),
         };
+        let (based_on, previous) = match previous {
+            Some((session_id, snapshot)) => (Some(session_id), snapshot),
+            None => (None, TreeSnapshot::new()),
+        };
 
         info!("Starting duplicate file scan for {} directories", directories.len());
 
-        // Collect all files first
+        // Collect all files first, reusing listings of directories that did not change
         let mut all_files = Vec::new();
+        let mut snapshot = TreeSnapshot::new();
+        let mut revisited = 0;
         for directory in &directories {
-            match self.collect_files(directory, &mut all_files, &progress_callback).await {
-                Ok(_) => {},
-                Err(e) => {
-                    result.errors.push(format!("Failed to scan {}: {}", directory.display(), e));
-                }
-            }
+            self.collect_files_incremental(directory, &previous, &mut snapshot, &mut all_files, &mut revisited, &progress_callback);
         }
 
         result.total_files = all_files.len();
         result.total_size = all_files.iter().map(|f| f.size).sum();
 
-        info!("Collected {} files, total size: {} bytes", result.total_files, result.total_size);
+        info!("Collected {} files, total size: {} bytes, {} of {} directories revisited",
+              result.total_files, result.total_size, revisited, snapshot.len());
+
+        let archive_members = if options.scan_archives {
+            self.hash_archive_members(&all_files, &mut result.errors).await
+        } else {
+            Vec::new()
+        };
+        let member_sizes: HashSet<u64> = archive_members.iter().map(|member| member.size).collect();
+
+        // Hashes from the previous session stay valid while size and modification time match
+        let known_hashes: HashMap<&Path, &FileInfo> = previous
+            .values()
+            .flat_map(|listing| listing.files.iter())
+            .filter(|file| !file.hash.is_empty())
+            .map(|file| (file.path.as_path(), file))
+            .collect();
 
         // Group files by size first (quick filter)
         let size_groups: HashMap<u64, Vec<FileInfo>> = all_files
@@ -135,13 +219,22 @@ impl FileManager {
         let mut hash_groups: HashMap<String, Vec<FileInfo>> = HashMap::new();
         
         for (size, files) in size_groups {
-            if files.len() > 1 { // Only process if there are potential duplicates
+            // Only process if there are potential duplicates, loose or inside archives
+            if files.len() > 1 || member_sizes.contains(&size) {
                 for file in files {
-                    match self.calculate_file_hash(&file.path).await {
+                    let known_hash = known_hashes.get(file.path.as_path())
+                        .filter(|known| known.size == file.size && known.modified == file.modified)
+                        .map(|known| known.hash.clone());
+                    let hash = match known_hash {
+                        Some(hash) => Ok(hash),
+                        None => self.calculate_file_hash(&file.path).await,
+                    };
+
+                    match hash {
                         Ok(hash) => {
                             let mut file_with_hash = file.clone();
-                            file_with_hash.hash = hash;
-                            hash_groups.entry(hash).or_insert_with(Vec::new).push(file_with_hash);
+                            file_with_hash.hash = hash.clone();
+                            hash_groups.entry(hash).or_default().push(file_with_hash);
                         }
                         Err(e) => {
                             result.errors.push(format!("Failed to hash {}: {}", file.path.display(), e));
@@ -151,6 +244,22 @@ impl FileManager {
             }
         }
 
+        // Remember computed hashes so the next rescan can reuse them
+        let computed: HashMap<&Path, &str> = hash_groups
+            .values()
+            .flatten()
+            .map(|file| (file.path.as_path(), file.hash.as_str()))
+            .collect();
+        for file in snapshot.values_mut().flat_map(|listing| listing.files.iter_mut()) {
+            if let Some(hash) = computed.get(file.path.as_path()) {
+                file.hash = hash.to_string();
+            }
+        }
+
+        if !archive_members.is_empty() {
+            result.archive_duplicate_groups = group_archive_duplicates(archive_members, &hash_groups);
+        }
+
         // Create duplicate groups
         for (hash, files) in hash_groups {
             if files.len() > 1 {
@@ -169,19 +278,330 @@ impl FileManager {
 
         result.scan_duration_ms = start_time.elapsed().as_millis() as u64;
         
-        // Store scan result
-        let scan_id = format!("scan_{}", Utc::now().timestamp());
+        // Persist the session so it can be reopened, rescanned and compared
+        let session_id = format!("scan_{}", Utc::now().timestamp_millis());
+        result.session_id = Some(session_id.clone());
+        let summary = ScanSessionSummary {
+            id: session_id.clone(),
+            created_at: Utc::now(),
+            scanned_directories: result.scanned_directories.clone(),
+            finding_count: result.duplicate_groups.len(),
+            potential_savings: result.duplicate_groups.iter().map(|group| group.potential_savings).sum(),
+            based_on,
+        };
+        let session = DuplicateScanSession {
+            id: session_id.clone(),
+            created_at: summary.created_at,
+            options,
+            result: result.clone(),
+        };
         {
-            let mut scan_results = self.scan_results.write().await;
-            scan_results.insert(scan_id, result.clone());
+            let mut sessions = self.sessions.write().await;
+            if let Err(e) = sessions.save(&session, summary).and_then(|_| sessions.save_snapshot(&session_id, &snapshot)) {
+                warn!("Failed to save scan session {}: {}", session_id, e);
+            }
+        }
+
+        info!("Duplicate scan completed: {} groups found, {} involving archives, {}ms", 
+              result.duplicate_groups.len(), result.archive_duplicate_groups.len(), result.scan_duration_ms);
+
+        Ok(result)
+    }
+
+    /// Saved duplicate scan sessions, newest first
+    pub async fn list_scan_sessions(&self) -> Vec<ScanSessionSummary> {
+        self.sessions.read().await.list()
+    }
+
+    /// Reopen a saved duplicate scan, the latest one by default
+    pub async fn open_scan_session(&self, session_id: Option<String>) -> Result<DuplicateScanSession> {
+        let sessions = self.sessions.read().await;
+        let session_id = session_id
+            .or_else(|| sessions.latest_id())
+            .ok_or_else(|| anyhow!("No saved duplicate scan"))?;
+        sessions.load(&session_id)
+    }
+
+    /// Compare two saved duplicate scans
+    pub async fn diff_scan_sessions(&self, from_session: &str, to_session: &str) -> Result<ScanDiff> {
+        let sessions = self.sessions.read().await;
+        let from: DuplicateScanSession = sessions.load(from_session)?;
+        let to: DuplicateScanSession = sessions.load(to_session)?;
+        Ok(scan_sessions::diff_scan_results(&from.id, &from.result, &to.id, &to.result))
+    }
+
+    /// Scan for folder trees that are copies of, or contained in, other folders
+    pub async fn scan_duplicate_folders(&self, directories: Vec<PathBuf>) -> Result<FolderScanResult> {
+        let start_time = std::time::Instant::now();
+        let mut result = FolderScanResult {
+            groups: Vec::new(),
+            total_directories: 0,
+            total_files: 0,
+            potential_savings: 0,
+            scan_duration_ms: 0,
+            scanned_directories: directories.clone(),
+            errors: Vec::new(),
+        };
+
+        info!("Starting duplicate folder scan for {} directories", directories.len());
+
+        let mut folder_paths = Vec::new();
+        let mut entries: Vec<(PathBuf, u64)> = Vec::new();
+        // Folders whose contents are not fully known must never be reported as removable
+        let mut incomplete = HashSet::new();
+
+        for directory in &directories {
+            if !directory.is_dir() || self.is_excluded_path(directory) {
+                continue;
+            }
+
+            let mut walker = WalkDir::new(directory).follow_links(false).into_iter();
+            while let Some(entry) = walker.next() {
+                let entry = match entry {
+                    Ok(entry) => entry,
+                    Err(e) => {
+                        if let Some(path) = e.path() {
+                            incomplete.insert(path.to_path_buf());
+                        }
+                        result.errors.push(format!("Failed to read entry: {}", e));
+                        continue;
+                    }
+                };
+                let path = entry.path();
+
+                if self.is_excluded_path(path) {
+                    incomplete.insert(path.to_path_buf());
+                    if entry.file_type().is_dir() {
+                        walker.skip_current_dir();
+                    }
+                } else if entry.file_type().is_dir() {
+                    folder_paths.push(path.to_path_buf());
+                } else if entry.file_type().is_file() && !self.is_critical_file(path) {
+                    match entry.metadata() {
+                        Ok(metadata) => entries.push((path.to_path_buf(), metadata.len())),
+                        Err(_) => { incomplete.insert(path.to_path_buf()); }
+                    }
+                } else {
+                    // Links and critical files are not content we can vouch for
+                    incomplete.insert(path.to_path_buf());
+                }
+            }
+        }
+
+        // Overlapping roots would otherwise count files twice
+        folder_paths.sort();
+        folder_paths.dedup();
+        entries.sort();
+        entries.dedup();
+
+        result.total_directories = folder_paths.len();
+        result.total_files = entries.len();
+
+        // Only sizes that occur more than once can be shared between folders
+        let mut size_counts: HashMap<u64, usize> = HashMap::new();
+        for (_, size) in &entries {
+            *size_counts.entry(*size).or_insert(0) += 1;
+        }
+
+        let mut files = Vec::with_capacity(entries.len());
+        for (path, size) in entries {
+            let hash = if size_counts[&size] > 1 {
+                match self.calculate_file_hash(&path).await {
+                    Ok(hash) => hash,
+                    Err(e) => {
+                        result.errors.push(format!("Failed to hash {}: {}", path.display(), e));
+                        incomplete.insert(path);
+                        continue;
+                    }
+                }
+            } else {
+                format!("unique:{}", path.display())
+            };
+            files.push(FolderFile { path, size, hash });
+        }
+
+        result.groups = tokio::task::spawn_blocking(move || {
+            folder_duplicates::find_duplicate_folders(folder_paths, files, &incomplete)
+        }).await?;
+        result.potential_savings = result.groups.iter().map(|group| group.potential_savings).sum();
+        result.scan_duration_ms = start_time.elapsed().as_millis() as u64;
+
+        info!("Duplicate folder scan completed: {} groups found, {}ms",
+              result.groups.len(), result.scan_duration_ms);
+
+        Ok(result)
+    }
+
+    /// Remove duplicate folders as units, keeping each group's `keep` folder
+    pub async fn cleanup_duplicate_folders(&self, groups: Vec<DuplicateFolderGroup>) -> Result<CleanupResult> {
+        let mut result = CleanupResult {
+            files_removed: 0,
+            files_renamed: 0,
+            space_freed: 0,
+            errors: Vec::new(),
+            backup_created: false,
+            backup_path: None,
+            accounting: SpaceAccounting::default(),
+        };
+
+        let mut meter = SpaceMeter::start(groups.iter().flat_map(|group| group.folders.iter().map(|folder| &folder.path)));
+
+        for group in groups {
+            let kept = match self.folder_contents(&group.keep).await {
+                Ok((hashes, _)) => hashes,
+                Err(e) => {
+                    result.errors.push(format!("Skipped group of {}: {}", group.keep.display(), e));
+                    continue;
+                }
+            };
+
+            for folder in group.folders.iter().filter(|folder| folder.path != group.keep) {
+                if folder.path.starts_with(&group.keep) || group.keep.starts_with(&folder.path) {
+                    result.errors.push(format!("Skipped {}: nested with the kept folder", folder.path.display()));
+                    continue;
+                }
+                if let Err(e) = self.check_tree_protection(&folder.path) {
+                    result.errors.push(format!("Failed to remove {}: {}", folder.path.display(), e));
+                    continue;
+                }
+
+                // Re-verify right before removing, either folder may have changed since the scan
+                let (hashes, size) = match self.folder_contents(&folder.path).await {
+                    Ok(contents) => contents,
+                    Err(e) => {
+                        result.errors.push(format!("Skipped {}: {}", folder.path.display(), e));
+                        continue;
+                    }
+                };
+                let still_duplicate = match group.relation {
+                    FolderRelation::Identical => hashes == kept,
+                    FolderRelation::Subset => folder_duplicates::contains_all(&kept, &hashes),
+                };
+                if !still_duplicate {
+                    result.errors.push(format!("Skipped {}: contents changed since the scan", folder.path.display()));
+                    continue;
+                }
+
+                let allocation = folder_allocation(&folder.path);
+                match tokio::fs::remove_dir_all(&folder.path).await {
+                    Ok(_) => {
+                        result.files_removed += hashes.len();
+                        result.space_freed += size;
+                        meter.record(allocation);
+                    }
+                    Err(e) => {
+                        result.errors.push(format!("Failed to remove {}: {}", folder.path.display(), e));
+                    }
+                }
+            }
+        }
+
+        result.accounting = meter.finish();
+        info!("Folder cleanup completed: {} files removed, {} bytes estimated, {} bytes measured",
+              result.files_removed, result.space_freed, result.accounting.measured_bytes);
+
+        Ok(result)
+    }
+
+    /// Scan for visually similar images (resized, recompressed or converted copies)
+    pub async fn scan_similar_images(
+        &self,
+        directories: Vec<PathBuf>,
+        options: ImageScanOptions
+    ) -> Result<ImageSimilarityResult> {
+        let start_time = std::time::Instant::now();
+        let mut result = ImageSimilarityResult {
+            total_images: 0,
+            images_hashed: 0,
+            similar_groups: Vec::new(),
+            scan_duration_ms: 0,
+            scanned_directories: directories.clone(),
+            errors: Vec::new(),
+        };
+
+        info!("Starting similar image scan for {} directories", directories.len());
+
+        let mut all_files = Vec::new();
+        for directory in &directories {
+            if let Err(e) = self.collect_files(directory, &mut all_files, &None).await {
+                result.errors.push(format!("Failed to scan {}: {}", directory.display(), e));
+            }
         }
 
-        info!("Duplicate scan completed: {} groups found, {}ms", 
-              result.duplicate_groups.len(), result.scan_duration_ms);
+        let images: Vec<FileInfo> = all_files
+            .into_iter()
+            .filter(|file| file.size > 0 && image_similarity::is_supported_image(&file.path))
+            .collect();
+        result.total_images = images.len();
+
+        // Decoding is CPU-bound, keep it off the async runtime
+        let (images_hashed, groups, errors) = tokio::task::spawn_blocking(move || {
+            image_similarity::find_similar_images(images, &options)
+        }).await?;
+
+        result.images_hashed = images_hashed;
+        result.similar_groups = groups;
+        result.errors.extend(errors);
+        result.scan_duration_ms = start_time.elapsed().as_millis() as u64;
+
+        info!("Similar image scan completed: {} groups found, {}ms",
+              result.similar_groups.len(), result.scan_duration_ms);
 
         Ok(result)
     }
 
+    /// Analyze disk usage under a root with per-directory aggregation
+    pub async fn analyze_disk_usage(&self, root: PathBuf, options: DiskUsageOptions) -> Result<DiskUsageReport> {
+        let start_time = std::time::Instant::now();
+
+        if !root.is_dir() {
+            return Err(anyhow!("Not a directory: {}", root.display()));
+        }
+
+        info!("Starting disk usage analysis for {}", root.display());
+
+        // Walking a large tree is blocking I/O, keep it off the async runtime
+        let manager = self.clone();
+        let report = tokio::task::spawn_blocking(move || manager.walk_disk_usage(root, options, start_time)).await?;
+
+        info!("Disk usage analysis completed: {} files, {} bytes, {}ms",
+              report.total_files, report.total_size, report.scan_duration_ms);
+
+        Ok(report)
+    }
+
+    /// Walk `root` and total up what every directory below it holds
+    fn walk_disk_usage(&self, root: PathBuf, options: DiskUsageOptions, start_time: std::time::Instant) -> DiskUsageReport {
+        let mut builder = DiskUsageBuilder::new(root.clone(), options);
+        let mut errors = Vec::new();
+
+        let walker = WalkDir::new(&root)
+            .follow_links(false)
+            .into_iter()
+            .filter_entry(|entry| !self.is_excluded_path(entry.path()));
+
+        for entry in walker {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    errors.push(format!("Failed to read entry: {}", e));
+                    continue;
+                }
+            };
+
+            if entry.file_type().is_dir() {
+                builder.add_directory(entry.path());
+            } else if entry.file_type().is_file() {
+                match entry.metadata() {
+                    Ok(metadata) => builder.add_file(self.build_file_info(entry.path(), &metadata)),
+                    Err(e) => errors.push(format!("Failed to read metadata for {}: {}", entry.path().display(), e)),
+                }
+            }
+        }
+
+        builder.finish(start_time.elapsed().as_millis() as u64, errors)
+    }
+
     /// Clean up duplicate files with safety measures
     pub async fn cleanup_duplicates(
         &self,
@@ -191,10 +611,12 @@ impl FileManager {
     ) -> Result<CleanupResult> {
         let mut result = CleanupResult {
             files_removed: 0,
+            files_renamed: 0,
             space_freed: 0,
             errors: Vec::new(),
             backup_created: false,
             backup_path: None,
+            accounting: SpaceAccounting::default(),
         };
 
         // Create backup if requested
@@ -211,9 +633,12 @@ impl FileManager {
             }
         }
 
+        // Started after the backup, which would otherwise count against the freed space
+        let mut meter = SpaceMeter::start(duplicate_groups.iter().flat_map(|group| group.files.iter().map(|file| &file.path)));
+
         // Process each duplicate group
         for group in duplicate_groups {
-            match self.process_duplicate_group(&group, &keep_strategy).await {
+            match self.process_duplicate_group(&group, &keep_strategy, &mut meter).await {
                 Ok((removed, freed)) => {
                     result.files_removed += removed;
                     result.space_freed += freed;
@@ -224,53 +649,121 @@ impl FileManager {
             }
         }
 
-        info!("Cleanup completed: {} files removed, {} bytes freed", 
-              result.files_removed, result.space_freed);
+        result.accounting = meter.finish();
+        info!("Cleanup completed: {} files removed, {} bytes estimated, {} bytes measured",
+              result.files_removed, result.space_freed, result.accounting.measured_bytes);
 
         Ok(result)
     }
 
+    /// Show which copy of each group a strategy keeps, and why, without deleting anything
+    pub fn preview_keepers(&self, duplicate_groups: &[DuplicateGroup], keep_strategy: &KeepStrategy) -> Vec<KeeperDecision> {
+        let rules = keep_strategy.keeper_rules();
+        duplicate_groups.iter().map(|group| keeper::select_keepers(group, &rules)).collect()
+    }
+
     /// Scan for temporary files and cleanup opportunities
     pub async fn scan_temp_files(&self) -> Result<Vec<FileInfo>> {
-        let temp_directories = vec![
-            std::env::temp_dir(),
-            PathBuf::from("C:\\Windows\\Temp"),
-            PathBuf::from("C:\\Windows\\Prefetch"),
-            PathBuf::from("C:\\Windows\\SoftwareDistribution\\Download"),
-        ];
-
-        let mut temp_files = Vec::new();
-        
-        for temp_dir in temp_directories {
-            if temp_dir.exists() {
-                match self.collect_files(&temp_dir, &mut temp_files, &None).await {
-                    Ok(_) => {},
-                    Err(e) => {
-                        warn!("Failed to scan temp directory {}: {}", temp_dir.display(), e);
-                    }
+        let scan = self.scan_cleanup_rules(None, RiskLevel::Low).await?;
+        for error in &scan.errors {
+            warn!("{}", error);
+        }
+
+        // Rules may overlap, report each file once
+        let mut seen = HashSet::new();
+        let temp_files = scan.matches
+            .into_iter()
+            .flat_map(|rule_match| rule_match.files)
+            .filter(|file| seen.insert(file.path.clone()))
+            .collect();
+
+        Ok(temp_files)
+    }
+
+    /// Evaluate cleanup rules, either the given ids or every enabled rule up to `max_risk`
+    pub async fn scan_cleanup_rules(&self, rule_ids: Option<Vec<String>>, max_risk: RiskLevel) -> Result<RuleScanResult> {
+        let start_time = std::time::Instant::now();
+        let rules = self.cleanup_rules.read().await.select(rule_ids.as_deref(), max_risk);
+        let now = Utc::now();
+
+        let mut result = RuleScanResult {
+            matches: Vec::new(),
+            total_files: 0,
+            total_size: 0,
+            scan_duration_ms: 0,
+            errors: Vec::new(),
+        };
+
+        for rule in rules {
+            let locations = rule.resolve_locations();
+            let mut files = Vec::new();
+
+            for location in &locations {
+                if let Err(e) = self.collect_rule_files(&rule, location, now, &mut files) {
+                    result.errors.push(format!("Rule {}: failed to scan {}: {}", rule.id, location.display(), e));
                 }
             }
+
+            let total_size = files.iter().map(|f| f.size).sum();
+            result.total_files += files.len();
+            result.total_size += total_size;
+            result.matches.push(RuleScanMatch {
+                rule_id: rule.id.clone(),
+                rule_name: rule.name.clone(),
+                risk: rule.risk,
+                locations,
+                files,
+                total_size,
+            });
         }
 
-        // Filter for files older than 7 days
-        let cutoff_date = Utc::now() - chrono::Duration::days(7);
-        temp_files.retain(|file| file.modified < cutoff_date);
+        result.scan_duration_ms = start_time.elapsed().as_millis() as u64;
+        info!("Cleanup rule scan completed: {} files, {} bytes", result.total_files, result.total_size);
 
-        Ok(temp_files)
+        Ok(result)
+    }
+
+    /// List built-in and user cleanup rules
+    pub async fn list_cleanup_rules(&self) -> Vec<CleanupRule> {
+        self.cleanup_rules.read().await.rules()
+    }
+
+    /// Add or replace a user cleanup rule
+    pub async fn save_cleanup_rule(&self, rule: CleanupRule) -> Result<()> {
+        self.cleanup_rules.write().await.save_user_rule(rule)
+    }
+
+    /// Remove a user cleanup rule
+    pub async fn remove_cleanup_rule(&self, rule_id: &str) -> Result<()> {
+        self.cleanup_rules.write().await.remove_user_rule(rule_id)
+    }
+
+    /// Current file protection policy
+    pub fn protection_policy(&self) -> ProtectionPolicy {
+        self.protection.read().policy()
+    }
+
+    /// Replace and persist the file protection policy
+    pub fn save_protection_policy(&self, policy: ProtectionPolicy) -> Result<()> {
+        self.protection.write().update(policy)
     }
 
     /// Clean up temporary files
     pub async fn cleanup_temp_files(&self, files: Vec<FileInfo>) -> Result<CleanupResult> {
         let mut result = CleanupResult {
             files_removed: 0,
+            files_renamed: 0,
             space_freed: 0,
             errors: Vec::new(),
             backup_created: false,
             backup_path: None,
+            accounting: SpaceAccounting::default(),
         };
 
+        let mut meter = SpaceMeter::start(files.iter().map(|file| &file.path));
+
         for file in files {
-            match self.safe_delete_file(&file.path).await {
+            match self.safe_delete_file(&file.path, &mut meter).await {
                 Ok(_) => {
                     result.files_removed += 1;
                     result.space_freed += file.size;
@@ -281,6 +774,92 @@ impl FileManager {
             }
         }
 
+        result.accounting = meter.finish();
+        Ok(result)
+    }
+
+    /// Scan for empty folders, zero-byte files, broken links and problem paths
+    pub async fn scan_hygiene(&self, directories: Vec<PathBuf>) -> Result<HygieneScanResult> {
+        let start_time = std::time::Instant::now();
+        let mut result = HygieneScanResult {
+            empty_directories: Vec::new(),
+            zero_byte_files: Vec::new(),
+            broken_links: Vec::new(),
+            long_paths: Vec::new(),
+            invalid_names: Vec::new(),
+            total_entries_scanned: 0,
+            scan_duration_ms: 0,
+            scanned_directories: directories.clone(),
+            errors: Vec::new(),
+        };
+
+        info!("Starting hygiene scan for {} directories", directories.len());
+
+        for directory in &directories {
+            if !directory.is_dir() || self.is_excluded_path(directory) {
+                continue;
+            }
+            self.scan_directory_hygiene(directory, &mut result);
+        }
+
+        result.scan_duration_ms = start_time.elapsed().as_millis() as u64;
+
+        info!("Hygiene scan completed: {} empty directories, {} zero-byte files, {} broken links, {} long paths, {} invalid names",
+              result.empty_directories.len(), result.zero_byte_files.len(), result.broken_links.len(),
+              result.long_paths.len(), result.invalid_names.len());
+
+        Ok(result)
+    }
+
+    /// Apply the category-specific cleanup action to each hygiene finding
+    pub async fn cleanup_hygiene(&self, findings: Vec<HygieneFinding>) -> Result<CleanupResult> {
+        let mut result = CleanupResult {
+            files_removed: 0,
+            files_renamed: 0,
+            space_freed: 0,
+            errors: Vec::new(),
+            backup_created: false,
+            backup_path: None,
+            accounting: SpaceAccounting::default(),
+        };
+
+        let mut meter = SpaceMeter::start(findings.iter().map(|finding| &finding.path));
+
+        for finding in findings {
+            // The action is always derived from the category, never trusted from the caller
+            let action = finding.category.cleanup_action();
+            let outcome = match action {
+                HygieneAction::RemoveEmptyDirectory => self.safe_remove_empty_directory(&finding.path).await,
+                HygieneAction::DeleteEmptyFile => self.safe_delete_empty_file(&finding.path, &mut meter).await,
+                HygieneAction::RemoveLink => self.safe_remove_broken_link(&finding.path).await,
+                HygieneAction::ShortenName => {
+                    let limit = if finding.path.is_dir() { hygiene::MAX_DIRECTORY_PATH_LENGTH } else { hygiene::MAX_PATH_LENGTH };
+                    match hygiene::shortened_name(&finding.path, limit) {
+                        Some(name) => self.safe_rename(&finding.path, &name).await,
+                        None => Err(anyhow!("Parent directory alone exceeds the path limit")),
+                    }
+                }
+                HygieneAction::SanitizeName => {
+                    let name = finding.path.file_name()
+                        .map(|name| hygiene::sanitize_file_name(&name.to_string_lossy()))
+                        .ok_or_else(|| anyhow!("Path has no file name"));
+                    match name {
+                        Ok(name) => self.safe_rename(&finding.path, &name).await,
+                        Err(e) => Err(e),
+                    }
+                }
+            };
+
+            match (outcome, action) {
+                (Ok(_), HygieneAction::ShortenName | HygieneAction::SanitizeName) => result.files_renamed += 1,
+                (Ok(_), _) => result.files_removed += 1,
+                (Err(e), _) => result.errors.push(format!("Failed to clean up {}: {}", finding.path.display(), e)),
+            }
+        }
+
+        result.accounting = meter.finish();
+        info!("Hygiene cleanup completed: {} removed, {} renamed", result.files_removed, result.files_renamed);
+
         Ok(result)
     }
 
@@ -296,7 +875,7 @@ impl FileManager {
         }
 
         // Check if directory is excluded
-        if self.excluded_paths.iter().any(|excluded| directory.starts_with(excluded)) {
+        if self.is_excluded_path(directory) {
             return Ok(());
         }
 
@@ -341,23 +920,402 @@ impl FileManager {
                     continue;
                 }
 
-                let file_info = FileInfo {
-                    path: path.to_path_buf(),
-                    size: metadata.len(),
-                    modified: DateTime::from(metadata.modified().unwrap_or_else(|_| std::time::SystemTime::now())),
-                    hash: String::new(), // Will be calculated later
-                    file_type: self.get_file_extension(path),
-                    is_system_file: self.is_system_file(path),
-                    is_critical: self.is_critical_file(path),
-                };
+                files.push(self.build_file_info(path, &metadata));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Collect files below `root`, reusing the listing of directories unchanged since `previous`
+    fn collect_files_incremental(
+        &self,
+        root: &Path,
+        previous: &TreeSnapshot,
+        snapshot: &mut TreeSnapshot,
+        files: &mut Vec<FileInfo>,
+        revisited: &mut usize,
+        progress_callback: &Option<Box<dyn Fn(ScanProgress) + Send + Sync>>
+    ) {
+        if !root.is_dir() || self.is_excluded_path(root) {
+            return;
+        }
+
+        let estimated_total: usize = previous.values().map(|listing| listing.files.len()).sum();
+        let mut pending = vec![(root.to_path_buf(), 0)];
+
+        while let Some((directory, depth)) = pending.pop() {
+            if self.is_excluded_path(&directory) || snapshot.contains_key(&directory) {
+                continue;
+            }
+
+            let Ok(modified) = std::fs::metadata(&directory).and_then(|metadata| metadata.modified()) else { continue };
+            let modified = DateTime::<Utc>::from(modified);
+
+            // A directory's modification time changes whenever entries are added, removed or renamed
+            let listing = match previous.get(&directory) {
+                Some(listing) if listing.modified == modified => self.refresh_listing(listing),
+                _ => {
+                    *revisited += 1;
+                    match self.list_directory(&directory, modified) {
+                        Some(listing) => listing,
+                        None => continue,
+                    }
+                }
+            };
+
+            // Same depth limit as collect_files
+            if depth + 1 < 10 {
+                pending.extend(listing.subdirectories.iter().map(|subdirectory| (subdirectory.clone(), depth + 1)));
+            }
+            files.extend(listing.files.iter().cloned());
+
+            if let Some(callback) = progress_callback {
+                let total_files = estimated_total.max(files.len());
+                callback(ScanProgress {
+                    current_directory: directory.clone(),
+                    files_scanned: files.len(),
+                    total_files,
+                    current_file: None,
+                    percentage: (files.len() as f32 / total_files.max(1) as f32) * 100.0,
+                });
+            }
+
+            snapshot.insert(directory, listing);
+        }
+    }
+
+    /// List one directory's collectable files and its subdirectories
+    /// A remembered listing with each file read again, since editing a file in place leaves its directory's time alone.
+    /// A hash is only kept while the file's size and modification time still match.
+    fn refresh_listing(&self, listing: &DirectorySnapshot) -> DirectorySnapshot {
+        let files = listing.files.iter().filter_map(|known| {
+            let metadata = std::fs::metadata(&known.path).ok()?;
+            if !metadata.is_file() || metadata.len() > self.max_file_size {
+                return None;
+            }
+            let mut file = self.build_file_info(&known.path, &metadata);
+            if file.size == known.size && file.modified == known.modified {
+                file.hash = known.hash.clone();
+            }
+            Some(file)
+        }).collect();
+
+        DirectorySnapshot {
+            modified: listing.modified,
+            files,
+            subdirectories: listing.subdirectories.clone(),
+        }
+    }
+
+    fn list_directory(&self, directory: &Path, modified: DateTime<Utc>) -> Option<DirectorySnapshot> {
+        let mut listing = DirectorySnapshot {
+            modified,
+            files: Vec::new(),
+            subdirectories: Vec::new(),
+        };
+
+        for entry in std::fs::read_dir(directory).ok()?.filter_map(|e| e.ok()) {
+            let path = entry.path();
+            let Ok(file_type) = entry.file_type() else { continue };
+            if file_type.is_dir() {
+                listing.subdirectories.push(path);
+                continue;
+            }
+            if !file_type.is_file() {
+                continue;
+            }
+
+            // Same filters as collect_files
+            let Ok(metadata) = entry.metadata() else { continue };
+            if metadata.len() > self.max_file_size || self.is_system_file(&path) || self.is_critical_file(&path) {
+                continue;
+            }
+            listing.files.push(self.build_file_info(&path, &metadata));
+        }
+
+        Some(listing)
+    }
+
+    /// Walk one root and record hygiene findings into the result
+    fn scan_directory_hygiene(&self, root: &Path, result: &mut HygieneScanResult) {
+        let mut directories = Vec::new();
+        let mut non_empty: HashSet<PathBuf> = HashSet::new();
+
+        let mut walker = WalkDir::new(root).follow_links(false).into_iter();
+        while let Some(entry) = walker.next() {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    // Unreadable directories are treated as having content
+                    if let Some(path) = e.path() {
+                        non_empty.insert(path.to_path_buf());
+                        mark_ancestors_non_empty(path, root, &mut non_empty);
+                    }
+                    result.errors.push(format!("Failed to read entry: {}", e));
+                    continue;
+                }
+            };
+
+            let path = entry.path();
+            let file_type = entry.file_type();
+
+            if path != root && self.is_excluded_path(path) {
+                mark_ancestors_non_empty(path, root, &mut non_empty);
+                if file_type.is_dir() {
+                    walker.skip_current_dir();
+                }
+                continue;
+            }
+
+            result.total_entries_scanned += 1;
+
+            if path != root {
+                self.check_path_hygiene(path, file_type.is_dir(), result);
+            }
+
+            if file_type.is_dir() {
+                if path != root {
+                    directories.push(path.to_path_buf());
+                }
+                continue;
+            }
 
-                files.push(file_info);
+            // Files and links both count as content for their ancestors
+            mark_ancestors_non_empty(path, root, &mut non_empty);
+
+            if file_type.is_symlink() {
+                if std::fs::metadata(path).is_err() {
+                    let mut finding = HygieneFinding::new(
+                        path.to_path_buf(),
+                        HygieneCategory::BrokenLink,
+                        "Link target does not exist".to_string(),
+                    );
+                    finding.link_target = std::fs::read_link(path).ok();
+                    result.broken_links.push(finding);
+                }
+            } else if file_type.is_file() {
+                let is_empty = entry.metadata().map(|metadata| metadata.len() == 0).unwrap_or(false);
+                if is_empty && !hygiene::is_marker_file(path) && !self.is_system_file(path) && !self.is_critical_file(path) {
+                    result.zero_byte_files.push(HygieneFinding::new(
+                        path.to_path_buf(),
+                        HygieneCategory::ZeroByteFile,
+                        "File is zero bytes".to_string(),
+                    ));
+                }
+            }
+        }
+
+        // Only report the topmost directory of each empty tree
+        for directory in directories {
+            if non_empty.contains(&directory) || self.is_system_directory(&directory) {
+                continue;
+            }
+            let parent_has_content = directory.parent()
+                .map(|parent| parent == root || non_empty.contains(parent))
+                .unwrap_or(true);
+            if parent_has_content {
+                result.empty_directories.push(HygieneFinding::new(
+                    directory,
+                    HygieneCategory::EmptyDirectory,
+                    "Directory contains only empty directories".to_string(),
+                ));
             }
         }
+    }
+
+    /// Check a path for legacy length limits and names invalid on Windows
+    fn check_path_hygiene(&self, path: &Path, is_dir: bool, result: &mut HygieneScanResult) {
+        if let Some(name) = path.file_name().map(|name| name.to_string_lossy().to_string()) {
+            if let Some(reason) = hygiene::invalid_name_reason(&name) {
+                let mut finding = HygieneFinding::new(
+                    path.to_path_buf(),
+                    HygieneCategory::InvalidName,
+                    format!("Name {}", reason),
+                );
+                finding.suggested_name = Some(hygiene::sanitize_file_name(&name));
+                result.invalid_names.push(finding);
+            }
+        }
+
+        // Children of an over-long directory are covered by the directory's own finding
+        let parent_too_long = path.parent()
+            .map(|parent| hygiene::path_length(parent) > hygiene::MAX_DIRECTORY_PATH_LENGTH)
+            .unwrap_or(false);
+        let limit = if is_dir { hygiene::MAX_DIRECTORY_PATH_LENGTH } else { hygiene::MAX_PATH_LENGTH };
+        let length = hygiene::path_length(path);
+
+        if length > limit && !parent_too_long {
+            let mut finding = HygieneFinding::new(
+                path.to_path_buf(),
+                HygieneCategory::LongPath,
+                format!("Path is {} characters, legacy limit is {}", length, limit),
+            );
+            finding.suggested_name = hygiene::shortened_name(path, limit);
+            result.long_paths.push(finding);
+        }
+    }
+
+    /// Remove a directory tree only if it still contains nothing but directories
+    async fn safe_remove_empty_directory(&self, path: &Path) -> Result<()> {
+        self.check_protection(path)?;
+        if self.is_system_directory(path) || self.is_excluded_path(path) {
+            return Err(anyhow!("Attempting to remove protected directory: {}", path.display()));
+        }
+
+        let has_content = WalkDir::new(path)
+            .follow_links(false)
+            .into_iter()
+            .any(|entry| entry.map(|e| !e.file_type().is_dir()).unwrap_or(true));
+        if has_content {
+            return Err(anyhow!("Directory is no longer empty: {}", path.display()));
+        }
+
+        // Bottom-up with remove_dir, which refuses anything that is not empty
+        for entry in WalkDir::new(path).follow_links(false).contents_first(true) {
+            let entry = entry?;
+            tokio::fs::remove_dir(hygiene::extended_length_path(entry.path())).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Delete a file only if it is still zero bytes
+    async fn safe_delete_empty_file(&self, path: &Path, meter: &mut SpaceMeter) -> Result<()> {
+        let metadata = tokio::fs::symlink_metadata(path).await?;
+        if !metadata.is_file() || metadata.len() != 0 {
+            return Err(anyhow!("File is no longer empty: {}", path.display()));
+        }
+
+        self.safe_delete_file(path, meter).await
+    }
+
+    /// Remove a link itself, only if it is still dangling
+    async fn safe_remove_broken_link(&self, path: &Path) -> Result<()> {
+        self.check_protection(path)?;
+
+        let metadata = tokio::fs::symlink_metadata(path).await?;
+        if !metadata.file_type().is_symlink() {
+            return Err(anyhow!("Path is no longer a link: {}", path.display()));
+        }
+        if tokio::fs::metadata(path).await.is_ok() {
+            return Err(anyhow!("Link target exists again: {}", path.display()));
+        }
+
+        // Directory symlinks and junctions need remove_dir on Windows
+        let link = hygiene::extended_length_path(path);
+        if tokio::fs::remove_file(&link).await.is_err() {
+            tokio::fs::remove_dir(&link).await?;
+        }
 
         Ok(())
     }
 
+    /// Rename a file or directory in place without overwriting anything
+    async fn safe_rename(&self, path: &Path, new_name: &str) -> Result<()> {
+        self.check_protection(path)?;
+        if self.is_system_directory(path) {
+            return Err(anyhow!("Attempting to rename protected path: {}", path.display()));
+        }
+
+        let parent = path.parent().ok_or_else(|| anyhow!("Path has no parent: {}", path.display()))?;
+        let target = parent.join(hygiene::unique_name(parent, new_name));
+
+        tokio::fs::rename(hygiene::extended_length_path(path), hygiene::extended_length_path(&target)).await?;
+        info!("Renamed {} to {}", path.display(), target.display());
+
+        Ok(())
+    }
+
+    /// Collect files under one rule location that match the rule.
+    ///
+    /// Unlike `collect_files` this does not skip system directories or large
+    /// files, since rules deliberately target locations such as `%SystemRoot%\Temp`.
+    fn collect_rule_files(&self, rule: &CleanupRule, location: &Path, now: DateTime<Utc>, files: &mut Vec<FileInfo>) -> Result<()> {
+        if self.is_excluded_path(location) {
+            return Err(anyhow!("Location is excluded from cleanup"));
+        }
+
+        let walker = WalkDir::new(location)
+            .follow_links(false)
+            .max_depth(if rule.recursive { usize::MAX } else { 1 })
+            .into_iter()
+            .filter_entry(|entry| !self.is_excluded_path(entry.path()));
+
+        for entry in walker.filter_map(|e| e.ok()).filter(|e| e.file_type().is_file()) {
+            if self.is_critical_file(entry.path()) {
+                continue;
+            }
+            if let Ok(metadata) = entry.metadata() {
+                let file_info = self.build_file_info(entry.path(), &metadata);
+                if rule.matches(&file_info, now) {
+                    files.push(file_info);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check if path falls under one of the excluded locations
+    fn is_excluded_path(&self, path: &Path) -> bool {
+        self.platform.is_excluded(path)
+    }
+
+    /// Build file information from already-fetched metadata
+    fn build_file_info(&self, path: &Path, metadata: &std::fs::Metadata) -> FileInfo {
+        FileInfo {
+            path: path.to_path_buf(),
+            size: metadata.len(),
+            modified: DateTime::from(metadata.modified().unwrap_or_else(|_| std::time::SystemTime::now())),
+            hash: String::new(), // Will be calculated later
+            file_type: self.get_file_extension(path),
+            is_system_file: self.is_system_file(path),
+            is_critical: self.is_critical_file(path),
+        }
+    }
+
+    /// Sorted content hashes and total size of every file below a folder
+    async fn folder_contents(&self, directory: &Path) -> Result<(Vec<String>, u64)> {
+        let mut hashes = Vec::new();
+        let mut size = 0;
+
+        for entry in WalkDir::new(directory).follow_links(false) {
+            let entry = entry?;
+            let path = entry.path();
+            if entry.file_type().is_dir() {
+                continue;
+            }
+            if !entry.file_type().is_file() {
+                return Err(anyhow!("contains a link: {}", path.display()));
+            }
+            self.check_protection(path)?;
+
+            size += entry.metadata()?.len();
+            hashes.push(self.calculate_file_hash(path).await?);
+        }
+
+        hashes.sort();
+        Ok((hashes, size))
+    }
+
+    /// Hash the members of every collected ZIP archive
+    async fn hash_archive_members(&self, files: &[FileInfo], errors: &mut Vec<String>) -> Vec<ArchiveMember> {
+        let mut members = Vec::new();
+        let max_member_size = self.max_file_size;
+
+        for archive in files.iter().filter(|file| archive_scan::is_zip_archive(&file.path)) {
+            let path = archive.path.clone();
+            match tokio::task::spawn_blocking(move || archive_scan::hash_zip_members(&path, max_member_size)).await {
+                Ok(Ok(found)) => members.extend(found),
+                Ok(Err(e)) => errors.push(format!("Failed to read archive {}: {}", archive.path.display(), e)),
+                Err(e) => errors.push(format!("Failed to read archive {}: {}", archive.path.display(), e)),
+            }
+        }
+
+        members
+    }
+
     /// Calculate SHA-256 hash of file
     async fn calculate_file_hash(&self, path: &Path) -> Result<String> {
         let mut file = tokio::fs::File::open(path).await?;
@@ -379,54 +1337,42 @@ impl FileManager {
     async fn process_duplicate_group(
         &self,
         group: &DuplicateGroup,
-        strategy: &KeepStrategy
+        strategy: &KeepStrategy,
+        meter: &mut SpaceMeter
     ) -> Result<(usize, u64)> {
-        let mut files_to_keep = Vec::new();
-        let mut files_to_remove = Vec::new();
-
-        match strategy {
-            KeepStrategy::KeepNewest => {
-                // Keep the most recently modified file
-                let mut sorted_files = group.files.clone();
-                sorted_files.sort_by(|a, b| b.modified.cmp(&a.modified));
-                files_to_keep.push(sorted_files[0].clone());
-                files_to_remove.extend(sorted_files[1..].iter().cloned());
-            }
-            KeepStrategy::KeepOldest => {
-                // Keep the oldest file
-                let mut sorted_files = group.files.clone();
-                sorted_files.sort_by(|a, b| a.modified.cmp(&b.modified));
-                files_to_keep.push(sorted_files[0].clone());
-                files_to_remove.extend(sorted_files[1..].iter().cloned());
-            }
-            KeepStrategy::KeepInSystem => {
-                // Keep files in system directories, remove others
-                for file in &group.files {
-                    if self.is_system_directory(&file.path) {
-                        files_to_keep.push(file.clone());
-                    } else {
-                        files_to_remove.push(file.clone());
-                    }
-                }
-            }
-            KeepStrategy::KeepInProgramFiles => {
-                // Keep files in Program Files, remove others
-                for file in &group.files {
-                    if file.path.starts_with("C:\\Program Files") || file.path.starts_with("C:\\Program Files (x86)") {
-                        files_to_keep.push(file.clone());
-                    } else {
-                        files_to_remove.push(file.clone());
-                    }
-                }
+        let decision = keeper::select_keepers(group, &strategy.keeper_rules());
+        for line in &decision.explanation {
+            info!("{}", line);
+        }
+
+        // Groups may come back hours after the scan; touch nothing unless every copy is unchanged
+        for file in &decision.keep {
+            self.verify_unchanged(file, &group.hash).await
+                .map_err(|e| anyhow!("group skipped, kept copy diverged: {}", e))?;
+        }
+        for file in &decision.remove {
+            self.verify_unchanged(file, &group.hash).await
+                .map_err(|e| anyhow!("group skipped, duplicate diverged: {}", e))?;
+        }
+
+        // A link or alias created since the scan would take the kept copy down with it
+        let kept: Vec<(FileIdentity, &Path)> = decision.keep.iter()
+            .map(|file| (keeper::file_identity(&file.path), file.path.as_path()))
+            .collect();
+        for file in &decision.remove {
+            let identity = keeper::file_identity(&file.path);
+            if let Some((_, kept_path)) = kept.iter().find(|(kept_identity, _)| *kept_identity == identity) {
+                return Err(anyhow!("group skipped, {} is the same file as kept {}", file.path.display(), kept_path.display()));
             }
         }
+        let files_to_remove = decision.remove;
 
         // Remove duplicate files
         let mut removed_count = 0;
         let mut freed_space = 0;
 
         for file in files_to_remove {
-            match self.safe_delete_file(&file.path).await {
+            match self.safe_delete_file(&file.path, meter).await {
                 Ok(_) => {
                     removed_count += 1;
                     freed_space += file.size;
@@ -440,35 +1386,50 @@ impl FileManager {
         Ok((removed_count, freed_space))
     }
 
-    /// Safely delete a file with error handling
-    async fn safe_delete_file(&self, path: &Path) -> Result<()> {
-        // Check if file is critical
-        if self.is_critical_file(path) {
-            return Err(anyhow!("Attempting to delete critical file: {}", path.display()));
+    /// Check that a scanned file is still the same regular file with the same content
+    async fn verify_unchanged(&self, file: &FileInfo, hash: &str) -> Result<()> {
+        let metadata = tokio::fs::symlink_metadata(&file.path).await
+            .map_err(|e| anyhow!("{} is no longer accessible: {}", file.path.display(), e))?;
+        if !metadata.is_file() {
+            return Err(anyhow!("{} is no longer a regular file", file.path.display()));
+        }
+        if metadata.len() != file.size {
+            return Err(anyhow!("{} changed size from {} to {} bytes", file.path.display(), file.size, metadata.len()));
+        }
+        if let Ok(modified) = metadata.modified() {
+            let modified = DateTime::<Utc>::from(modified);
+            if modified != file.modified {
+                return Err(anyhow!("{} was modified at {}", file.path.display(), modified.to_rfc3339()));
+            }
+        }
+        if self.calculate_file_hash(&file.path).await? != hash {
+            return Err(anyhow!("{} no longer has the scanned content", file.path.display()));
         }
 
-        // Move to recycle bin instead of permanent deletion
-        let delete_command = format!("powershell.exe -Command \"Remove-Item '{}' -Force -ErrorAction Stop\"", path.display());
-        
-        let output = tokio::process::Command::new("cmd")
-            .args(&["/C", &delete_command])
-            .output()
-            .await?;
+        Ok(())
+    }
 
-        if !output.status.success() {
-            return Err(anyhow!("Failed to delete file: {}", String::from_utf8_lossy(&output.stderr)));
-        }
+    /// Safely delete a file with error handling
+    async fn safe_delete_file(&self, path: &Path, meter: &mut SpaceMeter) -> Result<()> {
+        self.check_protection(path)?;
 
+        // Measure first, nothing is left to measure afterwards
+        let allocation = space_accounting::file_allocation(path);
+        delete_file(self.runner.as_ref(), path).await?;
+        match allocation {
+            Ok(allocation) => meter.record(allocation),
+            Err(e) => warn!("Could not measure {} before deleting it: {}", path.display(), e),
+        }
         Ok(())
     }
 
     /// Create backup of files before cleanup
     async fn create_cleanup_backup(&self, duplicate_groups: &[DuplicateGroup]) -> Result<PathBuf> {
         let backup_id = format!("cleanup_backup_{}", Utc::now().format("%Y%m%d_%H%M%S"));
-        let backup_path = self.backup_directory.join(&format!("{}.zip", backup_id));
+        let backup_path = self.backup_directory.join(format!("{}.zip", backup_id));
 
         // Create ZIP backup of files to be deleted
-        let file = tokio::fs::File::create(&backup_path).await?;
+        let file = std::fs::File::create(&backup_path)?;
         let mut zip = zip::ZipWriter::new(file);
 
         for group in duplicate_groups {
@@ -477,8 +1438,8 @@ impl FileManager {
                     let options = zip::write::FileOptions::default()
                         .compression_method(zip::CompressionMethod::Deflated);
                     
-                    if let Ok(mut file_in_zip) = zip.start_file(file.path.to_string_lossy(), options) {
-                        let _ = std::io::copy(&mut std::io::Cursor::new(content), &mut file_in_zip);
+                    if zip.start_file(file.path.to_string_lossy(), options).is_ok() {
+                        let _ = std::io::copy(&mut std::io::Cursor::new(content), &mut zip);
                     }
                 }
             }
@@ -490,9 +1451,7 @@ impl FileManager {
 
     /// Check if file is in system directory
     fn is_system_directory(&self, path: &Path) -> bool {
-        path.starts_with("C:\\Windows") || 
-        path.starts_with("C:\\System Volume Information") ||
-        path.starts_with("C:\\$Recycle.Bin")
+        self.platform.is_system_directory(path)
     }
 
     /// Check if file is a system file
@@ -504,24 +1463,24 @@ impl FileManager {
             .unwrap_or(false)
     }
 
-    /// Check if file is critical
+    /// Check if the protection policy forbids touching a file
     fn is_critical_file(&self, path: &Path) -> bool {
-        let critical_extensions = [".sys", ".dll", ".exe", ".drv", ".ocx"];
-        let critical_paths = [
-            "C:\\Windows\\System32",
-            "C:\\Windows\\SysWOW64",
-            "C:\\Windows\\WinSxS",
-        ];
+        self.check_protection(path).is_err()
+    }
 
-        // Check if file has critical extension
-        if let Some(ext) = path.extension() {
-            if critical_extensions.contains(&ext.to_string_lossy().to_lowercase().as_str()) {
-                return true;
-            }
-        }
+    /// Fail with the name of the policy rule that protects `path`
+    fn check_protection(&self, path: &Path) -> Result<()> {
+        self.protection.read().check(path)
+            .map_err(|refusal| anyhow!("{} is protected: {}", path.display(), refusal))
+    }
 
-        // Check if file is in critical path
-        critical_paths.iter().any(|critical_path| path.starts_with(critical_path))
+    /// Like `check_protection`, for a folder removed along with everything below it
+    fn check_tree_protection(&self, path: &Path) -> Result<()> {
+        if self.is_system_directory(path) {
+            return Err(anyhow!("{} is a system directory", path.display()));
+        }
+        self.protection.read().check_tree(path)
+            .map_err(|refusal| anyhow!("{} is protected: {}", path.display(), refusal))
     }
 
     /// Get file extension
@@ -539,4 +1498,282 @@ pub enum KeepStrategy {
     KeepOldest,
     KeepInSystem,
     KeepInProgramFiles,
-}
\ No newline at end of file
+    Rules(KeeperRules),
+}
+
+impl KeepStrategy {
+    /// Express the strategy as keeper rules, which always leave one copy per group
+    pub fn keeper_rules(&self) -> KeeperRules {
+        let prefer_location = |directories: Vec<PathBuf>| KeeperRules {
+            preferences: vec![
+                KeeperPreference::PreferredDirectories(directories.clone()),
+                KeeperPreference::AvoidCopyNames,
+                KeeperPreference::ShortestPath,
+            ],
+            protected_directories: directories,
+        };
+
+        match self {
+            KeepStrategy::KeepNewest => KeeperRules { preferences: vec![KeeperPreference::Newest], protected_directories: Vec::new() },
+            KeepStrategy::KeepOldest => KeeperRules { preferences: vec![KeeperPreference::Oldest], protected_directories: Vec::new() },
+            KeepStrategy::KeepInSystem => prefer_location(keeper::system_directories()),
+            KeepStrategy::KeepInProgramFiles => prefer_location(keeper::program_files_directories()),
+            KeepStrategy::Rules(rules) => rules.clone(),
+        }
+    }
+}
+
+/// Group archive members with each other and with loose files of the same content
+fn group_archive_duplicates(
+    members: Vec<ArchiveMember>,
+    hash_groups: &HashMap<String, Vec<FileInfo>>
+) -> Vec<ArchiveDuplicateGroup> {
+    let mut by_hash: HashMap<String, Vec<ArchiveMember>> = HashMap::new();
+    for member in members {
+        by_hash.entry(member.hash.clone()).or_default().push(member);
+    }
+
+    let mut groups: Vec<ArchiveDuplicateGroup> = by_hash
+        .into_iter()
+        .filter_map(|(hash, members)| {
+            let files = hash_groups.get(&hash).cloned().unwrap_or_default();
+            let copies = (files.len() + members.len()) as u64;
+            if copies < 2 {
+                return None;
+            }
+
+            let size = members[0].size;
+            Some(ArchiveDuplicateGroup {
+                hash,
+                size,
+                files,
+                members,
+                redundant_size: size * (copies - 1),
+            })
+        })
+        .collect();
+
+    groups.sort_by_key(|group| Reverse(group.redundant_size));
+    groups
+}
+
+/// Allocation released by removing a whole folder tree
+fn folder_allocation(directory: &Path) -> FileAllocation {
+    WalkDir::new(directory)
+        .follow_links(false)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| !entry.file_type().is_dir())
+        .filter_map(|entry| space_accounting::file_allocation(entry.path()).ok())
+        .fold(FileAllocation { logical: 0, released: 0 }, |total, file| FileAllocation {
+            logical: total.logical + file.logical,
+            released: total.released + file.released,
+        })
+}
+
+/// Mark every directory between a path and the scan root as having content
+fn mark_ancestors_non_empty(path: &Path, root: &Path, non_empty: &mut HashSet<PathBuf>) {
+    let mut current = path.parent();
+    while let Some(directory) = current {
+        if !non_empty.insert(directory.to_path_buf()) || directory == root {
+            break;
+        }
+        current = directory.parent();
+    }
+}
+
+async fn delete_file(runner: &dyn CommandRunner, path: &Path) -> Result<()> {
+    runner.checked(&delete_command(path)).await
+        .map(|_| ())
+        .map_err(|e| anyhow!("Failed to delete file: {}", e))
+}
+
+#[cfg(windows)]
+fn delete_command(path: &Path) -> CommandLine {
+    // Remove-Item -Force also deletes read-only and hidden files
+    CommandLine::powershell("Remove-Item -LiteralPath $Path -Force -ErrorAction Stop", &[("Path", path.into())])
+}
+
+#[cfg(not(windows))]
+fn delete_command(path: &Path) -> CommandLine {
+    CommandLine::new("rm", ["--".to_string(), path.to_string_lossy().to_string()])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command_runner::{CommandScript, ScriptedResponse, ScriptedRunner};
+    use crate::folder_duplicates::DuplicateFolder;
+    use crate::protection::{ProtectionKind, ProtectionRule};
+
+    fn scratch_directory() -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("file-manager-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    fn write_file(path: &Path, content: &[u8]) -> FileInfo {
+        std::fs::write(path, content).unwrap();
+        let metadata = std::fs::metadata(path).unwrap();
+        FileInfo {
+            path: path.to_path_buf(),
+            size: metadata.len(),
+            modified: DateTime::<Utc>::from(metadata.modified().unwrap()),
+            hash: String::new(),
+            file_type: "tmp".to_string(),
+            is_system_file: false,
+            is_critical: false,
+        }
+    }
+
+    /// Deletions of paths containing `contains` answer with `exit_code`
+    fn deletion(contains: Option<&str>, exit_code: i32, stderr: &str) -> ScriptedResponse {
+        ScriptedResponse {
+            program: delete_command(Path::new("probe")).program,
+            contains: contains.map(str::to_string),
+            exit_code,
+            stdout: String::new(),
+            stderr: stderr.to_string(),
+        }
+    }
+
+    fn manager(directory: &Path, responses: Vec<ScriptedResponse>) -> (FileManager, Arc<ScriptedRunner>) {
+        let runner = Arc::new(ScriptedRunner::new(CommandScript { responses }));
+        (FileManager::with_runner(directory.join("backups"), runner.clone()), runner)
+    }
+
+    #[tokio::test]
+    async fn rescans_rehash_files_edited_in_place() {
+        let directory = scratch_directory();
+        let files = directory.join("files");
+        std::fs::create_dir_all(&files).unwrap();
+        write_file(&files.join("report.txt"), b"quarterly");
+        write_file(&files.join("report copy.txt"), b"quarterly");
+        let (manager, _) = manager(&directory, Vec::new());
+
+        let first = manager.scan_duplicates(vec![files.clone()], DuplicateScanOptions::default(), None).await.unwrap();
+        assert_eq!(first.duplicate_groups.len(), 1);
+
+        // Same size, new content and time; the directory itself is left unchanged
+        let listed = std::fs::metadata(&files).unwrap().modified().unwrap();
+        let edited = files.join("report copy.txt");
+        std::fs::write(&edited, b"Quarterly").unwrap();
+        std::fs::File::options().write(true).open(&edited).unwrap()
+            .set_modified(std::time::SystemTime::now() + std::time::Duration::from_secs(60)).unwrap();
+        assert_eq!(std::fs::metadata(&files).unwrap().modified().unwrap(), listed);
+
+        let rescan = manager.rescan_duplicates(first.session_id.clone()).await.unwrap();
+        assert!(rescan.duplicate_groups.is_empty(), "{:?}", rescan.duplicate_groups);
+        assert_eq!(rescan.total_files, 2);
+        let _ = std::fs::remove_dir_all(&directory);
+    }
+
+    #[tokio::test]
+    async fn keeps_duplicate_folders_holding_a_protected_folder() {
+        let directory = scratch_directory();
+        let (original, copy) = (directory.join("photos"), directory.join("photos (copy)"));
+        for folder in [&original, &copy] {
+            std::fs::create_dir_all(folder.join("edits")).unwrap();
+            write_file(&folder.join("beach.jpg"), b"beach");
+            write_file(&folder.join("edits").join("beach.jpg"), b"beach, cropped");
+        }
+        let (manager, _) = manager(&directory, Vec::new());
+        manager.protection.write().update(ProtectionPolicy {
+            rules: vec![ProtectionRule {
+                id: "edits".to_string(),
+                kind: ProtectionKind::UserFolder,
+                description: "Edited photos".to_string(),
+                locations: vec![copy.join("edits").to_string_lossy().to_string()],
+                extensions: Vec::new(),
+            }],
+            allow_list: Vec::new(),
+        }).unwrap();
+        let folder = |path: &Path| DuplicateFolder { path: path.to_path_buf(), size: 19, file_count: 2 };
+
+        let result = manager.cleanup_duplicate_folders(vec![DuplicateFolderGroup {
+            fingerprint: "photos".to_string(),
+            relation: FolderRelation::Identical,
+            folders: vec![folder(&original), folder(&copy)],
+            keep: original.clone(),
+            total_size: 38,
+            potential_savings: 19,
+        }]).await.unwrap();
+
+        assert_eq!(result.files_removed, 0);
+        assert_eq!(result.errors.len(), 1, "{:?}", result.errors);
+        assert!(result.errors[0].contains("blocked by user-protected folder \"edits\""), "{}", result.errors[0]);
+        assert!(copy.join("edits").join("beach.jpg").exists());
+        let _ = std::fs::remove_dir_all(&directory);
+    }
+
+    #[tokio::test]
+    async fn deletes_temp_files_through_the_runner() {
+        let directory = scratch_directory();
+        let removable = write_file(&directory.join("setup.tmp"), b"0123456789");
+        let locked = write_file(&directory.join("locked.tmp"), b"01234567890123456789");
+        let (manager, runner) = manager(&directory, vec![
+            deletion(Some("locked.tmp"), 1, "The process cannot access the file because it is being used by another process."),
+            deletion(None, 0, ""),
+        ]);
+
+        let result = manager.cleanup_temp_files(vec![removable.clone(), locked.clone()]).await.unwrap();
+
+        assert_eq!(result.files_removed, 1);
+        assert_eq!(result.space_freed, 10);
+        assert_eq!(result.errors.len(), 1);
+        assert!(result.errors[0].contains("locked.tmp"), "{}", result.errors[0]);
+        assert!(result.errors[0].contains("being used by another process"), "{}", result.errors[0]);
+
+        let commands: Vec<CommandLine> = runner.transcript().entries().into_iter().map(|entry| entry.command).collect();
+        assert_eq!(commands, [delete_command(&removable.path), delete_command(&locked.path)]);
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[tokio::test]
+    async fn refuses_protected_files_without_running_anything() {
+        let directory = scratch_directory();
+        let (manager, runner) = manager(&directory, vec![deletion(None, 0, "")]);
+        let critical = platform::current().critical_directories[0].join("kernel.tmp");
+        let file = FileInfo {
+            path: critical.clone(),
+            size: 1,
+            modified: Utc::now(),
+            hash: String::new(),
+            file_type: "tmp".to_string(),
+            is_system_file: false,
+            is_critical: false,
+        };
+
+        let result = manager.cleanup_temp_files(vec![file]).await.unwrap();
+
+        assert_eq!(result.files_removed, 0);
+        assert!(result.errors[0].contains("is protected"), "{}", result.errors[0]);
+        assert!(runner.transcript().entries().is_empty());
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[tokio::test]
+    async fn deletes_only_the_copies_the_keeper_rules_reject() {
+        let directory = scratch_directory();
+        let (manager, runner) = manager(&directory, vec![deletion(None, 0, "")]);
+        let original = write_file(&directory.join("report.pdf"), b"quarterly numbers");
+        let copy = write_file(&directory.join("report - Copy.pdf"), b"quarterly numbers");
+        let hash = manager.calculate_file_hash(&original.path).await.unwrap();
+        let group = DuplicateGroup {
+            hash,
+            size: original.size,
+            files: vec![copy.clone(), original.clone()],
+            total_size: original.size * 2,
+            potential_savings: original.size,
+        };
+
+        let result = manager.cleanup_duplicates(vec![group], KeepStrategy::Rules(KeeperRules::default()), false).await.unwrap();
+
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.files_removed, 1);
+        assert_eq!(result.space_freed, original.size);
+        let commands: Vec<CommandLine> = runner.transcript().entries().into_iter().map(|entry| entry.command).collect();
+        assert_eq!(commands, [delete_command(&copy.path)]);
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,276 @@
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::keeper;
+
+/// Folders with fewer files are left to the per-file duplicate scan
+const MIN_FOLDER_FILES: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FolderRelation {
+    /// Same names and contents throughout the tree
+    Identical,
+    /// Every file of the first folder also exists somewhere in the kept folder
+    Subset,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateFolder {
+    pub path: PathBuf,
+    pub size: u64,
+    pub file_count: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateFolderGroup {
+    pub fingerprint: String,
+    pub relation: FolderRelation,
+    pub folders: Vec<DuplicateFolder>,
+    /// Folder that survives when the group is cleaned up
+    pub keep: PathBuf,
+    pub total_size: u64,
+    pub potential_savings: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FolderScanResult {
+    pub groups: Vec<DuplicateFolderGroup>,
+    pub total_directories: usize,
+    pub total_files: usize,
+    pub potential_savings: u64,
+    pub scan_duration_ms: u64,
+    pub scanned_directories: Vec<PathBuf>,
+    pub errors: Vec<String>,
+}
+
+/// A hashed file as input to the folder index
+pub struct FolderFile {
+    pub path: PathBuf,
+    pub size: u64,
+    pub hash: String,
+}
+
+#[derive(Default)]
+struct DirectoryNode {
+    /// (name, fingerprint) of direct children, files and directories alike
+    children: Vec<(String, String)>,
+    fingerprint: String,
+    size: u64,
+    file_count: usize,
+    /// Sorted content hash ids of every file below this directory
+    content: Vec<u32>,
+    /// Something below could not be read or must not be removed
+    incomplete: bool,
+}
+
+/// Fingerprint directories bottom-up and report duplicate and contained folder trees.
+///
+/// `incomplete` lists paths whose contents are not fully known; they and their
+/// ancestors are never reported.
+pub fn find_duplicate_folders(
+    directories: Vec<PathBuf>,
+    files: Vec<FolderFile>,
+    incomplete: &HashSet<PathBuf>,
+) -> Vec<DuplicateFolderGroup> {
+    let mut nodes: HashMap<PathBuf, DirectoryNode> = directories
+        .into_iter()
+        .map(|directory| (directory, DirectoryNode::default()))
+        .collect();
+
+    let mut hash_ids: HashMap<String, u32> = HashMap::new();
+    for file in files {
+        let Some(parent) = file.path.parent() else { continue };
+        let next_id = hash_ids.len() as u32;
+        let id = *hash_ids.entry(file.hash.clone()).or_insert(next_id);
+        if let Some(node) = nodes.get_mut(parent) {
+            node.children.push((child_name(&file.path), file.hash));
+            node.size += file.size;
+            node.file_count += 1;
+            node.content.push(id);
+        }
+    }
+
+    for path in incomplete {
+        for ancestor in path.ancestors() {
+            if let Some(node) = nodes.get_mut(ancestor) {
+                node.incomplete = true;
+            }
+        }
+    }
+
+    // Deepest first, so every child is final before its parent folds it in
+    let mut order: Vec<PathBuf> = nodes.keys().cloned().collect();
+    order.sort_by_key(|path| std::cmp::Reverse(path.components().count()));
+
+    for path in &order {
+        let mut node = nodes.remove(path).unwrap_or_default();
+        node.children.sort();
+        node.content.sort_unstable();
+
+        let mut hasher = Sha256::new();
+        for (name, fingerprint) in &node.children {
+            hasher.update(name.to_lowercase().as_bytes());
+            hasher.update([0]);
+            hasher.update(fingerprint.as_bytes());
+            hasher.update([0]);
+        }
+        node.fingerprint = format!("{:x}", hasher.finalize());
+
+        if let Some(parent) = path.parent().and_then(|parent| nodes.get_mut(parent)) {
+            parent.children.push((child_name(path), node.fingerprint.clone()));
+            parent.size += node.size;
+            parent.file_count += node.file_count;
+            parent.content.extend_from_slice(&node.content);
+        }
+        nodes.insert(path.clone(), node);
+    }
+
+    let eligible = |node: &DirectoryNode| !node.incomplete && node.file_count >= MIN_FOLDER_FILES && node.size > 0;
+
+    let mut groups = identical_groups(&nodes, &eligible);
+    let grouped: HashSet<PathBuf> = groups.iter()
+        .flat_map(|group| group.folders.iter().map(|folder| folder.path.clone()))
+        .collect();
+    let removable: HashSet<PathBuf> = groups.iter()
+        .flat_map(|group| group.folders.iter().map(|folder| folder.path.clone()).filter(move |path| *path != group.keep))
+        .collect();
+    groups.extend(subset_groups(&nodes, &order, &grouped, &removable, &eligible));
+
+    groups.sort_by_key(|group| Reverse(group.potential_savings));
+    groups
+}
+
+fn identical_groups(
+    nodes: &HashMap<PathBuf, DirectoryNode>,
+    eligible: &dyn Fn(&DirectoryNode) -> bool,
+) -> Vec<DuplicateFolderGroup> {
+    let mut by_fingerprint: HashMap<&str, Vec<&PathBuf>> = HashMap::new();
+    for (path, node) in nodes {
+        if eligible(node) {
+            by_fingerprint.entry(node.fingerprint.as_str()).or_default().push(path);
+        }
+    }
+    by_fingerprint.retain(|_, paths| paths.len() > 1);
+
+    // Nested copies are covered by their parents' group when every parent is duplicated too
+    let duplicated: HashSet<&PathBuf> = by_fingerprint.values().flatten().copied().collect();
+    let mut groups = Vec::new();
+    for (fingerprint, mut paths) in by_fingerprint {
+        let covered = paths.iter().all(|path| path.parent()
+            .map(|parent| duplicated.contains(&parent.to_path_buf()))
+            .unwrap_or(false));
+        if covered {
+            continue;
+        }
+
+        paths.sort();
+        let folders: Vec<DuplicateFolder> = paths.iter().map(|path| folder(path, &nodes[*path])).collect();
+        let keep = preferred_folder(&paths);
+        let size = folders[0].size;
+
+        groups.push(DuplicateFolderGroup {
+            fingerprint: fingerprint.to_string(),
+            relation: FolderRelation::Identical,
+            total_size: size * folders.len() as u64,
+            potential_savings: size * (folders.len() as u64 - 1),
+            folders,
+            keep,
+        });
+    }
+
+    groups
+}
+
+fn subset_groups(
+    nodes: &HashMap<PathBuf, DirectoryNode>,
+    order: &[PathBuf],
+    grouped: &HashSet<PathBuf>,
+    removable: &HashSet<PathBuf>,
+    eligible: &dyn Fn(&DirectoryNode) -> bool,
+) -> Vec<DuplicateFolderGroup> {
+    // Which folders contain each content hash, anywhere below them
+    let mut postings: HashMap<u32, Vec<&PathBuf>> = HashMap::new();
+    for (path, node) in nodes {
+        if !eligible(node) {
+            continue;
+        }
+        let mut previous = None;
+        for &id in &node.content {
+            if previous != Some(id) {
+                postings.entry(id).or_default().push(path);
+                previous = Some(id);
+            }
+        }
+    }
+
+    let mut reported: HashSet<PathBuf> = HashSet::new();
+    let mut groups = Vec::new();
+
+    // Shallowest first, so a contained tree is reported once at its top
+    for path in order.iter().rev() {
+        let node = &nodes[path];
+        if !eligible(node) || grouped.contains(path) {
+            continue;
+        }
+        if path.parent().map(|parent| reported.contains(parent) || grouped.contains(parent)).unwrap_or(false) {
+            reported.insert(path.clone());
+            continue;
+        }
+
+        let Some(rarest) = node.content.iter().min_by_key(|id| postings.get(id).map(|p| p.len()).unwrap_or(0)) else { continue };
+
+        let superset = postings.get(rarest)
+            .into_iter()
+            .flatten()
+            .filter(|candidate| **candidate != path && !candidate.starts_with(path) && !path.starts_with(candidate))
+            // Never point at a folder that an identical group would remove
+            .filter(|candidate| !candidate.ancestors().any(|ancestor| removable.contains(ancestor)))
+            .map(|candidate| (*candidate, &nodes[*candidate]))
+            .filter(|(_, candidate)| candidate.file_count > node.file_count && contains_all(&candidate.content, &node.content))
+            .min_by(|(a_path, a), (b_path, b)| a.file_count.cmp(&b.file_count).then_with(|| a_path.cmp(b_path)));
+
+        if let Some((superset_path, superset)) = superset {
+            reported.insert(path.clone());
+            groups.push(DuplicateFolderGroup {
+                fingerprint: node.fingerprint.clone(),
+                relation: FolderRelation::Subset,
+                folders: vec![folder(path, node), folder(superset_path, superset)],
+                keep: superset_path.clone(),
+                total_size: node.size + superset.size,
+                potential_savings: node.size,
+            });
+        }
+    }
+
+    groups
+}
+
+/// Multiset containment over sorted id lists
+pub fn contains_all<T: Ord>(superset: &[T], subset: &[T]) -> bool {
+    let mut candidates = superset.iter();
+    subset.iter().all(|wanted| candidates.any(|candidate| candidate == wanted))
+}
+
+/// Keep the folder that does not look like a copy, then the one with the shortest path
+fn preferred_folder(paths: &[&PathBuf]) -> PathBuf {
+    paths.iter()
+        .copied()
+        .min_by_key(|path| (keeper::looks_like_copy(path), path.as_os_str().len(), path.to_path_buf()))
+        .cloned()
+        .unwrap_or_default()
+}
+
+fn folder(path: &Path, node: &DirectoryNode) -> DuplicateFolder {
+    DuplicateFolder {
+        path: path.to_path_buf(),
+        size: node.size,
+        file_count: node.file_count,
+    }
+}
+
+fn child_name(path: &Path) -> String {
+    path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default()
+}
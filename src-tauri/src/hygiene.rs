@@ -0,0 +1,213 @@
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+
+/// Longest path (without the terminating NUL) legacy Win32 APIs accept
+pub const MAX_PATH_LENGTH: usize = 259;
+
+/// Longest directory path legacy APIs accept, leaving room for an 8.3 file name
+pub const MAX_DIRECTORY_PATH_LENGTH: usize = 247;
+
+/// Zero-byte files that are meaningful markers and must never be reported
+const MARKER_FILE_NAMES: [&str; 4] = ["__init__.py", "py.typed", ".gitkeep", ".keep"];
+
+const INVALID_NAME_CHARACTERS: [char; 9] = ['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+
+const RESERVED_DEVICE_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL",
+    "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9",
+    "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HygieneCategory {
+    EmptyDirectory,
+    ZeroByteFile,
+    BrokenLink,
+    LongPath,
+    InvalidName,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HygieneAction {
+    /// Remove the directory tree, which must still contain only empty directories
+    RemoveEmptyDirectory,
+    /// Delete the file, which must still be zero bytes
+    DeleteEmptyFile,
+    /// Remove the link itself, never its target
+    RemoveLink,
+    /// Truncate the file name so the full path fits the legacy limit
+    ShortenName,
+    /// Rename to a name that is valid on Windows
+    SanitizeName,
+}
+
+impl HygieneCategory {
+    /// Safe cleanup action used for findings of this category
+    pub fn cleanup_action(&self) -> HygieneAction {
+        match self {
+            HygieneCategory::EmptyDirectory => HygieneAction::RemoveEmptyDirectory,
+            HygieneCategory::ZeroByteFile => HygieneAction::DeleteEmptyFile,
+            HygieneCategory::BrokenLink => HygieneAction::RemoveLink,
+            HygieneCategory::LongPath => HygieneAction::ShortenName,
+            HygieneCategory::InvalidName => HygieneAction::SanitizeName,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HygieneFinding {
+    pub path: PathBuf,
+    pub category: HygieneCategory,
+    pub action: HygieneAction,
+    pub detail: String,
+    pub link_target: Option<PathBuf>,
+    pub suggested_name: Option<String>,
+}
+
+impl HygieneFinding {
+    pub fn new(path: PathBuf, category: HygieneCategory, detail: String) -> Self {
+        Self {
+            path,
+            category,
+            action: category.cleanup_action(),
+            detail,
+            link_target: None,
+            suggested_name: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HygieneScanResult {
+    pub empty_directories: Vec<HygieneFinding>,
+    pub zero_byte_files: Vec<HygieneFinding>,
+    pub broken_links: Vec<HygieneFinding>,
+    pub long_paths: Vec<HygieneFinding>,
+    pub invalid_names: Vec<HygieneFinding>,
+    pub total_entries_scanned: usize,
+    pub scan_duration_ms: u64,
+    pub scanned_directories: Vec<PathBuf>,
+    pub errors: Vec<String>,
+}
+
+/// Check if a zero-byte file is a meaningful marker file
+pub fn is_marker_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .map(|name| MARKER_FILE_NAMES.contains(&name))
+        .unwrap_or(false)
+}
+
+/// Path length in UTF-16 code units, which is what the Win32 limit counts
+pub fn path_length(path: &Path) -> usize {
+    path.to_string_lossy().encode_utf16().count()
+}
+
+/// Explain why a single path component is not a valid Windows file name
+pub fn invalid_name_reason(name: &str) -> Option<String> {
+    if let Some(c) = name.chars().find(|c| INVALID_NAME_CHARACTERS.contains(c) || (*c as u32) < 32) {
+        return Some(format!("contains invalid character {:?}", c));
+    }
+
+    if name.ends_with(' ') || name.ends_with('.') {
+        return Some("ends with a space or period".to_string());
+    }
+
+    let stem = name.split('.').next().unwrap_or(name).trim_end();
+    if RESERVED_DEVICE_NAMES.iter().any(|reserved| reserved.eq_ignore_ascii_case(stem)) {
+        return Some(format!("uses reserved device name {}", stem.to_uppercase()));
+    }
+
+    None
+}
+
+/// Produce a valid Windows file name by replacing or trimming offending parts
+pub fn sanitize_file_name(name: &str) -> String {
+    let mut sanitized: String = name
+        .chars()
+        .map(|c| if INVALID_NAME_CHARACTERS.contains(&c) || (c as u32) < 32 { '_' } else { c })
+        .collect();
+
+    let trimmed_len = sanitized.trim_end_matches([' ', '.']).len();
+    sanitized.truncate(trimmed_len);
+
+    let stem = sanitized.split('.').next().unwrap_or("").trim_end().to_string();
+    if RESERVED_DEVICE_NAMES.iter().any(|reserved| reserved.eq_ignore_ascii_case(&stem)) {
+        sanitized.insert(0, '_');
+    }
+
+    if sanitized.is_empty() {
+        sanitized.push('_');
+    }
+
+    sanitized
+}
+
+/// Shorten the file name (keeping its extension) so the whole path fits `max_length`
+pub fn shortened_name(path: &Path, max_length: usize) -> Option<String> {
+    let parent_length = path_length(path.parent()?) + 1; // separator
+    let name = path.file_name()?.to_string_lossy().to_string();
+    let extension = path.extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()))
+        .unwrap_or_default();
+
+    let extension_length = extension.encode_utf16().count();
+    let available = max_length.checked_sub(parent_length + extension_length)?;
+    if available == 0 {
+        return None;
+    }
+
+    let stem = name.strip_suffix(extension.as_str()).unwrap_or(&name);
+    let mut shortened = String::new();
+    let mut used = 0;
+    for c in stem.chars() {
+        if used + c.len_utf16() > available {
+            break;
+        }
+        used += c.len_utf16();
+        shortened.push(c);
+    }
+
+    let shortened = shortened.trim_end_matches([' ', '.']);
+    if shortened.is_empty() {
+        return None;
+    }
+
+    Some(format!("{}{}", shortened, extension))
+}
+
+/// Pick a name in `directory` that does not exist yet, appending " (n)" if needed
+pub fn unique_name(directory: &Path, name: &str) -> String {
+    if !directory.join(name).exists() {
+        return name.to_string();
+    }
+
+    let path = Path::new(name);
+    let stem = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let extension = path.extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()))
+        .unwrap_or_default();
+
+    (1..)
+        .map(|n| format!("{} ({}){}", stem, n, extension))
+        .find(|candidate| !directory.join(candidate).exists())
+        .unwrap_or_else(|| name.to_string())
+}
+
+/// Prefix absolute paths with `\\?\` so names Win32 normally rejects can be addressed
+#[cfg(windows)]
+pub fn extended_length_path(path: &Path) -> PathBuf {
+    let raw = path.as_os_str().to_string_lossy();
+    if raw.starts_with(r"\\?\") || !path.is_absolute() {
+        return path.to_path_buf();
+    }
+    if let Some(unc) = raw.strip_prefix(r"\\") {
+        return PathBuf::from(format!(r"\\?\UNC\{}", unc));
+    }
+    PathBuf::from(format!(r"\\?\{}", raw))
+}
+
+#[cfg(not(windows))]
+pub fn extended_length_path(path: &Path) -> PathBuf {
+    path.to_path_buf()
+}
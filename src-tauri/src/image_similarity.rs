@@ -0,0 +1,345 @@
+use std::path::{Path, PathBuf};
+use anyhow::Result;
+use image::imageops::FilterType;
+use image::{GenericImageView, GrayImage};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::file_manager::FileInfo;
+
+/// Image formats the perceptual hasher can decode
+pub const SUPPORTED_IMAGE_EXTENSIONS: [&str; 8] = ["jpg", "jpeg", "png", "gif", "bmp", "webp", "tif", "tiff"];
+
+/// Larger images are skipped rather than decoded; 16384 pixels covers any camera or scanner output
+const MAX_IMAGE_DIMENSION: u32 = 16384;
+/// Memory one decode may hold, pixels included
+const MAX_DECODE_BYTES: u64 = 256 * 1024 * 1024;
+/// Images decoded at once, so the worst case stays near 1 GiB whatever the core count
+const MAX_PARALLEL_DECODES: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PerceptualHashAlgorithm {
+    /// Difference hash: compares neighbouring pixels of a 9x8 thumbnail
+    DHash,
+    /// DCT hash: compares low-frequency coefficients of a 32x32 thumbnail
+    PHash,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImageScanOptions {
+    pub algorithm: PerceptualHashAlgorithm,
+    /// Maximum Hamming distance (out of 64 bits) for two images to be grouped
+    pub max_distance: u32,
+}
+
+impl Default for ImageScanOptions {
+    fn default() -> Self {
+        Self {
+            algorithm: PerceptualHashAlgorithm::DHash,
+            max_distance: 10,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimilarImage {
+    pub file: FileInfo,
+    pub width: u32,
+    pub height: u32,
+    pub perceptual_hash: String,
+    pub hamming_distance: u32,
+    pub similarity: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimilarImageGroup {
+    pub hash: String,
+    pub size: u64,
+    pub files: Vec<SimilarImage>,
+    pub total_size: u64,
+    pub potential_savings: u64,
+    pub recommended_keep: PathBuf,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageSimilarityResult {
+    pub total_images: usize,
+    pub images_hashed: usize,
+    pub similar_groups: Vec<SimilarImageGroup>,
+    pub scan_duration_ms: u64,
+    pub scanned_directories: Vec<PathBuf>,
+    pub errors: Vec<String>,
+}
+
+/// Hashed image awaiting clustering
+struct HashedImage {
+    file: FileInfo,
+    width: u32,
+    height: u32,
+    hash: u64,
+}
+
+/// Check if a file looks like an image we can hash
+pub fn is_supported_image(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| SUPPORTED_IMAGE_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+/// Decode an image and compute its perceptual hash and resolution
+pub fn compute_perceptual_hash(path: &Path, algorithm: PerceptualHashAlgorithm) -> Result<(u64, u32, u32)> {
+    let mut limits = image::Limits::default();
+    limits.max_image_width = Some(MAX_IMAGE_DIMENSION);
+    limits.max_image_height = Some(MAX_IMAGE_DIMENSION);
+    limits.max_alloc = Some(MAX_DECODE_BYTES);
+
+    let mut reader = image::ImageReader::open(path)?.with_guessed_format()?;
+    reader.limits(limits);
+    let image = reader.decode()?;
+    let (width, height) = image.dimensions();
+    let gray = image.into_luma8();
+
+    let hash = match algorithm {
+        PerceptualHashAlgorithm::DHash => dhash(&gray),
+        PerceptualHashAlgorithm::PHash => phash(&gray),
+    };
+
+    Ok((hash, width, height))
+}
+
+/// Hamming distance between two 64-bit perceptual hashes
+pub fn hamming_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// Hash and cluster images, returning the hashed count, the groups and any errors
+pub fn find_similar_images(files: Vec<FileInfo>, options: &ImageScanOptions) -> (usize, Vec<SimilarImageGroup>, Vec<String>) {
+    let hash_file = |file: FileInfo| {
+        compute_perceptual_hash(&file.path, options.algorithm)
+            .map(|(hash, width, height)| HashedImage { file: file.clone(), width, height, hash })
+            .map_err(|e| format!("Failed to decode image {}: {}", file.path.display(), e))
+    };
+    // A pool of its own bounds how many decoded images are in memory at once
+    let results: Vec<Result<HashedImage, String>> = match rayon::ThreadPoolBuilder::new().num_threads(MAX_PARALLEL_DECODES).build() {
+        Ok(pool) => pool.install(|| files.into_par_iter().map(hash_file).collect()),
+        Err(_) => files.into_iter().map(hash_file).collect(),
+    };
+
+    let mut hashed = Vec::new();
+    let mut errors = Vec::new();
+    for result in results {
+        match result {
+            Ok(image) => hashed.push(image),
+            Err(e) => errors.push(e),
+        }
+    }
+
+    let hashed_count = hashed.len();
+    (hashed_count, cluster_images(hashed, options.max_distance), errors)
+}
+
+/// Greedily cluster images around the highest-quality unassigned image.
+///
+/// Every member of a group is within `max_distance` of the group's keeper, so
+/// chains of slightly-different images never merge into one oversized group.
+fn cluster_images(mut images: Vec<HashedImage>, max_distance: u32) -> Vec<SimilarImageGroup> {
+    // Highest resolution first, then largest file as the tie-breaker
+    images.sort_by(|a, b| {
+        let pixels_a = a.width as u64 * a.height as u64;
+        let pixels_b = b.width as u64 * b.height as u64;
+        pixels_b.cmp(&pixels_a).then(b.file.size.cmp(&a.file.size))
+    });
+
+    let mut assigned = vec![false; images.len()];
+    let mut groups = Vec::new();
+
+    for i in 0..images.len() {
+        if assigned[i] {
+            continue;
+        }
+
+        let keeper_hash = images[i].hash;
+        let members: Vec<usize> = (i..images.len())
+            .filter(|&j| !assigned[j] && hamming_distance(keeper_hash, images[j].hash) <= max_distance)
+            .collect();
+
+        if members.len() < 2 {
+            continue;
+        }
+
+        let files: Vec<SimilarImage> = members.iter().map(|&j| {
+            assigned[j] = true;
+            let distance = hamming_distance(keeper_hash, images[j].hash);
+            SimilarImage {
+                file: images[j].file.clone(),
+                width: images[j].width,
+                height: images[j].height,
+                perceptual_hash: format!("{:016x}", images[j].hash),
+                hamming_distance: distance,
+                similarity: 1.0 - distance as f32 / 64.0,
+            }
+        }).collect();
+
+        let total_size: u64 = files.iter().map(|f| f.file.size).sum();
+        let keeper_size = files[0].file.size;
+
+        groups.push(SimilarImageGroup {
+            hash: format!("{:016x}", keeper_hash),
+            size: keeper_size,
+            recommended_keep: files[0].file.path.clone(),
+            total_size,
+            potential_savings: total_size - keeper_size,
+            files,
+        });
+    }
+
+    groups
+}
+
+/// Difference hash over a 9x8 grayscale thumbnail
+fn dhash(gray: &GrayImage) -> u64 {
+    let small = image::imageops::resize(gray, 9, 8, FilterType::Triangle);
+    let mut hash = 0u64;
+
+    for y in 0..8 {
+        for x in 0..8 {
+            let left = small.get_pixel(x, y)[0];
+            let right = small.get_pixel(x + 1, y)[0];
+            hash = (hash << 1) | (left > right) as u64;
+        }
+    }
+
+    hash
+}
+
+/// DCT-based hash over a 32x32 grayscale thumbnail
+fn phash(gray: &GrayImage) -> u64 {
+    const SIZE: usize = 32;
+    const LOW: usize = 8;
+
+    let small = image::imageops::resize(gray, SIZE as u32, SIZE as u32, FilterType::Triangle);
+    let pixels: Vec<f64> = small.pixels().map(|p| p[0] as f64).collect();
+
+    // Separable 2D DCT-II, only the low-frequency block is needed
+    let cosines: Vec<f64> = (0..LOW)
+        .flat_map(|u| (0..SIZE).map(move |x| {
+            ((2 * x + 1) as f64 * u as f64 * std::f64::consts::PI / (2 * SIZE) as f64).cos()
+        }))
+        .collect();
+
+    let mut rows = vec![0.0; SIZE * LOW];
+    for y in 0..SIZE {
+        for u in 0..LOW {
+            rows[y * LOW + u] = (0..SIZE).map(|x| pixels[y * SIZE + x] * cosines[u * SIZE + x]).sum();
+        }
+    }
+
+    let mut coefficients = [0.0; LOW * LOW];
+    for v in 0..LOW {
+        for u in 0..LOW {
+            coefficients[v * LOW + u] = (0..SIZE).map(|y| rows[y * LOW + u] * cosines[v * SIZE + y]).sum();
+        }
+    }
+
+    // The DC term only reflects average brightness, so leave it out of the median
+    let mut sorted: Vec<f64> = coefficients[1..].to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let median = sorted[sorted.len() / 2];
+
+    coefficients.iter().fold(0u64, |hash, &c| (hash << 1) | (c > median) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageBuffer, Luma};
+
+    /// Overlapping rectangles of different shades, laid out by `seed`; resizing keeps the layout
+    fn scene(width: u32, height: u32, seed: u32) -> GrayImage {
+        let mut state = seed.wrapping_mul(2654435761).wrapping_add(12345);
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            (state % 1000) as f64 / 1000.0
+        };
+        let shapes: Vec<[f64; 5]> = (0..12).map(|_| [next(), next(), 0.1 + next() * 0.4, 0.1 + next() * 0.4, next() * 255.0]).collect();
+
+        ImageBuffer::from_fn(width, height, |x, y| {
+            let (u, v) = (x as f64 / width as f64, y as f64 / height as f64);
+            // Later shapes are drawn on top
+            let shade = shapes.iter()
+                .rev()
+                .find(|[left, top, w, h, _]| u >= *left && u < left + w && v >= *top && v < top + h)
+                .map_or(100.0, |shape| shape[4]);
+            Luma([shade as u8])
+        })
+    }
+
+    fn hash(gray: &GrayImage, algorithm: PerceptualHashAlgorithm) -> u64 {
+        match algorithm {
+            PerceptualHashAlgorithm::DHash => dhash(gray),
+            PerceptualHashAlgorithm::PHash => phash(gray),
+        }
+    }
+
+    #[test]
+    fn resized_and_retouched_copies_stay_close_unrelated_images_do_not() {
+        let original = scene(640, 480, 1);
+        let resized = image::imageops::resize(&original, 320, 240, FilterType::Lanczos3);
+        let mut retouched = original.clone();
+        for pixel in retouched.pixels_mut() {
+            pixel[0] = pixel[0].saturating_add(12);
+        }
+        for (x, y) in (300..340).flat_map(|x| (200..240).map(move |y| (x, y))) {
+            retouched.put_pixel(x, y, Luma([255]));
+        }
+        let unrelated = scene(640, 480, 2);
+        let max_distance = ImageScanOptions::default().max_distance;
+
+        for algorithm in [PerceptualHashAlgorithm::DHash, PerceptualHashAlgorithm::PHash] {
+            let reference = hash(&original, algorithm);
+            assert!(hamming_distance(reference, hash(&resized, algorithm)) <= max_distance, "{:?} resized", algorithm);
+            assert!(hamming_distance(reference, hash(&retouched, algorithm)) <= max_distance, "{:?} retouched", algorithm);
+            assert!(hamming_distance(reference, hash(&unrelated, algorithm)) > max_distance, "{:?} unrelated", algorithm);
+        }
+    }
+
+    #[test]
+    fn groups_copies_around_the_largest_and_skips_oversize_images() {
+        let directory = std::env::temp_dir().join(format!("image-similarity-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let original = scene(640, 480, 1);
+        let images = [
+            ("thumbnail.png", image::imageops::resize(&original, 160, 120, FilterType::Triangle)),
+            ("original.png", original),
+            ("other.png", scene(640, 480, 2)),
+            ("panorama.png", GrayImage::new(MAX_IMAGE_DIMENSION + 1, 1)),
+        ];
+        let files: Vec<FileInfo> = images.iter().map(|(name, image)| {
+            let path = directory.join(name);
+            image.save(&path).unwrap();
+            FileInfo {
+                size: std::fs::metadata(&path).unwrap().len(),
+                path,
+                modified: chrono::Utc::now(),
+                hash: String::new(),
+                file_type: "png".to_string(),
+                is_system_file: false,
+                is_critical: false,
+            }
+        }).collect();
+
+        let (hashed, groups, errors) = find_similar_images(files, &ImageScanOptions::default());
+
+        assert_eq!(hashed, 3);
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert!(errors[0].contains("panorama.png"), "{}", errors[0]);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].recommended_keep, directory.join("original.png"));
+        assert_eq!(groups[0].files.len(), 2);
+        let _ = std::fs::remove_dir_all(&directory);
+    }
+}
@@ -0,0 +1,534 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::bloatware_matching::{AppIdentity, MatchSubject};
+use crate::registry_provider::{self, RegistryHive, RegistryProvider, RegistryValue};
+#[cfg(debug_assertions)]
+use crate::registry_provider::RecordedRegistry;
+
+/// Uninstall registrations: 64-bit machine, 32-bit machine, and per-user
+const UNINSTALL_KEYS: [(RegistryHive, &str); 3] = [
+    (RegistryHive::LocalMachine, r"SOFTWARE\Microsoft\Windows\CurrentVersion\Uninstall"),
+    (RegistryHive::LocalMachine, r"SOFTWARE\WOW6432Node\Microsoft\Windows\CurrentVersion\Uninstall"),
+    (RegistryHive::CurrentUser, r"Software\Microsoft\Windows\CurrentVersion\Uninstall"),
+];
+
+/// Packed upgrade codes with the packed product codes they cover as value names
+const UPGRADE_CODES_KEY: &str = r"SOFTWARE\Classes\Installer\UpgradeCodes";
+
+/// Packages of the current user; SignatureKind is stringified because ConvertTo-Json emits enums as numbers
+pub const APPX_QUERY: &str = "Get-AppxPackage | Select-Object Name, PackageFullName, PackageFamilyName, Publisher, Version, \
+    InstallLocation, IsFramework, NonRemovable, @{Name='SignatureKind';Expression={$_.SignatureKind.ToString()}}, \
+    @{Name='Dependencies';Expression={@($_.Dependencies | ForEach-Object { $_.PackageFamilyName })}} | ConvertTo-Json -Compress";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InstallScope {
+    Machine,
+    User,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum InventorySource {
+    /// Full path of the Uninstall subkey
+    UninstallKey(String),
+    Appx { package_full_name: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstalledApplication {
+    pub display_name: String,
+    pub publisher: String,
+    pub version: String,
+    pub install_location: Option<PathBuf>,
+    pub size_bytes: Option<u64>,
+    pub uninstall_command: Option<String>,
+    pub quiet_uninstall_command: Option<String>,
+    pub scope: InstallScope,
+    pub identity: AppIdentity,
+    /// Appx runtime packages other apps depend on
+    pub is_framework: bool,
+    /// Package families of the Appx packages this one declares as dependencies
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+    pub removable: bool,
+    /// Every registration the application was found under
+    pub sources: Vec<InventorySource>,
+}
+
+impl InstalledApplication {
+    pub fn match_subject(&self) -> MatchSubject<'_> {
+        MatchSubject {
+            display_name: &self.display_name,
+            publisher: &self.publisher,
+            version: &self.version,
+            identity: &self.identity,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Inventory {
+    pub applications: Vec<InstalledApplication>,
+    /// Sources that could not be read; the rest of the inventory is still valid
+    pub errors: Vec<String>,
+}
+
+/// Recorded inputs of an inventory run, replayable on any machine; debug builds only
+#[cfg(debug_assertions)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct InventoryFixture {
+    pub registry: RecordedRegistry,
+    /// Raw output of `APPX_QUERY`
+    pub appx_json: Option<String>,
+}
+
+#[cfg(debug_assertions)]
+impl InventoryFixture {
+    /// Snapshot the keys the inventory reads
+    pub fn record(registry: &dyn RegistryProvider, appx_json: Option<String>) -> Result<Self> {
+        let mut recorded = RecordedRegistry::default();
+        for (hive, path) in UNINSTALL_KEYS {
+            recorded.keys.extend(RecordedRegistry::record(registry, hive, &[path])?.keys);
+        }
+        recorded.keys.extend(RecordedRegistry::record(registry, RegistryHive::LocalMachine, &[UPGRADE_CODES_KEY])?.keys);
+        Ok(Self { registry: recorded, appx_json })
+    }
+
+    pub fn build(&self) -> Inventory {
+        build(&self.registry, self.appx_json.as_deref())
+    }
+}
+
+/// Merge the Uninstall registrations and Appx packages into one deduplicated list
+pub fn build(registry: &dyn RegistryProvider, appx_json: Option<&str>) -> Inventory {
+    let mut inventory = Inventory::default();
+    let mut applications = read_uninstall_entries(registry, &mut inventory.errors);
+
+    if let Some(json) = appx_json {
+        match parse_appx_packages(json) {
+            Ok(packages) => applications.extend(packages),
+            Err(e) => inventory.errors.push(e.to_string()),
+        }
+    }
+
+    inventory.applications = deduplicate(applications);
+    inventory
+}
+
+pub fn read_uninstall_entries(registry: &dyn RegistryProvider, errors: &mut Vec<String>) -> Vec<InstalledApplication> {
+    let upgrade_codes = match upgrade_codes(registry) {
+        Ok(codes) => codes,
+        Err(e) => {
+            errors.push(format!("Cannot read MSI upgrade codes: {}", e));
+            HashMap::new()
+        }
+    };
+
+    let mut applications = Vec::new();
+    for (hive, path) in UNINSTALL_KEYS {
+        let names = match registry.subkeys(hive, path) {
+            Ok(names) => names,
+            Err(e) => {
+                errors.push(e.to_string());
+                continue;
+            }
+        };
+
+        for name in names {
+            let key = format!("{}\\{}", path, name);
+            match registry.values(hive, &key) {
+                Ok(values) => applications.extend(uninstall_entry(hive, &key, &name, &values, &upgrade_codes)),
+                Err(e) => errors.push(e.to_string()),
+            }
+        }
+    }
+    applications
+}
+
+fn uninstall_entry(
+    hive: RegistryHive,
+    key: &str,
+    name: &str,
+    values: &std::collections::BTreeMap<String, RegistryValue>,
+    upgrade_codes: &HashMap<String, String>,
+) -> Option<InstalledApplication> {
+    let text = |value: &str| {
+        values.get(value)
+            .and_then(RegistryValue::as_text)
+            .map(|text| text.trim().trim_matches('"').trim().to_string())
+            .filter(|text| !text.is_empty())
+    };
+    let number = |value: &str| values.get(value).and_then(RegistryValue::as_number);
+
+    let display_name = text("DisplayName")?;
+    // Components, patches and updates are listed under the products they belong to
+    if number("SystemComponent") == Some(1) || text("ParentKeyName").is_some() {
+        return None;
+    }
+    if text("ReleaseType").is_some_and(|kind| ["Update", "Hotfix", "Security Update", "Update Rollup"].contains(&kind.as_str())) {
+        return None;
+    }
+
+    let product_code = (number("WindowsInstaller") == Some(1))
+        .then(|| normalize_guid(name))
+        .flatten();
+    let upgrade_code = product_code.as_ref().and_then(|code| upgrade_codes.get(code).cloned());
+    let uninstall_command = text("UninstallString");
+
+    Some(InstalledApplication {
+        display_name,
+        publisher: text("Publisher").unwrap_or_default(),
+        version: text("DisplayVersion").unwrap_or_default(),
+        install_location: text("InstallLocation").map(PathBuf::from),
+        size_bytes: number("EstimatedSize").map(|kilobytes| kilobytes * 1024),
+        removable: uninstall_command.is_some() && number("NoRemove") != Some(1),
+        uninstall_command,
+        quiet_uninstall_command: text("QuietUninstallString"),
+        scope: match hive {
+            RegistryHive::LocalMachine => InstallScope::Machine,
+            RegistryHive::CurrentUser => InstallScope::User,
+        },
+        identity: AppIdentity {
+            package_family_name: None,
+            product_code,
+            upgrade_code,
+        },
+        is_framework: false,
+        dependencies: Vec::new(),
+        sources: vec![InventorySource::UninstallKey(registry_provider::full_path(hive, key))],
+    })
+}
+
+/// Product code to upgrade code, both braced and upper-case
+fn upgrade_codes(registry: &dyn RegistryProvider) -> Result<HashMap<String, String>> {
+    let mut codes = HashMap::new();
+    for packed_upgrade in registry.subkeys(RegistryHive::LocalMachine, UPGRADE_CODES_KEY)? {
+        let Some(upgrade) = unpack_guid(&packed_upgrade) else { continue };
+        let products = registry.values(RegistryHive::LocalMachine, &format!("{}\\{}", UPGRADE_CODES_KEY, packed_upgrade))?;
+        for packed_product in products.keys() {
+            if let Some(product) = unpack_guid(packed_product) {
+                codes.insert(product, upgrade.clone());
+            }
+        }
+    }
+    Ok(codes)
+}
+
+/// Installer keys store GUIDs with each group reversed and each byte of the last two groups swapped
+fn unpack_guid(packed: &str) -> Option<String> {
+    if packed.len() != 32 || !packed.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let reversed = |part: &str| part.chars().rev().collect::<String>();
+    let swapped: String = packed.as_bytes()[16..]
+        .chunks(2)
+        .flat_map(|pair| [pair[1] as char, pair[0] as char])
+        .collect();
+    Some(format!(
+        "{{{}-{}-{}-{}-{}}}",
+        reversed(&packed[0..8]),
+        reversed(&packed[8..12]),
+        reversed(&packed[12..16]),
+        &swapped[0..4],
+        &swapped[4..],
+    ).to_uppercase())
+}
+
+fn normalize_guid(name: &str) -> Option<String> {
+    let inner = name.trim().strip_prefix('{')?.strip_suffix('}')?;
+    let groups: Vec<&str> = inner.split('-').collect();
+    let shape = groups.iter().map(|group| group.len()).collect::<Vec<_>>() == [8, 4, 4, 4, 12];
+    (shape && inner.chars().all(|c| c == '-' || c.is_ascii_hexdigit())).then(|| format!("{{{}}}", inner.to_uppercase()))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct AppxRecord {
+    name: String,
+    package_full_name: String,
+    package_family_name: String,
+    #[serde(default)]
+    publisher: String,
+    #[serde(default)]
+    version: String,
+    #[serde(default)]
+    install_location: Option<String>,
+    #[serde(default)]
+    is_framework: bool,
+    #[serde(default)]
+    non_removable: bool,
+    #[serde(default)]
+    signature_kind: Option<String>,
+    #[serde(default, deserialize_with = "one_or_many")]
+    dependencies: Vec<String>,
+}
+
+/// ConvertTo-Json may still write a one-element list as a bare string, and an empty one as null
+fn one_or_many<'de, D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<Vec<String>, D::Error> {
+    match Option::<serde_json::Value>::deserialize(deserializer)? {
+        None | Some(serde_json::Value::Null) => Ok(Vec::new()),
+        Some(serde_json::Value::String(value)) => Ok(vec![value]),
+        Some(value) => serde_json::from_value(value).map_err(serde::de::Error::custom),
+    }
+}
+
+/// ConvertTo-Json emits a bare object for one package and nothing at all for none
+pub fn parse_appx_packages(json: &str) -> Result<Vec<InstalledApplication>> {
+    let json = json.trim().trim_start_matches('\u{feff}');
+    if json.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let value: serde_json::Value = serde_json::from_str(json).map_err(|e| anyhow!("Unreadable Get-AppxPackage output: {}", e))?;
+    let records: Vec<AppxRecord> = match value {
+        serde_json::Value::Array(_) => serde_json::from_value(value),
+        _ => serde_json::from_value(value).map(|record| vec![record]),
+    }.map_err(|e| anyhow!("Unexpected Get-AppxPackage output: {}", e))?;
+
+    Ok(records.into_iter().map(|record| InstalledApplication {
+        display_name: record.name,
+        publisher: publisher_from_subject(&record.publisher),
+        version: record.version,
+        install_location: record.install_location.filter(|location| !location.is_empty()).map(PathBuf::from),
+        size_bytes: None,
+        uninstall_command: None,
+        quiet_uninstall_command: None,
+        scope: InstallScope::User,
+        identity: AppIdentity {
+            package_family_name: Some(record.package_family_name),
+            product_code: None,
+            upgrade_code: None,
+        },
+        is_framework: record.is_framework,
+        dependencies: record.dependencies,
+        // System-signed packages ship with Windows and cannot be removed per user
+        removable: !record.non_removable && record.signature_kind.as_deref() != Some("System"),
+        sources: vec![InventorySource::Appx { package_full_name: record.package_full_name }],
+    }).collect())
+}
+
+/// "CN=Microsoft Corporation, O=Microsoft Corporation, L=Redmond" reads as "Microsoft Corporation"
+fn publisher_from_subject(subject: &str) -> String {
+    let field = |prefix: &str| {
+        subject.split(", ")
+            .find_map(|part| part.strip_prefix(prefix))
+            .map(|value| value.trim_matches('"').to_string())
+    };
+    field("O=").or_else(|| field("CN=")).unwrap_or_else(|| subject.to_string())
+}
+
+/// One entry per product: the same app is often registered in both registry views or both hives
+fn deduplicate(applications: Vec<InstalledApplication>) -> Vec<InstalledApplication> {
+    let mut merged: Vec<InstalledApplication> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+
+    for application in applications {
+        let key = dedup_key(&application);
+        match positions.get(&key) {
+            Some(&position) => {
+                let existing = &mut merged[position];
+                existing.install_location = existing.install_location.take().or(application.install_location);
+                existing.size_bytes = existing.size_bytes.or(application.size_bytes);
+                existing.uninstall_command = existing.uninstall_command.take().or(application.uninstall_command);
+                existing.quiet_uninstall_command = existing.quiet_uninstall_command.take().or(application.quiet_uninstall_command);
+                existing.identity.upgrade_code = existing.identity.upgrade_code.take().or(application.identity.upgrade_code);
+                existing.removable |= application.removable;
+                existing.sources.extend(application.sources);
+            }
+            None => {
+                positions.insert(key, merged.len());
+                merged.push(application);
+            }
+        }
+    }
+    merged
+}
+
+fn dedup_key(application: &InstalledApplication) -> String {
+    if let Some(code) = &application.identity.product_code {
+        return format!("msi:{}", code);
+    }
+    if let Some(family) = &application.identity.package_family_name {
+        return format!("appx:{}", family.to_lowercase());
+    }
+    format!(
+        "name:{}|{}|{}",
+        application.display_name.to_lowercase(),
+        application.publisher.to_lowercase(),
+        application.version,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UNINSTALL_KEYS_FIXTURE: &str = include_str!("../fixtures/inventory/uninstall_keys.json");
+    const APPX_PACKAGES_FIXTURE: &str = include_str!("../fixtures/inventory/appx_packages.json");
+    const APPX_SINGLE_FIXTURE: &str = include_str!("../fixtures/inventory/appx_single.json");
+
+    fn registry() -> RecordedRegistry {
+        serde_json::from_str(UNINSTALL_KEYS_FIXTURE).unwrap()
+    }
+
+    fn find<'a>(inventory: &'a Inventory, name: &str) -> &'a InstalledApplication {
+        inventory.applications.iter()
+            .find(|app| app.display_name == name)
+            .unwrap_or_else(|| panic!("{} missing from the inventory", name))
+    }
+
+    #[test]
+    fn reads_every_uninstall_view() {
+        let inventory = build(&registry(), None);
+
+        assert!(inventory.errors.is_empty(), "{:?}", inventory.errors);
+        let mut names: Vec<&str> = inventory.applications.iter().map(|app| app.display_name.as_str()).collect();
+        names.sort();
+        assert_eq!(names, [
+            "7-Zip 23.01 (x64 edition)",
+            "HP Support Assistant",
+            "McAfee LiveSafe",
+            "Microsoft Edge",
+            "Mozilla Firefox (x64 en-US)",
+            "Spotify",
+        ]);
+        assert_eq!(find(&inventory, "Spotify").scope, InstallScope::User);
+        assert_eq!(find(&inventory, "7-Zip 23.01 (x64 edition)").scope, InstallScope::Machine);
+    }
+
+    #[test]
+    fn parses_uninstall_values() {
+        let inventory = build(&registry(), None);
+
+        let seven_zip = find(&inventory, "7-Zip 23.01 (x64 edition)");
+        assert_eq!(seven_zip.identity.product_code.as_deref(), Some("{23170F69-40C1-2702-2301-000001000000}"));
+        assert_eq!(seven_zip.identity.upgrade_code.as_deref(), Some("{23170F69-40C1-2702-0000-000004000000}"));
+        assert_eq!(seven_zip.size_bytes, Some(5632 * 1024));
+        assert_eq!(seven_zip.install_location, Some(PathBuf::from(r"C:\Program Files\7-Zip\")));
+
+        let firefox = find(&inventory, "Mozilla Firefox (x64 en-US)");
+        assert_eq!(firefox.identity.product_code, None);
+        assert_eq!(firefox.uninstall_command.as_deref(), Some(r"C:\Program Files\Mozilla Firefox\uninstall\helper.exe"));
+        // DWORDs written as strings by some installers still count
+        assert_eq!(firefox.size_bytes, Some(245760 * 1024));
+        assert!(firefox.removable);
+
+        assert!(!find(&inventory, "Microsoft Edge").removable);
+    }
+
+    #[test]
+    fn skips_components_updates_and_unnamed_keys() {
+        let inventory = build(&registry(), None);
+
+        assert!(inventory.applications.iter().all(|app| !app.display_name.contains("Visual C++")));
+        assert!(inventory.applications.iter().all(|app| !app.display_name.contains("KB5034441")));
+        assert!(inventory.applications.iter().all(|app| app.sources.iter().all(|source| {
+            !matches!(source, InventorySource::UninstallKey(key) if key.ends_with("AddressBook"))
+        })));
+    }
+
+    #[test]
+    fn merges_a_product_registered_in_both_registry_views() {
+        let inventory = build(&registry(), None);
+
+        let matches: Vec<&InstalledApplication> = inventory.applications.iter()
+            .filter(|app| app.display_name == "HP Support Assistant")
+            .collect();
+        assert_eq!(matches.len(), 1);
+        let hp = matches[0];
+        assert_eq!(hp.identity.product_code.as_deref(), Some("{7D2F3870-D4A5-4AFC-9D21-5DC19F1F5E11}"));
+        // The 64-bit view is read first; the 32-bit view fills in what it lacks
+        assert_eq!(hp.install_location, Some(PathBuf::from(r"C:\Program Files (x86)\HP\HP Support Framework\")));
+        assert_eq!(hp.size_bytes, Some(102400 * 1024));
+        // A packed upgrade code that does not unpack is ignored
+        assert_eq!(hp.identity.upgrade_code, None);
+        assert_eq!(hp.sources.len(), 2);
+        assert!(hp.sources.iter().any(|source| matches!(source, InventorySource::UninstallKey(key) if key.contains("WOW6432Node"))));
+    }
+
+    #[test]
+    fn merges_a_product_registered_per_machine_and_per_user() {
+        let inventory = build(&registry(), None);
+
+        let mcafee = find(&inventory, "McAfee LiveSafe");
+        assert_eq!(inventory.applications.iter().filter(|app| app.display_name == "McAfee LiveSafe").count(), 1);
+        assert_eq!(mcafee.scope, InstallScope::Machine);
+        assert!(mcafee.uninstall_command.is_some());
+        assert!(mcafee.quiet_uninstall_command.as_deref().is_some_and(|command| command.ends_with("/silent")));
+        assert!(mcafee.sources.iter().any(|source| matches!(source, InventorySource::UninstallKey(key) if key.starts_with("HKEY_CURRENT_USER"))));
+    }
+
+    #[test]
+    fn parses_appx_package_lists() {
+        let packages = parse_appx_packages(APPX_PACKAGES_FIXTURE).unwrap();
+
+        assert_eq!(packages.len(), 4);
+        let weather = &packages[0];
+        assert_eq!(weather.publisher, "Microsoft Corporation");
+        assert_eq!(weather.identity.package_family_name.as_deref(), Some("Microsoft.BingWeather_8wekyb3d8bbwe"));
+        // A single dependency comes through as a bare string
+        assert_eq!(weather.dependencies, ["Microsoft.VCLibs.140.00_8wekyb3d8bbwe"]);
+        assert!(weather.removable);
+
+        let vclibs = &packages[1];
+        assert!(vclibs.is_framework);
+        assert!(vclibs.dependencies.is_empty());
+
+        let shell = &packages[2];
+        assert!(!shell.removable);
+
+        let spotify = &packages[3];
+        assert_eq!(spotify.publisher, "453637B3-4E12-4CDF-B0D3-2A3C863BF6EF");
+        assert_eq!(spotify.install_location, None);
+        assert_eq!(spotify.dependencies.len(), 2);
+    }
+
+    #[test]
+    fn parses_a_single_appx_package_object() {
+        let packages = parse_appx_packages(APPX_SINGLE_FIXTURE).unwrap();
+
+        assert_eq!(packages.len(), 1);
+        assert_eq!(packages[0].display_name, "Microsoft.BingWeather");
+        assert!(matches!(&packages[0].sources[..], [InventorySource::Appx { package_full_name }] if package_full_name.starts_with("Microsoft.BingWeather_4.53")));
+    }
+
+    #[test]
+    fn reads_empty_and_bom_prefixed_appx_output() {
+        assert!(parse_appx_packages("").unwrap().is_empty());
+        assert!(parse_appx_packages("\r\n").unwrap().is_empty());
+        assert_eq!(parse_appx_packages(&format!("\u{feff}{}", APPX_SINGLE_FIXTURE)).unwrap().len(), 1);
+        assert!(parse_appx_packages("Get-AppxPackage : Access is denied.").is_err());
+    }
+
+    #[test]
+    fn keeps_the_registry_inventory_when_appx_output_is_unreadable() {
+        let inventory = build(&registry(), Some("not json"));
+
+        assert_eq!(inventory.errors.len(), 1);
+        assert_eq!(inventory.applications.len(), 6);
+    }
+
+    #[test]
+    fn fixtures_replay_the_recorded_inventory() {
+        let fixture = InventoryFixture::record(&registry(), Some(APPX_PACKAGES_FIXTURE.to_string())).unwrap();
+        let replayed: InventoryFixture = serde_json::from_str(&serde_json::to_string(&fixture).unwrap()).unwrap();
+        let inventory = replayed.build();
+
+        assert!(inventory.errors.is_empty(), "{:?}", inventory.errors);
+        assert_eq!(inventory.applications.len(), 10);
+        assert_eq!(find(&inventory, "HP Support Assistant").sources.len(), 2);
+        assert!(find(&inventory, "Microsoft.BingWeather").identity.package_family_name.is_some());
+    }
+
+    #[test]
+    fn unpacks_installer_guids() {
+        assert_eq!(unpack_guid("96F071321C0420723210000010000000").as_deref(), Some("{23170F69-40C1-2702-2301-000001000000}"));
+        assert_eq!(unpack_guid("96f071321c0420723210000010000000").as_deref(), Some("{23170F69-40C1-2702-2301-000001000000}"));
+        assert_eq!(unpack_guid("not-a-packed-guid"), None);
+        assert_eq!(normalize_guid("{7d2f3870-d4a5-4afc-9d21-5dc19f1f5e11}").as_deref(), Some("{7D2F3870-D4A5-4AFC-9D21-5DC19F1F5E11}"));
+        assert_eq!(normalize_guid("Mozilla Firefox 128.0 (x64 en-US)"), None);
+    }
+}
@@ -0,0 +1,239 @@
+use std::cmp::Ordering;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::file_manager::{DuplicateGroup, FileInfo};
+use crate::path_resolver;
+
+/// One criterion for choosing which copy of a duplicate group survives
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum KeeperPreference {
+    /// Prefer copies inside these directories, earlier entries winning
+    PreferredDirectories(Vec<PathBuf>),
+    /// Prefer copies not named like "Copy of x", "x - Copy" or "x (1)"
+    AvoidCopyNames,
+    ShortestPath,
+    /// Prefer copies whose file name matches this glob, e.g. the camera's `IMG_*.jpg`
+    OriginalNamePattern(String),
+    Newest,
+    Oldest,
+}
+
+/// Ordered keeper preferences; earlier preferences decide before later ones
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeeperRules {
+    pub preferences: Vec<KeeperPreference>,
+    /// Copies inside these directories are never removed
+    pub protected_directories: Vec<PathBuf>,
+}
+
+impl Default for KeeperRules {
+    fn default() -> Self {
+        Self {
+            preferences: vec![
+                KeeperPreference::AvoidCopyNames,
+                KeeperPreference::ShortestPath,
+                KeeperPreference::Oldest,
+            ],
+            protected_directories: Vec::new(),
+        }
+    }
+}
+
+/// What makes two paths the same file on disk
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum FileIdentity {
+    /// Device and inode, or volume serial number and file index
+    Id { volume: u64, index: u64 },
+    /// Canonical path, for files whose ID cannot be read
+    Path(PathBuf),
+}
+
+/// Which copies of a group are kept and why
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeeperDecision {
+    pub hash: String,
+    pub keep: Vec<FileInfo>,
+    pub remove: Vec<FileInfo>,
+    pub explanation: Vec<String>,
+}
+
+/// Pick the surviving copies of a group; at least one copy is always kept
+pub fn select_keepers(group: &DuplicateGroup, rules: &KeeperRules) -> KeeperDecision {
+    let mut decision = KeeperDecision {
+        hash: group.hash.clone(),
+        keep: Vec::new(),
+        remove: Vec::new(),
+        explanation: Vec::new(),
+    };
+
+    let mut ranked: Vec<(&FileInfo, Vec<i64>)> = group.files
+        .iter()
+        .map(|file| (file, rules.preferences.iter().map(|preference| rank(file, preference)).collect()))
+        .collect();
+    ranked.sort_by(|(a, a_key), (b, b_key)| a_key.cmp(b_key).then_with(|| a.path.cmp(&b.path)));
+
+    // Hard links, symlinks and differently spelled paths to one file are a single copy, ranked by its best name
+    let mut identities: Vec<(FileIdentity, &FileInfo)> = Vec::new();
+    let mut aliases: Vec<(&FileInfo, &FileInfo)> = Vec::new();
+    ranked.retain(|(file, _)| {
+        let identity = file_identity(&file.path);
+        match identities.iter().find(|(seen, _)| *seen == identity) {
+            Some((_, original)) => {
+                aliases.push((file, original));
+                false
+            }
+            None => {
+                identities.push((identity, file));
+                true
+            }
+        }
+    });
+
+    let Some((keeper, keeper_key)) = ranked.first() else {
+        return decision;
+    };
+
+    decision.explanation.push(match ranked.get(1) {
+        Some((runner_up, runner_up_key)) => explain(keeper, keeper_key, runner_up, runner_up_key, &rules.preferences),
+        None => format!("Kept {}: only copy in the group", keeper.path.display()),
+    });
+
+    for (index, (file, _)) in ranked.iter().enumerate() {
+        if index == 0 {
+            decision.keep.push((*file).clone());
+        } else if let Some(directory) = rules.protected_directories.iter().find(|directory| is_within(&file.path, directory)) {
+            decision.explanation.push(format!("Kept {}: inside protected directory {}", file.path.display(), directory.display()));
+            decision.keep.push((*file).clone());
+        } else {
+            decision.remove.push((*file).clone());
+        }
+    }
+
+    // Removing another name of a copy frees nothing, and removing the keeper's would lose it
+    for (alias, original) in aliases {
+        decision.explanation.push(format!("Kept {}: same file as {}", alias.path.display(), original.path.display()));
+        decision.keep.push(alias.clone());
+    }
+
+    decision
+}
+
+/// Identify the file behind a path, following symlinks
+pub fn file_identity(path: &Path) -> FileIdentity {
+    match file_id(path) {
+        Some((volume, index)) => FileIdentity::Id { volume, index },
+        None => FileIdentity::Path(std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())),
+    }
+}
+
+#[cfg(not(windows))]
+fn file_id(path: &Path) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(windows)]
+fn file_id(path: &Path) -> Option<(u64, u64)> {
+    use std::os::windows::fs::OpenOptionsExt;
+    use std::os::windows::io::AsRawHandle;
+    use windows::Win32::Foundation::HANDLE;
+    use windows::Win32::Storage::FileSystem::{GetFileInformationByHandle, BY_HANDLE_FILE_INFORMATION};
+
+    // Attribute-only access works on files we may delete but not read
+    let file = std::fs::OpenOptions::new().access_mode(0).open(path).ok()?;
+    let mut information = BY_HANDLE_FILE_INFORMATION::default();
+    unsafe { GetFileInformationByHandle(HANDLE(file.as_raw_handle() as isize), &mut information).ok()? };
+    let index = ((information.nFileIndexHigh as u64) << 32) | information.nFileIndexLow as u64;
+    Some((information.dwVolumeSerialNumber as u64, index))
+}
+
+/// Lower ranks are better
+fn rank(file: &FileInfo, preference: &KeeperPreference) -> i64 {
+    match preference {
+        KeeperPreference::PreferredDirectories(directories) => directories
+            .iter()
+            .position(|directory| is_within(&file.path, directory))
+            .unwrap_or(directories.len()) as i64,
+        KeeperPreference::AvoidCopyNames => looks_like_copy(&file.path) as i64,
+        KeeperPreference::ShortestPath => file.path.as_os_str().len() as i64,
+        KeeperPreference::OriginalNamePattern(pattern) => {
+            let name = file.path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+            !path_resolver::glob_match(pattern, &name) as i64
+        }
+        KeeperPreference::Newest => -file.modified.timestamp(),
+        KeeperPreference::Oldest => file.modified.timestamp(),
+    }
+}
+
+/// Name the preference that separated the keeper from the next best copy
+fn explain(keeper: &FileInfo, keeper_key: &[i64], runner_up: &FileInfo, runner_up_key: &[i64], preferences: &[KeeperPreference]) -> String {
+    let deciding = keeper_key.iter()
+        .zip(runner_up_key)
+        .position(|(a, b)| a.cmp(b) != Ordering::Equal);
+
+    let reason = match deciding.map(|index| (index, &preferences[index])) {
+        Some((index, KeeperPreference::PreferredDirectories(directories))) => match directories.get(keeper_key[index] as usize) {
+            Some(directory) => format!("inside preferred directory {}", directory.display()),
+            None => "closest to a preferred directory".to_string(),
+        },
+        Some((_, KeeperPreference::AvoidCopyNames)) => format!("{} is named like a copy", runner_up.path.display()),
+        Some((_, KeeperPreference::ShortestPath)) => "shortest path".to_string(),
+        Some((_, KeeperPreference::OriginalNamePattern(pattern))) => format!("name matches {}", pattern),
+        Some((_, KeeperPreference::Newest)) => "most recently modified".to_string(),
+        Some((_, KeeperPreference::Oldest)) => "oldest modification time".to_string(),
+        None => "all preferences tied, first path in order".to_string(),
+    };
+
+    format!("Kept {}: {}", keeper.path.display(), reason)
+}
+
+/// Detect Explorer, macOS and browser style copy names
+pub fn looks_like_copy(path: &Path) -> bool {
+    static COPY_NAME: OnceLock<Regex> = OnceLock::new();
+    let pattern = COPY_NAME.get_or_init(|| {
+        Regex::new(r"(?i)^copy( \(\d+\))? of | - copy( \(\d+\))?$| copy( \d+)?$| ?\(\d{1,3}\)$").unwrap()
+    });
+
+    path.file_stem()
+        .map(|stem| pattern.is_match(&stem.to_string_lossy()))
+        .unwrap_or(false)
+}
+
+/// Case-insensitive, component-wise prefix check as Windows compares paths
+pub fn is_within(path: &Path, directory: &Path) -> bool {
+    let mut path_components = path.components();
+    directory.components().all(|component| {
+        path_components.next()
+            .map(|candidate| candidate.as_os_str().to_string_lossy().to_lowercase() == component.as_os_str().to_string_lossy().to_lowercase())
+            .unwrap_or(false)
+    })
+}
+
+/// Windows directory, resolved from the environment
+pub fn system_directories() -> Vec<PathBuf> {
+    resolve_or_default(&["%SystemRoot%"], &[r"C:\Windows"])
+}
+
+/// Program Files directories, resolved from the environment
+pub fn program_files_directories() -> Vec<PathBuf> {
+    resolve_or_default(&["%ProgramFiles%", "%ProgramFiles(x86)%"], &[r"C:\Program Files", r"C:\Program Files (x86)"])
+}
+
+fn resolve_or_default(patterns: &[&str], defaults: &[&str]) -> Vec<PathBuf> {
+    let resolved: Vec<PathBuf> = patterns.iter()
+        .map(|pattern| path_resolver::expand_environment_strings(pattern))
+        .filter(|expanded| !expanded.contains('%'))
+        .map(PathBuf::from)
+        .collect();
+
+    if resolved.is_empty() {
+        defaults.iter().map(PathBuf::from).collect()
+    } else {
+        resolved
+    }
+}
@@ -1,23 +1,66 @@
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
 
 use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
-use tauri::{AppHandle, Manager, WebviewWindow};
 use tokio::sync::RwLock;
 use tracing::{info, warn, error};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 // Import our modules
+#[cfg(windows)]
 mod registry;
 mod file_manager;
 mod bloatware;
-
+mod bloatware_definitions;
+mod bloatware_matching;
+mod inventory;
+mod registry_provider;
+mod winget;
+mod removal_plan;
+mod removal_backup;
+mod dependencies;
+mod command_runner;
+mod image_similarity;
+mod disk_usage;
+mod hygiene;
+mod shortcuts;
+mod path_resolver;
+mod cleanup_rules;
+mod browser;
+mod keeper;
+mod folder_duplicates;
+mod archive_scan;
+mod scan_sessions;
+mod reports;
+mod platform;
+mod protection;
+mod space_accounting;
+
+#[cfg(windows)]
 use registry::{RegistryManager, RegistryBackup, RegistryScanResult, RegistryOperation};
-use file_manager::{FileManager, ScanResult, CleanupResult, ScanProgress, KeepStrategy};
-use bloatware::{BloatwareManager, BloatwareScanResult, UninstallResult, BloatwareCategory};
+use file_manager::{FileManager, ScanResult, CleanupResult, KeepStrategy, DuplicateScanOptions, DuplicateScanSession};
+use bloatware::{BloatwareManager, BloatwareScanResult, UninstallResult, UninstallOptions, BloatwareCategory, BloatwareScanSession};
+use bloatware_definitions::DefinitionStatus;
+#[cfg(windows)]
+use command_runner::CommandLine;
+use command_runner::TranscriptEntry;
+use removal_plan::RemovalPlan;
+use removal_backup::RestoreResult;
+use dependencies::DependencyGraph;
+use image_similarity::{ImageScanOptions, ImageSimilarityResult};
+use disk_usage::{DiskUsageOptions, DiskUsageReport};
+use hygiene::{HygieneFinding, HygieneScanResult};
+use shortcuts::{ShortcutManager, ShortcutScanResult, BrokenShortcut, QuarantinedShortcut, QuarantineResult};
+use cleanup_rules::{CleanupRule, RiskLevel, RuleScanResult};
+use protection::ProtectionPolicy;
+use space_accounting::SpaceAccounting;
+use keeper::KeeperDecision;
+use folder_duplicates::{DuplicateFolderGroup, FolderScanResult};
+use scan_sessions::{ScanDiff, ScanSessionSummary};
+use reports::{ReportData, ReportFormat};
+use browser::{BrowserManager, BrowserScanResult, BrowserCleanResult, BrowserCacheCategory};
 
 // Performance-optimized data structures
 type FileCache = Arc<RwLock<HashMap<String, CachedFileInfo>>>;
@@ -78,7 +121,11 @@ pub struct OptimizationResult {
     pub message: String,
     pub details: Vec<String>,
     pub errors: Vec<String>,
+    /// Estimated from the sizes of removed files
     pub space_freed_mb: u64,
+    /// Measured counterpart of `space_freed_mb`
+    #[serde(default)]
+    pub accounting: SpaceAccounting,
     pub files_removed: usize,
     pub registry_entries_cleaned: usize,
     pub bloatware_removed: usize,
@@ -98,15 +145,18 @@ pub struct BackupInfo {
 pub struct AppState {
     pub file_cache: FileCache,
     pub optimization_running: Arc<RwLock<bool>>,
+    #[cfg(windows)]
     pub registry_manager: Arc<RegistryManager>,
     pub file_manager: Arc<FileManager>,
     pub bloatware_manager: Arc<BloatwareManager>,
+    pub shortcut_manager: Arc<ShortcutManager>,
+    pub browser_manager: Arc<BrowserManager>,
     pub backup_directory: PathBuf,
 }
 
 impl Default for AppState {
     fn default() -> Self {
-        let backup_dir = PathBuf::from("C:\\WindowsOptimizer\\Backups");
+        let backup_dir = platform::current().backup_directory;
         
         // Create backup directory if it doesn't exist
         if !backup_dir.exists() {
@@ -118,9 +168,12 @@ impl Default for AppState {
         Self {
             file_cache: Arc::new(RwLock::new(HashMap::new())),
             optimization_running: Arc::new(RwLock::new(false)),
+            #[cfg(windows)]
             registry_manager: Arc::new(RegistryManager::new(backup_dir.clone())),
             file_manager: Arc::new(FileManager::new(backup_dir.clone())),
             bloatware_manager: Arc::new(BloatwareManager::new(backup_dir.clone())),
+            shortcut_manager: Arc::new(ShortcutManager::new(backup_dir.clone())),
+            browser_manager: Arc::new(BrowserManager::new(backup_dir.clone())),
             backup_directory: backup_dir,
         }
     }
@@ -129,7 +182,7 @@ impl Default for AppState {
 // Tauri command handlers
 
 #[tauri::command]
-pub async fn get_system_info() -> Result<SystemInfo, String> {
+async fn get_system_info() -> Result<SystemInfo, String> {
     let os_version = get_os_version().unwrap_or_else(|_| "Unknown".to_string());
     let (total_memory, free_memory) = get_memory_info().unwrap_or((0, 0));
     let cpu_usage = get_cpu_usage().unwrap_or(0.0);
@@ -148,65 +201,54 @@ pub async fn get_system_info() -> Result<SystemInfo, String> {
 }
 
 #[tauri::command]
-pub async fn scan_installed_applications() -> Result<Vec<AppInfo>, String> {
+async fn scan_installed_applications(state: tauri::State<'_, AppState>) -> Result<Vec<AppInfo>, String> {
+    let inventory = match state.bloatware_manager.installed_inventory().await {
+        Ok(inventory) => inventory,
+        Err(e) => return Err(format!("Failed to read installed applications: {}", e)),
+    };
+    for error in &inventory.errors {
+        warn!("Incomplete application inventory: {}", error);
+    }
+
     let mut apps = Vec::new();
-    
-    // Get applications from WMI
-    let command = r#"
-        Get-WmiObject -Class Win32_Product | 
-        Select-Object Name, Version, InstallLocation, @{Name="SizeMB";Expression={[math]::Round(($_.Size / 1MB), 2)}} |
-        ConvertTo-Json
-    "#;
-
-    match execute_system_command(&format!("powershell.exe -Command \"{}\"", command)).await {
-        Ok(output) => {
-            // Parse JSON output and convert to AppInfo structs
-            // This is a simplified version - in production, you'd want robust JSON parsing
-            let lines: Vec<&str> = output.lines().collect();
-            for line in lines {
-                if line.contains("Name") && line.contains("Version") {
-                    apps.push(AppInfo {
-                        name: "Sample App".to_string(), // Would parse from JSON
-                        version: "1.0".to_string(),
-                        install_location: "C:\\Program Files\\Sample".to_string(),
-                        size_mb: 100,
-                        category: "Utility".to_string(),
-                        is_bloatware: false,
-                        can_uninstall: true,
-                        registry_key: "HKLM\\Software\\Sample".to_string(),
-                    });
-                }
-            }
-        }
-        Err(_) => {
-            // Fallback: Return sample data for demonstration
-            apps.push(AppInfo {
-                name: "Sample Bloatware App".to_string(),
-                version: "1.0".to_string(),
-                install_location: "C:\\Program Files\\Bloatware".to_string(),
-                size_mb: 500,
-                category: "Entertainment".to_string(),
-                is_bloatware: true,
-                can_uninstall: true,
-                registry_key: "HKLM\\Software\\Bloatware".to_string(),
-            });
-        }
+    for app in inventory.applications {
+        let is_bloatware = state.bloatware_manager.identify(&app).await.is_some();
+        let registry_key = app.sources.iter().find_map(|source| match source {
+            inventory::InventorySource::UninstallKey(key) => Some(key.clone()),
+            inventory::InventorySource::Appx { .. } => None,
+        });
+        let category = match (&app.identity.package_family_name, app.is_framework) {
+            (Some(_), true) => "Framework package",
+            (Some(_), false) => "Appx package",
+            (None, _) => "Desktop application",
+        };
+
+        apps.push(AppInfo {
+            name: app.display_name,
+            version: app.version,
+            install_location: app.install_location.map(|location| location.display().to_string()).unwrap_or_default(),
+            size_mb: app.size_bytes.unwrap_or(0) / (1024 * 1024),
+            category: category.to_string(),
+            is_bloatware,
+            can_uninstall: app.removable,
+            registry_key: registry_key.unwrap_or_default(),
+        });
     }
 
     Ok(apps)
 }
 
+#[cfg(windows)]
 #[tauri::command]
-pub async fn scan_services() -> Result<Vec<ServiceInfo>, String> {
+async fn scan_services() -> Result<Vec<ServiceInfo>, String> {
     let mut services = Vec::new();
     
-    let command = r#"
-        Get-Service | 
-        Select-Object Name, DisplayName, Status, StartType |
-        ConvertTo-Json
-    "#;
+    let command = CommandLine::powershell(
+        "Get-Service | Select-Object Name, DisplayName, Status, StartType | ConvertTo-Json",
+        &[],
+    );
 
-    match execute_system_command(&format!("powershell.exe -Command \"{}\"", command)).await {
+    match execute_system_command(&command).await {
         Ok(_) => {
             // Sample services for demonstration
             for service_name in &["Fax", "Windows Search", "Print Spooler", "Remote Registry"] {
@@ -236,141 +278,197 @@ pub async fn scan_services() -> Result<Vec<ServiceInfo>, String> {
     Ok(services)
 }
 
+#[cfg(windows)]
 #[tauri::command]
-pub async fn uninstall_application(app_name: String, registry_key: String) -> Result<OptimizationResult, String> {
-    let mut result = OptimizationResult {
-        success: false,
-        message: String::new(),
-        details: Vec::new(),
-        errors: Vec::new(),
-        space_freed_mb: 0,
+async fn uninstall_application(
+    app_name: String,
+    registry_key: String,
+    state: tauri::State<'_, AppState>
+) -> Result<OptimizationResult, String> {
+    // The key comes from the frontend; only entries of the inventory are uninstalled
+    let removal = state.bloatware_manager.uninstall_installed(&registry_key).await
+        .map_err(|e| format!("Failed to uninstall {}: {}", app_name, e))?;
+
+    Ok(OptimizationResult {
+        success: removal.success,
+        message: if removal.success {
+            format!("Successfully uninstalled {}", removal.app_name)
+        } else {
+            format!("Failed to uninstall {}", removal.app_name)
+        },
+        details: removal.details,
+        errors: removal.errors,
+        space_freed_mb: removal.space_freed_mb,
+        accounting: removal.accounting,
         files_removed: 0,
         registry_entries_cleaned: 0,
         bloatware_removed: 0,
-    };
-
-    let uninstall_methods = vec![
-        format!("wmic product where name=\"{}\" call uninstall", app_name),
-        format!("powershell.exe -Command \"Get-WmiObject -Class Win32_Product | Where-Object {{$_.Name -eq '{}'}} | ForEach-Object {{$_.Uninstall()}}\"", app_name),
-        format!("reg delete \"{}\" /f", registry_key),
-    ];
-
-    for (i, command) in uninstall_methods.iter().enumerate() {
-        match execute_system_command(command).await {
-            Ok(output) => {
-                result.details.push(format!("Method {}: {}", i + 1, output));
-                if output.contains("successful") || output.contains("removed") {
-                    result.success = true;
-                    result.message = format!("Successfully uninstalled {}", app_name);
-                    result.space_freed_mb = 100; // Estimate
-                    result.files_removed = 1;
-                    break;
-                }
-            }
-            Err(e) => {
-                result.errors.push(format!("Method {} failed: {}", i + 1, e));
-            }
-        }
-    }
-
-    if !result.success {
-        result.message = format!("Failed to uninstall {} after trying all methods", app_name);
-    }
-
-    Ok(result)
+    })
 }
 
 #[tauri::command]
-pub async fn optimize_system() -> Result<OptimizationResult, String> {
+async fn optimize_system(state: tauri::State<'_, AppState>) -> Result<OptimizationResult, String> {
+    Ok(run_basic_optimization(&state.file_manager).await)
+}
+
+async fn run_basic_optimization(file_manager: &FileManager) -> OptimizationResult {
     let mut result = OptimizationResult {
         success: true,
         message: "System optimization completed".to_string(),
         details: Vec::new(),
         errors: Vec::new(),
         space_freed_mb: 0,
+        accounting: SpaceAccounting::default(),
         files_removed: 0,
         registry_entries_cleaned: 0,
         bloatware_removed: 0,
     };
 
+    let rule_ids = platform::current().basic_cleanup_rules.iter().map(|id| id.to_string()).collect();
+    match file_manager.scan_cleanup_rules(Some(rule_ids), RiskLevel::Medium).await {
+        Ok(scan) => {
+            result.errors.extend(scan.errors);
+            for rule_match in scan.matches {
+                match file_manager.cleanup_temp_files(rule_match.files).await {
+                    Ok(cleanup) => {
+                        result.space_freed_mb += cleanup.space_freed / (1024 * 1024);
+                        result.accounting.merge(cleanup.accounting);
+                        result.files_removed += cleanup.files_removed;
+                        result.details.push(format!("{}: removed {} files", rule_match.rule_name, cleanup.files_removed));
+                        result.errors.extend(cleanup.errors);
+                    }
+                    Err(e) => {
+                        result.errors.push(format!("{}: {}", rule_match.rule_name, e));
+                    }
+                }
+            }
+        }
+        Err(e) => {
+            result.errors.push(format!("Cleanup rule scan failed: {}", e));
+        }
+    }
+
+    #[cfg(windows)]
     let optimization_commands = vec![
-        ("Cleaning Temp Files", "powershell.exe -Command \"Remove-Item -Path $env:TEMP\\* -Recurse -Force -ErrorAction SilentlyContinue\""),
-        ("Cleaning Windows Temp", "powershell.exe -Command \"Remove-Item -Path C:\\Windows\\Temp\\* -Recurse -Force -ErrorAction SilentlyContinue\""),
-        ("Cleaning Prefetch", "powershell.exe -Command \"Remove-Item -Path C:\\Windows\\Prefetch\\* -Force -ErrorAction SilentlyContinue\""),
-        ("Registry Cleanup", "powershell.exe -Command \"Remove-Item -Path 'HKCU:\\Software\\Microsoft\\Windows\\CurrentVersion\\Explorer\\RecentDocs' -Recurse -Force -ErrorAction SilentlyContinue\""),
-        ("DNS Flush", "ipconfig /flushdns"),
-        ("System File Check", "sfc /scannow"),
+        ("Registry Cleanup", CommandLine::powershell(
+            r"Remove-Item -LiteralPath 'HKCU:\Software\Microsoft\Windows\CurrentVersion\Explorer\RecentDocs' -Recurse -Force -ErrorAction SilentlyContinue",
+            &[],
+        )),
+        ("DNS Flush", CommandLine::new("ipconfig", ["/flushdns"])),
+        ("System File Check", CommandLine::new("sfc", ["/scannow"])),
     ];
 
+    #[cfg(windows)]
     for (description, command) in optimization_commands {
-        match execute_system_command(command).await {
-            Ok(output) => {
+        match execute_system_command(&command).await {
+            Ok(_) => {
                 result.details.push(format!("{}: Success", description));
-                if description.contains("Temp") || description.contains("Prefetch") {
-                    result.space_freed_mb += 50; // Estimate
-                    result.files_removed += 10; // Estimate
-                }
             }
             Err(e) => {
                 result.errors.push(format!("{}: {}", description, e));
-                if result.errors.len() > 3 {
-                    result.success = false;
-                }
             }
         }
     }
 
-    Ok(result)
+    if result.errors.len() > 3 {
+        result.success = false;
+    }
+
+    result
 }
 
 // Registry Management Commands
 
+#[cfg(windows)]
 #[tauri::command]
-pub async fn create_registry_backup(description: String, state: tauri::State<'_, AppState>) -> Result<RegistryBackup, String> {
+async fn create_registry_backup(description: String, state: tauri::State<'_, AppState>) -> Result<RegistryBackup, String> {
     match state.registry_manager.create_backup(description).await {
         Ok(backup) => Ok(backup),
         Err(e) => Err(format!("Failed to create registry backup: {}", e)),
     }
 }
 
+#[cfg(windows)]
 #[tauri::command]
-pub async fn scan_registry_orphaned_entries(state: tauri::State<'_, AppState>) -> Result<RegistryScanResult, String> {
+async fn scan_registry_orphaned_entries(state: tauri::State<'_, AppState>) -> Result<RegistryScanResult, String> {
     match state.registry_manager.scan_orphaned_entries().await {
         Ok(result) => Ok(result),
         Err(e) => Err(format!("Failed to scan registry: {}", e)),
     }
 }
 
+#[cfg(windows)]
 #[tauri::command]
-pub async fn restore_registry_backup(backup_id: String, state: tauri::State<'_, AppState>) -> Result<(), String> {
+async fn restore_registry_backup(backup_id: String, state: tauri::State<'_, AppState>) -> Result<(), String> {
     match state.registry_manager.restore_backup(&backup_id).await {
         Ok(_) => Ok(()),
         Err(e) => Err(format!("Failed to restore registry backup: {}", e)),
     }
 }
 
+#[cfg(windows)]
 #[tauri::command]
-pub async fn list_registry_backups(state: tauri::State<'_, AppState>) -> Result<Vec<RegistryBackup>, String> {
+async fn list_registry_backups(state: tauri::State<'_, AppState>) -> Result<Vec<RegistryBackup>, String> {
     Ok(state.registry_manager.list_backups().await)
 }
 
 // File Management Commands
 
 #[tauri::command]
-pub async fn scan_duplicate_files(
+async fn scan_duplicate_files(
     directories: Vec<String>,
+    options: Option<DuplicateScanOptions>,
     state: tauri::State<'_, AppState>
 ) -> Result<ScanResult, String> {
     let paths: Vec<PathBuf> = directories.into_iter().map(PathBuf::from).collect();
     
-    match state.file_manager.scan_duplicates(paths, None).await {
+    match state.file_manager.scan_duplicates(paths, options.unwrap_or_default(), None).await {
         Ok(result) => Ok(result),
         Err(e) => Err(format!("Failed to scan duplicate files: {}", e)),
     }
 }
 
 #[tauri::command]
-pub async fn cleanup_duplicate_files(
+async fn rescan_duplicate_files(
+    session_id: Option<String>,
+    state: tauri::State<'_, AppState>
+) -> Result<ScanResult, String> {
+    match state.file_manager.rescan_duplicates(session_id).await {
+        Ok(result) => Ok(result),
+        Err(e) => Err(format!("Failed to rescan duplicate files: {}", e)),
+    }
+}
+
+#[tauri::command]
+async fn list_duplicate_scan_sessions(state: tauri::State<'_, AppState>) -> Result<Vec<ScanSessionSummary>, String> {
+    Ok(state.file_manager.list_scan_sessions().await)
+}
+
+#[tauri::command]
+async fn open_duplicate_scan_session(
+    session_id: Option<String>,
+    state: tauri::State<'_, AppState>
+) -> Result<DuplicateScanSession, String> {
+    match state.file_manager.open_scan_session(session_id).await {
+        Ok(session) => Ok(session),
+        Err(e) => Err(format!("Failed to open scan session: {}", e)),
+    }
+}
+
+#[tauri::command]
+async fn diff_duplicate_scan_sessions(
+    from_session: String,
+    to_session: String,
+    state: tauri::State<'_, AppState>
+) -> Result<ScanDiff, String> {
+    match state.file_manager.diff_scan_sessions(&from_session, &to_session).await {
+        Ok(diff) => Ok(diff),
+        Err(e) => Err(format!("Failed to compare scan sessions: {}", e)),
+    }
+}
+
+#[tauri::command]
+async fn cleanup_duplicate_files(
     duplicate_groups: Vec<file_manager::DuplicateGroup>,
     keep_strategy: KeepStrategy,
     create_backup: bool,
@@ -383,7 +481,66 @@ pub async fn cleanup_duplicate_files(
 }
 
 #[tauri::command]
-pub async fn scan_temp_files(state: tauri::State<'_, AppState>) -> Result<Vec<file_manager::FileInfo>, String> {
+async fn preview_duplicate_keepers(
+    duplicate_groups: Vec<file_manager::DuplicateGroup>,
+    keep_strategy: KeepStrategy,
+    state: tauri::State<'_, AppState>
+) -> Result<Vec<KeeperDecision>, String> {
+    Ok(state.file_manager.preview_keepers(&duplicate_groups, &keep_strategy))
+}
+
+#[tauri::command]
+async fn scan_duplicate_folders(
+    directories: Vec<String>,
+    state: tauri::State<'_, AppState>
+) -> Result<FolderScanResult, String> {
+    let paths: Vec<PathBuf> = directories.into_iter().map(PathBuf::from).collect();
+
+    match state.file_manager.scan_duplicate_folders(paths).await {
+        Ok(result) => Ok(result),
+        Err(e) => Err(format!("Failed to scan duplicate folders: {}", e)),
+    }
+}
+
+#[tauri::command]
+async fn cleanup_duplicate_folders(
+    groups: Vec<DuplicateFolderGroup>,
+    state: tauri::State<'_, AppState>
+) -> Result<CleanupResult, String> {
+    match state.file_manager.cleanup_duplicate_folders(groups).await {
+        Ok(result) => Ok(result),
+        Err(e) => Err(format!("Failed to cleanup duplicate folders: {}", e)),
+    }
+}
+
+#[tauri::command]
+async fn scan_similar_images(
+    directories: Vec<String>,
+    options: Option<ImageScanOptions>,
+    state: tauri::State<'_, AppState>
+) -> Result<ImageSimilarityResult, String> {
+    let paths: Vec<PathBuf> = directories.into_iter().map(PathBuf::from).collect();
+
+    match state.file_manager.scan_similar_images(paths, options.unwrap_or_default()).await {
+        Ok(result) => Ok(result),
+        Err(e) => Err(format!("Failed to scan similar images: {}", e)),
+    }
+}
+
+#[tauri::command]
+async fn analyze_disk_usage(
+    root: String,
+    options: Option<DiskUsageOptions>,
+    state: tauri::State<'_, AppState>
+) -> Result<DiskUsageReport, String> {
+    match state.file_manager.analyze_disk_usage(PathBuf::from(root), options.unwrap_or_default()).await {
+        Ok(report) => Ok(report),
+        Err(e) => Err(format!("Failed to analyze disk usage: {}", e)),
+    }
+}
+
+#[tauri::command]
+async fn scan_temp_files(state: tauri::State<'_, AppState>) -> Result<Vec<file_manager::FileInfo>, String> {
     match state.file_manager.scan_temp_files().await {
         Ok(files) => Ok(files),
         Err(e) => Err(format!("Failed to scan temp files: {}", e)),
@@ -391,7 +548,7 @@ pub async fn scan_temp_files(state: tauri::State<'_, AppState>) -> Result<Vec<fi
 }
 
 #[tauri::command]
-pub async fn cleanup_temp_files(
+async fn cleanup_temp_files(
     files: Vec<file_manager::FileInfo>,
     state: tauri::State<'_, AppState>
 ) -> Result<CleanupResult, String> {
@@ -401,10 +558,139 @@ pub async fn cleanup_temp_files(
     }
 }
 
+#[tauri::command]
+async fn scan_cleanup_rules(
+    rule_ids: Option<Vec<String>>,
+    max_risk: Option<RiskLevel>,
+    state: tauri::State<'_, AppState>
+) -> Result<RuleScanResult, String> {
+    match state.file_manager.scan_cleanup_rules(rule_ids, max_risk.unwrap_or(RiskLevel::Low)).await {
+        Ok(result) => Ok(result),
+        Err(e) => Err(format!("Failed to scan cleanup rules: {}", e)),
+    }
+}
+
+#[tauri::command]
+async fn list_cleanup_rules(state: tauri::State<'_, AppState>) -> Result<Vec<CleanupRule>, String> {
+    Ok(state.file_manager.list_cleanup_rules().await)
+}
+
+#[tauri::command]
+async fn save_cleanup_rule(rule: CleanupRule, state: tauri::State<'_, AppState>) -> Result<(), String> {
+    match state.file_manager.save_cleanup_rule(rule).await {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("Failed to save cleanup rule: {}", e)),
+    }
+}
+
+#[tauri::command]
+async fn remove_cleanup_rule(rule_id: String, state: tauri::State<'_, AppState>) -> Result<(), String> {
+    match state.file_manager.remove_cleanup_rule(&rule_id).await {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("Failed to remove cleanup rule: {}", e)),
+    }
+}
+
+#[tauri::command]
+async fn get_protection_policy(state: tauri::State<'_, AppState>) -> Result<ProtectionPolicy, String> {
+    Ok(state.file_manager.protection_policy())
+}
+
+#[tauri::command]
+async fn save_protection_policy(policy: ProtectionPolicy, state: tauri::State<'_, AppState>) -> Result<(), String> {
+    match state.file_manager.save_protection_policy(policy) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("Failed to save protection policy: {}", e)),
+    }
+}
+
+#[tauri::command]
+async fn scan_file_hygiene(
+    directories: Vec<String>,
+    state: tauri::State<'_, AppState>
+) -> Result<HygieneScanResult, String> {
+    let paths: Vec<PathBuf> = directories.into_iter().map(PathBuf::from).collect();
+
+    match state.file_manager.scan_hygiene(paths).await {
+        Ok(result) => Ok(result),
+        Err(e) => Err(format!("Failed to scan file hygiene: {}", e)),
+    }
+}
+
+#[tauri::command]
+async fn cleanup_file_hygiene(
+    findings: Vec<HygieneFinding>,
+    state: tauri::State<'_, AppState>
+) -> Result<CleanupResult, String> {
+    match state.file_manager.cleanup_hygiene(findings).await {
+        Ok(result) => Ok(result),
+        Err(e) => Err(format!("Failed to clean up file hygiene findings: {}", e)),
+    }
+}
+
+// Shortcut Management Commands
+
+#[tauri::command]
+async fn scan_broken_shortcuts(state: tauri::State<'_, AppState>) -> Result<ShortcutScanResult, String> {
+    match state.shortcut_manager.scan_broken_shortcuts().await {
+        Ok(result) => Ok(result),
+        Err(e) => Err(format!("Failed to scan shortcuts: {}", e)),
+    }
+}
+
+#[tauri::command]
+async fn quarantine_broken_shortcuts(
+    shortcuts: Vec<BrokenShortcut>,
+    state: tauri::State<'_, AppState>
+) -> Result<QuarantineResult, String> {
+    match state.shortcut_manager.quarantine_shortcuts(shortcuts).await {
+        Ok(result) => Ok(result),
+        Err(e) => Err(format!("Failed to quarantine shortcuts: {}", e)),
+    }
+}
+
+#[tauri::command]
+async fn restore_quarantined_shortcuts(
+    ids: Vec<String>,
+    state: tauri::State<'_, AppState>
+) -> Result<QuarantineResult, String> {
+    match state.shortcut_manager.restore_shortcuts(ids).await {
+        Ok(result) => Ok(result),
+        Err(e) => Err(format!("Failed to restore shortcuts: {}", e)),
+    }
+}
+
+#[tauri::command]
+async fn list_quarantined_shortcuts(state: tauri::State<'_, AppState>) -> Result<Vec<QuarantinedShortcut>, String> {
+    Ok(state.shortcut_manager.list_quarantined().await)
+}
+
+// Browser Management Commands
+
+#[tauri::command]
+async fn scan_browser_profiles(state: tauri::State<'_, AppState>) -> Result<BrowserScanResult, String> {
+    match state.browser_manager.scan_profiles().await {
+        Ok(result) => Ok(result),
+        Err(e) => Err(format!("Failed to scan browser profiles: {}", e)),
+    }
+}
+
+#[tauri::command]
+async fn clean_browser_caches(
+    profile_ids: Vec<String>,
+    categories: Vec<BrowserCacheCategory>,
+    state: tauri::State<'_, AppState>
+) -> Result<BrowserCleanResult, String> {
+    match state.browser_manager.clean_caches(profile_ids, categories).await {
+        Ok(result) => Ok(result),
+        Err(e) => Err(format!("Failed to clean browser caches: {}", e)),
+    }
+}
+
 // Bloatware Management Commands
 
 #[tauri::command]
-pub async fn scan_bloatware(state: tauri::State<'_, AppState>) -> Result<BloatwareScanResult, String> {
+async fn scan_bloatware(state: tauri::State<'_, AppState>) -> Result<BloatwareScanResult, String> {
     match state.bloatware_manager.scan_bloatware().await {
         Ok(result) => Ok(result),
         Err(e) => Err(format!("Failed to scan bloatware: {}", e)),
@@ -412,34 +698,115 @@ pub async fn scan_bloatware(state: tauri::State<'_, AppState>) -> Result<Bloatwa
 }
 
 #[tauri::command]
-pub async fn uninstall_bloatware(
-    app_name: String,
+async fn list_bloatware_scan_sessions(state: tauri::State<'_, AppState>) -> Result<Vec<ScanSessionSummary>, String> {
+    Ok(state.bloatware_manager.list_scan_sessions().await)
+}
+
+#[tauri::command]
+async fn open_bloatware_scan_session(
+    session_id: Option<String>,
     state: tauri::State<'_, AppState>
-) -> Result<UninstallResult, String> {
-    match state.bloatware_manager.uninstall_bloatware(app_name).await {
-        Ok(result) => Ok(result),
+) -> Result<BloatwareScanSession, String> {
+    match state.bloatware_manager.open_scan_session(session_id).await {
+        Ok(session) => Ok(session),
+        Err(e) => Err(format!("Failed to open bloatware scan session: {}", e)),
+    }
+}
+
+#[tauri::command]
+async fn plan_bloatware_removal(
+    app_names: Vec<String>,
+    options: Option<UninstallOptions>,
+    state: tauri::State<'_, AppState>
+) -> Result<RemovalPlan, String> {
+    match state.bloatware_manager.plan_removal(app_names, options.unwrap_or_default()).await {
+        Ok(plan) => Ok(plan),
+        Err(e) => Err(format!("Failed to plan bloatware removal: {}", e)),
+    }
+}
+
+#[tauri::command]
+async fn execute_bloatware_removal_plan(
+    plan_id: String,
+    state: tauri::State<'_, AppState>
+) -> Result<Vec<UninstallResult>, String> {
+    match state.bloatware_manager.execute_removal_plan(&plan_id).await {
+        Ok(results) => Ok(results),
         Err(e) => Err(format!("Failed to uninstall bloatware: {}", e)),
     }
 }
 
 #[tauri::command]
-pub async fn get_bloatware_categories() -> Result<Vec<BloatwareCategory>, String> {
+async fn get_dependency_graph(state: tauri::State<'_, AppState>) -> Result<DependencyGraph, String> {
+    match state.bloatware_manager.dependency_graph().await {
+        Ok(graph) => Ok(graph),
+        Err(e) => Err(format!("Failed to build dependency graph: {}", e)),
+    }
+}
+
+#[tauri::command]
+async fn get_bloatware_categories() -> Result<Vec<BloatwareCategory>, String> {
     Ok(BloatwareManager::get_bloatware_categories())
 }
 
 #[tauri::command]
-pub async fn get_removal_history(state: tauri::State<'_, AppState>) -> Result<Vec<UninstallResult>, String> {
+async fn get_removal_history(state: tauri::State<'_, AppState>) -> Result<Vec<UninstallResult>, String> {
     Ok(state.bloatware_manager.get_removal_history().await)
 }
 
+#[tauri::command]
+async fn restore_removed_app(backup_id: String, state: tauri::State<'_, AppState>) -> Result<RestoreResult, String> {
+    match state.bloatware_manager.restore_removed_app(&backup_id).await {
+        Ok(result) => Ok(result),
+        Err(e) => Err(format!("Failed to restore removed app: {}", e)),
+    }
+}
+
+// Lets the webview write anywhere, so only development builds register it
+#[cfg(debug_assertions)]
+#[tauri::command]
+async fn record_inventory_fixture(state: tauri::State<'_, AppState>, path: String) -> Result<(), String> {
+    match state.bloatware_manager.record_inventory_fixture(PathBuf::from(path)).await {
+        Ok(()) => Ok(()),
+        Err(e) => Err(format!("Failed to record inventory fixture: {}", e)),
+    }
+}
+
+#[tauri::command]
+async fn get_bloatware_definition_status(state: tauri::State<'_, AppState>) -> Result<DefinitionStatus, String> {
+    Ok(state.bloatware_manager.definition_status().await)
+}
+
+#[tauri::command]
+async fn reload_bloatware_definitions(state: tauri::State<'_, AppState>) -> Result<DefinitionStatus, String> {
+    Ok(state.bloatware_manager.reload_definitions().await)
+}
+
+#[tauri::command]
+async fn get_command_transcript() -> Result<Vec<TranscriptEntry>, String> {
+    Ok(command_runner::shared().transcript().entries())
+}
+
+// Report Commands
+
+#[tauri::command]
+async fn export_report(report: ReportData, format: ReportFormat, path: String) -> Result<(), String> {
+    let path = PathBuf::from(path);
+    match tokio::task::spawn_blocking(move || reports::write_report(&report, format, &path)).await {
+        Ok(Ok(())) => Ok(()),
+        Ok(Err(e)) => Err(format!("Failed to export report: {}", e)),
+        Err(e) => Err(format!("Failed to export report: {}", e)),
+    }
+}
+
 // Comprehensive Optimization Command
 
 #[tauri::command]
-pub async fn perform_comprehensive_optimization(
+async fn perform_comprehensive_optimization(
     include_registry_cleanup: bool,
     include_file_cleanup: bool,
     include_bloatware_removal: bool,
-    create_backups: bool,
+    #[cfg_attr(not(windows), allow(unused_variables))] create_backups: bool,
     state: tauri::State<'_, AppState>
 ) -> Result<OptimizationResult, String> {
     let mut result = OptimizationResult {
@@ -448,6 +815,7 @@ pub async fn perform_comprehensive_optimization(
         details: Vec::new(),
         errors: Vec::new(),
         space_freed_mb: 0,
+        accounting: SpaceAccounting::default(),
         files_removed: 0,
         registry_entries_cleaned: 0,
         bloatware_removed: 0,
@@ -456,8 +824,15 @@ pub async fn perform_comprehensive_optimization(
     info!("Starting comprehensive system optimization");
 
     // Registry cleanup
+    #[cfg(windows)]
     if include_registry_cleanup {
         result.details.push("Starting registry cleanup...".to_string());
+        if create_backups {
+            match state.registry_manager.create_backup("Before comprehensive optimization".to_string()).await {
+                Ok(backup) => result.details.push(format!("Registry backed up to {}", backup.backup_path.display())),
+                Err(e) => result.errors.push(format!("Registry backup failed: {}", e)),
+            }
+        }
         match state.registry_manager.scan_orphaned_entries().await {
             Ok(scan_result) => {
                 result.details.push(format!("Found {} orphaned registry entries", scan_result.total_keys_scanned));
@@ -468,6 +843,10 @@ pub async fn perform_comprehensive_optimization(
             }
         }
     }
+    #[cfg(not(windows))]
+    if include_registry_cleanup {
+        result.details.push("Registry cleanup skipped: only available on Windows".to_string());
+    }
 
     // File cleanup
     if include_file_cleanup {
@@ -478,6 +857,7 @@ pub async fn perform_comprehensive_optimization(
                 match state.file_manager.cleanup_temp_files(temp_files).await {
                     Ok(cleanup_result) => {
                         result.space_freed_mb += cleanup_result.space_freed / (1024 * 1024);
+                        result.accounting.merge(cleanup_result.accounting);
                         result.files_removed += cleanup_result.files_removed;
                         result.details.push(format!("Cleaned up {} temp files", cleanup_result.files_removed));
                     }
@@ -493,6 +873,7 @@ pub async fn perform_comprehensive_optimization(
     }
 
     // Bloatware removal
+    #[cfg(windows)]
     if include_bloatware_removal {
         result.details.push("Starting bloatware scan...".to_string());
         match state.bloatware_manager.scan_bloatware().await {
@@ -505,52 +886,53 @@ pub async fn perform_comprehensive_optimization(
             }
         }
     }
+    #[cfg(not(windows))]
+    if include_bloatware_removal {
+        result.details.push("Bloatware removal skipped: only available on Windows".to_string());
+    }
 
     // Basic system optimization
     result.details.push("Performing basic system optimization...".to_string());
-    let basic_optimization = optimize_system().await;
-    match basic_optimization {
-        Ok(basic_result) => {
-            result.space_freed_mb += basic_result.space_freed_mb;
-            result.files_removed += basic_result.files_removed;
-            result.details.extend(basic_result.details);
-            result.errors.extend(basic_result.errors);
-        }
-        Err(e) => {
-            result.errors.push(format!("Basic optimization failed: {}", e));
-        }
-    }
+    let basic_result = run_basic_optimization(&state.file_manager).await;
+    result.space_freed_mb += basic_result.space_freed_mb;
+    result.accounting.merge(basic_result.accounting);
+    result.files_removed += basic_result.files_removed;
+    result.details.extend(basic_result.details);
+    result.errors.extend(basic_result.errors);
 
     if result.errors.len() > 5 {
         result.success = false;
         result.message = "Optimization completed with errors".to_string();
     }
 
-    info!("Comprehensive optimization completed: {}MB freed, {} files removed", 
-          result.space_freed_mb, result.files_removed);
+    info!("Comprehensive optimization completed: {}MB estimated, {} bytes measured, {} files removed",
+          result.space_freed_mb, result.accounting.measured_bytes, result.files_removed);
 
     Ok(result)
 }
 
 // Helper functions
 
-async fn execute_system_command(command: &str) -> Result<String> {
-    let output = tokio::process::Command::new("cmd")
-        .args(&["/C", command])
-        .output()
-        .await?;
-
-    if output.status.success() {
-        Ok(String::from_utf8_lossy(&output.stdout).to_string())
-    } else {
-        Err(anyhow!("Command failed: {}", String::from_utf8_lossy(&output.stderr)))
-    }
+#[cfg(windows)]
+async fn execute_system_command(command: &CommandLine) -> Result<String> {
+    command_runner::shared().checked(command).await
 }
 
+#[cfg(windows)]
 fn get_os_version() -> Result<String> {
     Ok("Windows 11 Pro".to_string()) // Simplified for demo
 }
 
+#[cfg(not(windows))]
+fn get_os_version() -> Result<String> {
+    let os_release = std::fs::read_to_string("/etc/os-release")?;
+    os_release
+        .lines()
+        .find_map(|line| line.strip_prefix("PRETTY_NAME="))
+        .map(|name| name.trim_matches('"').to_string())
+        .ok_or_else(|| anyhow!("PRETTY_NAME missing from /etc/os-release"))
+}
+
 fn get_memory_info() -> Result<(u64, u64)> {
     Ok((16_000_000_000, 8_000_000_000)) // 16GB total, 8GB free (demo values)
 }
@@ -575,6 +957,7 @@ fn get_system_uptime() -> Result<(u64, String)> {
     Ok((3600, "2024-01-01 12:00:00".to_string())) // Demo values
 }
 
+#[cfg(windows)]
 fn get_recommended_disable_services() -> Vec<String> {
     vec![
         "fax".to_string(),
@@ -604,27 +987,73 @@ pub fn run() {
             // System information
             get_system_info,
             scan_installed_applications,
+            #[cfg(windows)]
             scan_services,
+            #[cfg(windows)]
             uninstall_application,
             optimize_system,
             
             // Registry management
+            #[cfg(windows)]
             create_registry_backup,
+            #[cfg(windows)]
             scan_registry_orphaned_entries,
+            #[cfg(windows)]
             restore_registry_backup,
+            #[cfg(windows)]
             list_registry_backups,
             
             // File management
             scan_duplicate_files,
+            rescan_duplicate_files,
+            list_duplicate_scan_sessions,
+            open_duplicate_scan_session,
+            diff_duplicate_scan_sessions,
             cleanup_duplicate_files,
+            preview_duplicate_keepers,
+            scan_duplicate_folders,
+            cleanup_duplicate_folders,
+            scan_similar_images,
+            analyze_disk_usage,
             scan_temp_files,
             cleanup_temp_files,
+            scan_cleanup_rules,
+            list_cleanup_rules,
+            save_cleanup_rule,
+            remove_cleanup_rule,
+            get_protection_policy,
+            save_protection_policy,
+            scan_file_hygiene,
+            cleanup_file_hygiene,
+            
+            // Shortcut management
+            scan_broken_shortcuts,
+            quarantine_broken_shortcuts,
+            restore_quarantined_shortcuts,
+            list_quarantined_shortcuts,
+            
+            // Browser management
+            scan_browser_profiles,
+            clean_browser_caches,
             
             // Bloatware management
             scan_bloatware,
-            uninstall_bloatware,
+            list_bloatware_scan_sessions,
+            open_bloatware_scan_session,
+            plan_bloatware_removal,
+            execute_bloatware_removal_plan,
+            get_dependency_graph,
             get_bloatware_categories,
             get_removal_history,
+            restore_removed_app,
+            #[cfg(debug_assertions)]
+            record_inventory_fixture,
+            get_bloatware_definition_status,
+            reload_bloatware_definitions,
+            get_command_transcript,
+            
+            // Reports
+            export_report,
             
             // Comprehensive optimization
             perform_comprehensive_optimization,
@@ -0,0 +1,375 @@
+use std::path::PathBuf;
+use anyhow::{anyhow, Result};
+use tracing::warn;
+
+use crate::registry_provider::{self, RegistryHive, RegistryProvider};
+
+/// Every profile Windows knows, by SID
+const PROFILE_LIST_KEY: &str = r"SOFTWARE\Microsoft\Windows NT\CurrentVersion\ProfileList";
+/// SIDs of local and domain user accounts; system and service profiles use others
+const USER_SID_PREFIX: &str = "S-1-5-21-";
+
+/// Variables whose value depends on the profile they are expanded for
+const PROFILE_VARIABLES: &[&str] = &["USERPROFILE", "APPDATA", "LOCALAPPDATA", "TEMP", "TMP"];
+
+/// Expand `%VAR%` references from the current environment, leaving unknown ones intact
+pub fn expand_environment_strings(value: &str) -> String {
+    expand_with(value, |name| std::env::var(name).ok())
+}
+
+/// Names of the `%VAR%` references in `value`
+fn referenced_variables(value: &str) -> Vec<&str> {
+    value.split('%').skip(1).step_by(2).filter(|name| !name.is_empty()).collect()
+}
+
+fn expand_with(value: &str, lookup: impl Fn(&str) -> Option<String>) -> String {
+    let mut expanded = String::new();
+    let mut rest = value;
+
+    while let Some(start) = rest.find('%') {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        match after.find('%') {
+            Some(end) => {
+                let name = &after[..end];
+                match lookup(name) {
+                    Some(resolved) if !name.is_empty() => expanded.push_str(&resolved),
+                    _ => expanded.push_str(&rest[start..start + end + 2]),
+                }
+                rest = &after[end + 1..];
+            }
+            None => {
+                expanded.push_str(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+
+    expanded.push_str(rest);
+    expanded
+}
+
+/// Check for glob wildcards in a pattern
+pub fn has_wildcards(pattern: &str) -> bool {
+    pattern.contains('*') || pattern.contains('?')
+}
+
+/// Check that a definition path names something below its drive, share or variable root.
+/// `%USERPROFILE%\*` or a bare `%LOCALAPPDATA%` would match a whole profile.
+pub fn check_anchored(pattern: &str) -> Result<()> {
+    let absolute = pattern.starts_with(['%', '\\', '/']) || pattern.get(1..2) == Some(":");
+    if !absolute {
+        return Err(anyhow!("{} is not an absolute path", pattern));
+    }
+
+    let components: Vec<&str> = pattern.split(['\\', '/']).filter(|part| !part.is_empty() && *part != ".").collect();
+    if components.contains(&"..") {
+        return Err(anyhow!("{} leaves its root through '..'", pattern));
+    }
+    let root = if pattern.starts_with(r"\\") {
+        2
+    } else {
+        components.iter().take_while(|part| part.ends_with(':') || part.contains('%')).count()
+    };
+    if !components[root.min(components.len())..].iter().any(|part| !has_wildcards(part) && !part.contains('%')) {
+        return Err(anyhow!("{} has no fixed folder below its root", pattern));
+    }
+    if components.last().is_some_and(|last| last.chars().all(|c| c == '*' || c == '?')) {
+        return Err(anyhow!("{} ends in a bare wildcard", pattern));
+    }
+    Ok(())
+}
+
+/// Check that a definition registry key names something below the hive's `SOFTWARE` or `SYSTEM` key.
+/// `HKEY_CURRENT_USER\Software\*` would match every program's settings.
+pub fn check_key_anchored(key: &str) -> Result<()> {
+    let (_, path) = registry_provider::parse_key_path(key).ok_or_else(|| anyhow!("{} has no supported hive", key))?;
+    let components: Vec<&str> = path.split('\\').filter(|part| !part.is_empty()).collect();
+    // The 32-bit view is a second copy of SOFTWARE, not a vendor key
+    let root = if components.get(1).is_some_and(|part| part.eq_ignore_ascii_case("WOW6432Node")) { 2 } else { 1 };
+    if !components.iter().skip(root).any(|part| !has_wildcards(part) && !part.contains('%')) {
+        return Err(anyhow!("{} has no fixed key below {}", key, components.first().unwrap_or(&"its hive")));
+    }
+    if components.last().is_some_and(|last| last.chars().all(|c| c == '*' || c == '?')) {
+        return Err(anyhow!("{} ends in a bare wildcard", key));
+    }
+    Ok(())
+}
+
+/// Case-insensitive glob match supporting `*` and `?`
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            t = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Expand environment variables and wildcard components into existing paths.
+///
+/// Patterns that still reference an unknown variable resolve to nothing rather
+/// than to a literal `%VAR%` directory.
+pub fn resolve_path_pattern(pattern: &str) -> Vec<PathBuf> {
+    resolve_expanded(&expand_environment_strings(pattern))
+}
+
+fn resolve_expanded(expanded: &str) -> Vec<PathBuf> {
+    if expanded.contains('%') {
+        return Vec::new();
+    }
+
+    if let Some(unc) = expanded.strip_prefix(r"\\") {
+        // UNC share: keep \\server\share together as the root
+        let mut parts = unc.splitn(3, ['\\', '/']);
+        let root = format!(r"\\{}\{}\", parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+        return resolve_components(PathBuf::from(root), parts.next().unwrap_or(""));
+    }
+
+    let root = if expanded.starts_with('/') { PathBuf::from("/") } else { PathBuf::new() };
+    resolve_components(root, expanded)
+}
+
+fn resolve_components(root: PathBuf, rest: &str) -> Vec<PathBuf> {
+    let mut candidates = vec![root];
+    for (index, part) in rest.split(['\\', '/']).enumerate() {
+        if part.is_empty() || part == "." {
+            continue;
+        }
+
+        if index == 0 && part.ends_with(':') {
+            candidates = vec![PathBuf::from(format!("{}\\", part))];
+            continue;
+        }
+
+        if !has_wildcards(part) {
+            for candidate in &mut candidates {
+                candidate.push(part);
+            }
+            continue;
+        }
+
+        candidates = candidates
+            .iter()
+            .filter_map(|base| std::fs::read_dir(base).ok())
+            .flat_map(|entries| entries.filter_map(|entry| entry.ok()))
+            .filter(|entry| glob_match(part, &entry.file_name().to_string_lossy()))
+            .map(|entry| entry.path())
+            .collect();
+    }
+
+    candidates.sort();
+    candidates.dedup();
+    candidates.retain(|candidate| !candidate.as_os_str().is_empty() && candidate.exists());
+    candidates
+}
+
+/// A user profile and the per-user variables that point into it
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserProfile {
+    pub sid: Option<String>,
+    pub directory: PathBuf,
+}
+
+impl UserProfile {
+    /// The profile of the user running the app
+    pub fn current() -> Option<Self> {
+        std::env::var_os("USERPROFILE").map(|directory| Self { sid: None, directory: PathBuf::from(directory) })
+    }
+
+    /// Value of a per-user variable in this profile, in the default folder layout
+    fn variable(&self, name: &str) -> Option<String> {
+        let local = self.directory.join("AppData").join("Local");
+        let path = match name.to_uppercase().as_str() {
+            "USERPROFILE" => self.directory.clone(),
+            "APPDATA" => self.directory.join("AppData").join("Roaming"),
+            "LOCALAPPDATA" => local,
+            "TEMP" | "TMP" => local.join("Temp"),
+            _ => return None,
+        };
+        Some(path.to_string_lossy().to_string())
+    }
+}
+
+/// User profiles registered under ProfileList; system and service profiles are left out
+pub fn user_profiles(registry: &dyn RegistryProvider) -> Result<Vec<UserProfile>> {
+    let mut profiles = Vec::new();
+    for sid in registry.subkeys(RegistryHive::LocalMachine, PROFILE_LIST_KEY)? {
+        if !sid.starts_with(USER_SID_PREFIX) {
+            continue;
+        }
+        let values = registry.values(RegistryHive::LocalMachine, &format!(r"{}\{}", PROFILE_LIST_KEY, sid))?;
+        // ProfileImagePath is REG_EXPAND_SZ, usually %SystemDrive%\Users\name
+        if let Some(directory) = values.get("ProfileImagePath").and_then(|value| value.as_text()) {
+            profiles.push(UserProfile { sid: Some(sid.clone()), directory: PathBuf::from(expand_environment_strings(directory)) });
+        }
+    }
+    Ok(profiles)
+}
+
+/// Turns definition paths and registry keys into the concrete targets present on this machine
+#[derive(Debug, Clone, Default)]
+pub struct TargetResolver {
+    profiles: Vec<UserProfile>,
+}
+
+impl TargetResolver {
+    pub fn new(profiles: Vec<UserProfile>) -> Self {
+        Self { profiles }
+    }
+
+    /// Resolve for every user profile of the machine, or the current user when none can be read
+    pub fn for_machine(registry: &dyn RegistryProvider) -> Self {
+        let profiles = match user_profiles(registry) {
+            Ok(profiles) if !profiles.is_empty() => profiles,
+            Ok(_) => UserProfile::current().into_iter().collect(),
+            Err(e) => {
+                warn!("Resolving per-user paths for the current user only: {}", e);
+                UserProfile::current().into_iter().collect()
+            }
+        };
+        Self::new(profiles)
+    }
+
+    /// Existing paths matching `pattern`; a pattern using per-user variables is expanded once per profile
+    pub fn resolve_paths(&self, pattern: &str) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = self.expansions(pattern).iter().flat_map(|expanded| resolve_expanded(expanded)).collect();
+        paths.sort();
+        paths.dedup();
+        paths
+    }
+
+    /// Existing keys matching `pattern`, with wildcard components matched against the subkeys the registry has
+    pub fn resolve_registry_keys(&self, registry: &dyn RegistryProvider, pattern: &str) -> Result<Vec<String>> {
+        let mut keys = Vec::new();
+        for expanded in self.expansions(pattern) {
+            if expanded.contains('%') {
+                continue;
+            }
+            let (hive, path) = registry_provider::parse_key_path(&expanded)
+                .ok_or_else(|| anyhow!("unsupported hive"))?;
+
+            let mut candidates = vec![String::new()];
+            for part in path.split('\\').filter(|part| !part.is_empty()) {
+                if !has_wildcards(part) {
+                    for candidate in &mut candidates {
+                        *candidate = join_key(candidate, part);
+                    }
+                    continue;
+                }
+                let mut matched = Vec::new();
+                for candidate in &candidates {
+                    matched.extend(registry.subkeys(hive, candidate)?
+                        .into_iter()
+                        .filter(|name| glob_match(part, name))
+                        .map(|name| join_key(candidate, &name)));
+                }
+                candidates = matched;
+            }
+
+            for candidate in candidates {
+                if !candidate.is_empty() && registry.key_exists(hive, &candidate)? {
+                    keys.push(registry_provider::full_path(hive, &candidate));
+                }
+            }
+        }
+        keys.sort();
+        keys.dedup();
+        Ok(keys)
+    }
+
+    /// `pattern` with its variables expanded, once per profile when it uses per-user ones
+    fn expansions(&self, pattern: &str) -> Vec<String> {
+        let per_user = referenced_variables(pattern).iter().any(|name| PROFILE_VARIABLES.contains(&name.to_uppercase().as_str()));
+        if !per_user || self.profiles.is_empty() {
+            return vec![expand_environment_strings(pattern)];
+        }
+        self.profiles.iter()
+            .map(|profile| expand_with(pattern, |name| profile.variable(name).or_else(|| std::env::var(name).ok())))
+            .collect()
+    }
+}
+
+fn join_key(parent: &str, name: &str) -> String {
+    if parent.is_empty() { name.to_string() } else { format!("{}\\{}", parent, name) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_patterns_naming_a_folder_below_their_root() {
+        for pattern in [
+            r"%LOCALAPPDATA%\Packages\Microsoft.MicrosoftSolitaireCollection_*",
+            r"C:\Program Files\WindowsApps\king.com.CandyCrushSaga_*",
+            r"%PROGRAMDATA%\McAfee",
+            r"\\fileserver\deploy\Toolbar",
+            "/opt/vendor-agent",
+        ] {
+            assert!(check_anchored(pattern).is_ok(), "{}", pattern);
+        }
+    }
+
+    #[test]
+    fn rejects_patterns_that_match_a_whole_root() {
+        for pattern in [
+            r"%USERPROFILE%\*",
+            "%LOCALAPPDATA%",
+            r"%LOCALAPPDATA%\%TEMP%",
+            r"C:\",
+            r"C:\*\*",
+            r"\\fileserver\deploy",
+            r"%APPDATA%\Vendor\*",
+            r"%APPDATA%\Vendor\..\..",
+            r"Vendor\Cache",
+        ] {
+            assert!(check_anchored(pattern).is_err(), "{}", pattern);
+        }
+    }
+
+    #[test]
+    fn accepts_keys_naming_a_vendor_below_their_root() {
+        for key in [
+            r"HKEY_LOCAL_MACHINE\SOFTWARE\HP",
+            r"HKEY_LOCAL_MACHINE\SOFTWARE\WOW6432Node\McAfee",
+            r"HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\Windows\CurrentVersion\Uninstall\Microsoft.BingNews_*",
+            r"HKCU\Software\Vendor\Toolbar",
+        ] {
+            assert!(check_key_anchored(key).is_ok(), "{}", key);
+        }
+    }
+
+    #[test]
+    fn rejects_keys_that_match_a_whole_root() {
+        for key in [
+            r"HKEY_LOCAL_MACHINE\SOFTWARE",
+            r"HKEY_LOCAL_MACHINE\SYSTEM\",
+            r"HKEY_CURRENT_USER\Software\*",
+            r"HKEY_LOCAL_MACHINE\SOFTWARE\WOW6432Node\*",
+            r"HKEY_LOCAL_MACHINE\SOFTWARE\Vendor\*",
+            r"HKEY_CURRENT_USER\Software\%VENDOR%",
+            r"HKEY_USERS\.DEFAULT\Software\Vendor",
+            "HKEY_LOCAL_MACHINE",
+        ] {
+            assert!(check_key_anchored(key).is_err(), "{}", key);
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use std::path::{Path, PathBuf};
+
+/// Locations the file engine treats specially on the operating system it runs on
+#[derive(Debug, Clone)]
+pub struct PlatformProfile {
+    /// Never scanned or cleaned
+    pub excluded_paths: Vec<PathBuf>,
+    /// Files below these are flagged as system files and their folders are never removed
+    pub system_directories: Vec<PathBuf>,
+    /// Files below these are never deleted, whatever the protection policy says
+    pub critical_directories: Vec<PathBuf>,
+    /// Cleanup rules run by the basic optimization
+    pub basic_cleanup_rules: Vec<&'static str>,
+    /// Where backups, scan sessions and user rules are kept
+    pub backup_directory: PathBuf,
+}
+
+impl PlatformProfile {
+    pub fn is_excluded(&self, path: &Path) -> bool {
+        self.excluded_paths.iter().any(|excluded| path.starts_with(excluded))
+    }
+
+    pub fn is_system_directory(&self, path: &Path) -> bool {
+        self.system_directories.iter().any(|directory| path.starts_with(directory))
+    }
+}
+
+/// Profile of the platform this build targets
+#[cfg(windows)]
+pub fn current() -> PlatformProfile {
+    let system_root = std::env::var_os("SystemRoot").map(PathBuf::from).unwrap_or_else(|| PathBuf::from(r"C:\Windows"));
+    let system_drive = system_root.ancestors().last().map(Path::to_path_buf).unwrap_or_else(|| PathBuf::from(r"C:\"));
+
+    PlatformProfile {
+        excluded_paths: vec![
+            system_root.join("System32"),
+            system_root.join("SysWOW64"),
+            system_drive.join(r"Program Files\WindowsApps"),
+            system_drive.join("$Recycle.Bin"),
+            system_drive.join("System Volume Information"),
+        ],
+        system_directories: vec![
+            system_root.clone(),
+            system_drive.join("System Volume Information"),
+            system_drive.join("$Recycle.Bin"),
+        ],
+        critical_directories: vec![
+            system_root.join("System32"),
+            system_root.join("SysWOW64"),
+            system_root.join("WinSxS"),
+        ],
+        basic_cleanup_rules: vec!["user-temp", "windows-temp", "prefetch"],
+        backup_directory: PathBuf::from(r"C:\WindowsOptimizer\Backups"),
+    }
+}
+
+/// Profile of the platform this build targets
+#[cfg(not(windows))]
+pub fn current() -> PlatformProfile {
+    let data_home = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("share")))
+        .unwrap_or_else(|| PathBuf::from("/tmp"));
+
+    PlatformProfile {
+        excluded_paths: ["/proc", "/sys", "/dev", "/run", "/lost+found"].iter().map(PathBuf::from).collect(),
+        system_directories: ["/bin", "/boot", "/dev", "/etc", "/lib", "/lib32", "/lib64", "/proc", "/run", "/sbin", "/sys", "/usr", "/var"]
+            .iter()
+            .map(PathBuf::from)
+            .collect(),
+        critical_directories: ["/bin", "/boot", "/etc", "/lib", "/lib32", "/lib64", "/sbin", "/usr"].iter().map(PathBuf::from).collect(),
+        basic_cleanup_rules: vec!["tmp", "var-tmp", "thumbnails"],
+        backup_directory: data_home.join("system-optimizer").join("backups"),
+    }
+}
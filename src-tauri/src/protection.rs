@@ -0,0 +1,399 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::keeper;
+use crate::path_resolver;
+
+/// Id of the built-in rule guarding the operating system's own directories
+pub const SYSTEM_LOCATIONS_RULE: &str = "system-locations";
+
+/// File in the backup directory holding the user's policy
+pub const POLICY_FILE: &str = "protection_policy.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProtectionKind {
+    /// Operating system directories; cannot be edited or allow-listed
+    ProtectedLocation,
+    /// Everything below the locations is left alone
+    UserFolder,
+    /// Only files with the listed extensions below the locations are left alone
+    ExtensionRule,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProtectionRule {
+    pub id: String,
+    pub kind: ProtectionKind,
+    pub description: String,
+    /// `%VAR%` references and `*`/`?` wildcards are expanded
+    pub locations: Vec<String>,
+    /// Extensions without the dot, for extension rules
+    #[serde(default)]
+    pub extensions: Vec<String>,
+}
+
+/// User-editable part of the protection policy
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProtectionPolicy {
+    pub rules: Vec<ProtectionRule>,
+    /// Locations exempt from user folder and extension rules
+    #[serde(default)]
+    pub allow_list: Vec<String>,
+}
+
+/// Why a path may not be touched
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProtectionRefusal {
+    pub rule_id: String,
+    pub kind: ProtectionKind,
+    pub location: PathBuf,
+}
+
+impl fmt::Display for ProtectionRefusal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            ProtectionKind::ProtectedLocation => "protected location",
+            ProtectionKind::UserFolder => "user-protected folder",
+            ProtectionKind::ExtensionRule => "extension rule",
+        };
+        write!(f, "blocked by {} \"{}\" ({})", kind, self.rule_id, self.location.display())
+    }
+}
+
+struct CompiledRule {
+    id: String,
+    kind: ProtectionKind,
+    locations: Vec<PathBuf>,
+    extensions: Vec<String>,
+}
+
+/// The policy with every location resolved, persisted next to the backups
+pub struct FileProtection {
+    system: CompiledRule,
+    policy: ProtectionPolicy,
+    rules: Vec<CompiledRule>,
+    allowed: Vec<PathBuf>,
+    policy_path: PathBuf,
+}
+
+impl FileProtection {
+    pub fn load(policy_path: PathBuf, system_locations: Vec<PathBuf>) -> Self {
+        let policy = match std::fs::read(&policy_path) {
+            Ok(content) => serde_json::from_slice::<ProtectionPolicy>(&content).unwrap_or_else(|e| {
+                warn!("Ignoring unreadable protection policy {}: {}", policy_path.display(), e);
+                default_policy()
+            }),
+            Err(_) => default_policy(),
+        };
+
+        let system = CompiledRule {
+            id: SYSTEM_LOCATIONS_RULE.to_string(),
+            kind: ProtectionKind::ProtectedLocation,
+            locations: system_locations,
+            extensions: Vec::new(),
+        };
+
+        let mut protection = Self {
+            system,
+            policy: ProtectionPolicy { rules: Vec::new(), allow_list: Vec::new() },
+            rules: Vec::new(),
+            allowed: Vec::new(),
+            policy_path,
+        };
+        protection.apply(policy);
+        protection
+    }
+
+    pub fn policy(&self) -> ProtectionPolicy {
+        self.policy.clone()
+    }
+
+    /// Validate, persist and start enforcing a new policy
+    pub fn update(&mut self, mut policy: ProtectionPolicy) -> Result<()> {
+        validate_policy(&mut policy)?;
+
+        if let Some(parent) = self.policy_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&self.policy_path, serde_json::to_vec_pretty(&policy)?)?;
+
+        self.apply(policy);
+        Ok(())
+    }
+
+    /// The first rule that forbids touching `path`, if any
+    pub fn check(&self, path: &Path) -> std::result::Result<(), ProtectionRefusal> {
+        if let Some(refusal) = self.system.refuses(path) {
+            return Err(refusal);
+        }
+        if self.allowed.iter().any(|allowed| keeper::is_within(path, allowed)) {
+            return Ok(());
+        }
+
+        match self.rules.iter().find_map(|rule| rule.refuses(path)) {
+            Some(refusal) => Err(refusal),
+            None => Ok(()),
+        }
+    }
+
+    /// Like `check`, and also refuses a directory holding a protected location, which removing it would take along
+    pub fn check_tree(&self, path: &Path) -> std::result::Result<(), ProtectionRefusal> {
+        self.check(path)?;
+        if let Some(refusal) = self.system.holds(path) {
+            return Err(refusal);
+        }
+
+        let allowed = |refusal: &ProtectionRefusal| self.allowed.iter().any(|allowed| keeper::is_within(&refusal.location, allowed));
+        match self.rules.iter().filter_map(|rule| rule.holds(path)).find(|refusal| !allowed(refusal)) {
+            Some(refusal) => Err(refusal),
+            None => Ok(()),
+        }
+    }
+
+    fn apply(&mut self, policy: ProtectionPolicy) {
+        self.rules = policy.rules.iter().map(|rule| CompiledRule {
+            id: rule.id.clone(),
+            kind: rule.kind,
+            locations: resolve_all(&rule.locations),
+            extensions: rule.extensions.iter().map(|ext| normalize_extension(ext)).collect(),
+        }).collect();
+        self.allowed = resolve_all(&policy.allow_list);
+        self.policy = policy;
+    }
+}
+
+impl CompiledRule {
+    fn refuses(&self, path: &Path) -> Option<ProtectionRefusal> {
+        let location = self.locations.iter().find(|location| keeper::is_within(path, location))?;
+
+        if self.kind == ProtectionKind::ExtensionRule {
+            let extension = path.extension().map(|ext| ext.to_string_lossy().to_lowercase())?;
+            if !self.extensions.contains(&extension) {
+                return None;
+            }
+        }
+
+        Some(ProtectionRefusal {
+            rule_id: self.id.clone(),
+            kind: self.kind,
+            location: location.clone(),
+        })
+    }
+
+    /// A location of the rule inside `directory`, whatever the extensions
+    fn holds(&self, directory: &Path) -> Option<ProtectionRefusal> {
+        let location = self.locations.iter().find(|location| keeper::is_within(location, directory))?;
+        Some(ProtectionRefusal {
+            rule_id: self.id.clone(),
+            kind: self.kind,
+            location: location.clone(),
+        })
+    }
+}
+
+fn resolve_all(patterns: &[String]) -> Vec<PathBuf> {
+    patterns.iter().flat_map(|pattern| path_resolver::resolve_path_pattern(pattern)).collect()
+}
+
+fn normalize_extension(extension: &str) -> String {
+    extension.trim().trim_start_matches('.').to_lowercase()
+}
+
+fn validate_policy(policy: &mut ProtectionPolicy) -> Result<()> {
+    let mut ids = HashSet::new();
+
+    for rule in &mut policy.rules {
+        if rule.id.trim().is_empty() {
+            return Err(anyhow!("Protection rules need an id"));
+        }
+        if rule.id == SYSTEM_LOCATIONS_RULE || rule.kind == ProtectionKind::ProtectedLocation {
+            return Err(anyhow!("Rule {} cannot redefine the built-in protected locations", rule.id));
+        }
+        if !ids.insert(rule.id.clone()) {
+            return Err(anyhow!("Duplicate protection rule id {}", rule.id));
+        }
+        if rule.locations.is_empty() {
+            return Err(anyhow!("Protection rule {} has no locations", rule.id));
+        }
+
+        rule.extensions = rule.extensions.iter().map(|ext| normalize_extension(ext)).filter(|ext| !ext.is_empty()).collect();
+        if rule.kind == ProtectionKind::ExtensionRule && rule.extensions.is_empty() {
+            return Err(anyhow!("Extension rule {} lists no extensions", rule.id));
+        }
+    }
+
+    Ok(())
+}
+
+fn rule(id: &str, kind: ProtectionKind, description: &str, locations: &[&str], extensions: &[&str]) -> ProtectionRule {
+    ProtectionRule {
+        id: id.to_string(),
+        kind,
+        description: description.to_string(),
+        locations: locations.iter().map(|location| location.to_string()).collect(),
+        extensions: extensions.iter().map(|ext| ext.to_string()).collect(),
+    }
+}
+
+/// Policy used until the user saves their own
+#[cfg(windows)]
+fn default_policy() -> ProtectionPolicy {
+    ProtectionPolicy {
+        rules: vec![
+            rule("documents", ProtectionKind::UserFolder,
+                 "The user's Documents folder",
+                 &[r"%USERPROFILE%\Documents"], &[]),
+            rule("onedrive", ProtectionKind::UserFolder,
+                 "OneDrive folders, where deletions sync to every device",
+                 &["%OneDrive%", "%OneDriveConsumer%", "%OneDriveCommercial%"], &[]),
+            rule("installed-binaries", ProtectionKind::ExtensionRule,
+                 "Executables and drivers of installed software; portable copies elsewhere stay cleanable",
+                 &["%SystemRoot%", "%ProgramFiles%", "%ProgramFiles(x86)%", "%ProgramData%", r"%LOCALAPPDATA%\Programs"],
+                 &["sys", "dll", "exe", "drv", "ocx"]),
+        ],
+        allow_list: Vec::new(),
+    }
+}
+
+/// Policy used until the user saves their own
+#[cfg(not(windows))]
+fn default_policy() -> ProtectionPolicy {
+    ProtectionPolicy {
+        rules: vec![
+            rule("documents", ProtectionKind::UserFolder,
+                 "The user's Documents folder",
+                 &["%HOME%/Documents"], &[]),
+            rule("installed-libraries", ProtectionKind::ExtensionRule,
+                 "Shared libraries and kernel modules of locally installed software",
+                 &["/opt", "/usr/local", "%HOME%/.local/lib"],
+                 &["so", "ko"]),
+        ],
+        allow_list: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Protection with `system` as the only system location and an empty policy file in a fresh directory
+    fn protection(root: &Path, system: &Path) -> FileProtection {
+        std::fs::create_dir_all(root).unwrap();
+        let mut protection = FileProtection::load(root.join(POLICY_FILE), vec![system.to_path_buf()]);
+        protection.update(ProtectionPolicy { rules: Vec::new(), allow_list: Vec::new() }).unwrap();
+        protection
+    }
+
+    fn location(path: &Path) -> String {
+        path.to_string_lossy().into_owned()
+    }
+
+    fn refused_by(protection: &FileProtection, path: &Path) -> Option<String> {
+        protection.check(path).err().map(|refusal| refusal.rule_id)
+    }
+
+    #[test]
+    fn system_locations_cannot_be_redefined_or_allow_listed() {
+        let root = std::env::temp_dir().join(format!("protection-test-{}", uuid::Uuid::new_v4()));
+        let system = root.join("Windows");
+        let mut protection = protection(&root, &system);
+
+        for rule in [
+            rule(SYSTEM_LOCATIONS_RULE, ProtectionKind::UserFolder, "", &[&location(&root)], &[]),
+            rule("mine", ProtectionKind::ProtectedLocation, "", &[&location(&root)], &[]),
+        ] {
+            let id = rule.id.clone();
+            assert!(protection.update(ProtectionPolicy { rules: vec![rule], allow_list: Vec::new() }).is_err(), "{}", id);
+        }
+
+        protection.update(ProtectionPolicy { rules: Vec::new(), allow_list: vec![location(&system), location(&root)] }).unwrap();
+        let refusal = protection.check(&system.join("System32").join("kernel32.dll")).unwrap_err();
+        assert_eq!((refusal.rule_id.as_str(), refusal.kind), (SYSTEM_LOCATIONS_RULE, ProtectionKind::ProtectedLocation));
+        assert!(protection.check_tree(&system).is_err());
+        assert!(protection.check(&root.join("Temp")).is_ok());
+
+        // A saved policy cannot smuggle the rule in either
+        std::fs::write(root.join(POLICY_FILE), serde_json::to_vec(&ProtectionPolicy {
+            rules: Vec::new(),
+            allow_list: vec![location(&system)],
+        }).unwrap()).unwrap();
+        let reloaded = FileProtection::load(root.join(POLICY_FILE), vec![system.clone()]);
+        assert_eq!(refused_by(&reloaded, &system.join("notepad.exe")).as_deref(), Some(SYSTEM_LOCATIONS_RULE));
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn extension_rules_cover_only_their_extensions() {
+        let root = std::env::temp_dir().join(format!("protection-test-{}", uuid::Uuid::new_v4()));
+        let programs = root.join("Programs");
+        std::fs::create_dir_all(&programs).unwrap();
+        let mut protection = protection(&root, &root.join("Windows"));
+        protection.update(ProtectionPolicy {
+            rules: vec![rule("binaries", ProtectionKind::ExtensionRule, "", &[&location(&programs)], &[".DLL", " exe "])],
+            allow_list: Vec::new(),
+        }).unwrap();
+
+        assert_eq!(protection.policy().rules[0].extensions, ["dll", "exe"]);
+        assert_eq!(refused_by(&protection, &programs.join("App").join("core.dll")).as_deref(), Some("binaries"));
+        assert_eq!(refused_by(&protection, &programs.join("App").join("APP.EXE")).as_deref(), Some("binaries"));
+        assert_eq!(refused_by(&protection, &programs.join("App").join("cache.tmp")), None);
+        assert_eq!(refused_by(&protection, &programs.join("App").join("README")), None);
+        assert_eq!(refused_by(&protection, &root.join("Downloads").join("setup.exe")), None);
+
+        let empty = rule("empty", ProtectionKind::ExtensionRule, "", &[&location(&programs)], &[" . "]);
+        assert!(protection.update(ProtectionPolicy { rules: vec![empty], allow_list: Vec::new() }).is_err());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn allow_list_exempts_paths_from_user_rules() {
+        let root = std::env::temp_dir().join(format!("protection-test-{}", uuid::Uuid::new_v4()));
+        let documents = root.join("Documents");
+        let scratch = documents.join("Scratch");
+        std::fs::create_dir_all(&scratch).unwrap();
+        let mut protection = protection(&root, &root.join("Windows"));
+        protection.update(ProtectionPolicy {
+            rules: vec![
+                rule("documents", ProtectionKind::UserFolder, "", &[&location(&documents)], &[]),
+                rule("binaries", ProtectionKind::ExtensionRule, "", &[&location(&root)], &["exe"]),
+            ],
+            allow_list: vec![location(&scratch)],
+        }).unwrap();
+
+        assert_eq!(refused_by(&protection, &documents.join("report.docx")).as_deref(), Some("documents"));
+        assert_eq!(refused_by(&protection, &scratch.join("report.docx")), None);
+        assert_eq!(refused_by(&protection, &scratch.join("tool.exe")), None);
+        assert_eq!(refused_by(&protection, &root.join("tool.exe")).as_deref(), Some("binaries"));
+        // Documents still holds protected files outside the allow-listed folder
+        assert!(protection.check_tree(&scratch).is_ok());
+        assert!(protection.check_tree(&documents).is_err());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn check_tree_refuses_parents_of_protected_folders() {
+        let root = std::env::temp_dir().join(format!("protection-test-{}", uuid::Uuid::new_v4()));
+        let system = root.join("Windows");
+        let documents = root.join("Users").join("alex").join("Documents");
+        std::fs::create_dir_all(&documents).unwrap();
+        let mut protection = protection(&root, &system);
+        protection.update(ProtectionPolicy {
+            rules: vec![rule("documents", ProtectionKind::UserFolder, "", &[&location(&documents)], &[])],
+            allow_list: Vec::new(),
+        }).unwrap();
+
+        let profile = root.join("Users").join("alex");
+        assert!(protection.check(&profile).is_ok());
+        let refusal = protection.check_tree(&profile).unwrap_err();
+        assert_eq!((refusal.rule_id.as_str(), refusal.location.clone()), ("documents", documents.clone()));
+        assert_eq!(protection.check_tree(&root).unwrap_err().rule_id, SYSTEM_LOCATIONS_RULE);
+        assert!(protection.check_tree(&root.join("Users").join("sam")).is_ok());
+        assert!(protection.check_tree(&profile.join("Downloads")).is_ok());
+        assert!(protection.check_tree(&documents.join("Old")).is_err());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
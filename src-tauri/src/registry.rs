@@ -10,6 +10,8 @@ use winreg::enums::*;
 use winreg::RegKey;
 use tracing::{info, warn, error};
 
+use crate::command_runner::{self, CommandLine, CommandRunner};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RegistryBackup {
     pub id: String,
@@ -55,6 +57,7 @@ pub struct RegistryManager {
     backups: Arc<RwLock<HashMap<String, RegistryBackup>>>,
     operations_log: Arc<RwLock<Vec<RegistryOperation>>>,
     backup_directory: PathBuf,
+    runner: Arc<dyn CommandRunner>,
 }
 
 impl RegistryManager {
@@ -63,6 +66,7 @@ impl RegistryManager {
             backups: Arc::new(RwLock::new(HashMap::new())),
             operations_log: Arc::new(RwLock::new(Vec::new())),
             backup_directory: backup_dir,
+            runner: command_runner::shared(),
         }
     }
 
@@ -83,20 +87,17 @@ impl RegistryManager {
         info!("Creating registry backup: {}", backup_path.display());
         
         // Export full registry
-        let export_command = format!(
-            "reg export HKLM \"{}\" /y",
-            backup_path.display()
+        let export_command = CommandLine::new(
+            "reg.exe",
+            ["export".to_string(), "HKLM".to_string(), backup_path.display().to_string(), "/y".to_string()],
         );
         
         // Execute registry export
-        let output = tokio::process::Command::new("cmd")
-            .args(&["/C", &export_command])
-            .output()
-            .await?;
+        let output = self.runner.run(&export_command).await?;
             
-        if !output.status.success() {
+        if !output.success() {
             return Err(anyhow!("Registry export failed: {}", 
-                String::from_utf8_lossy(&output.stderr)));
+                output.stderr.trim()));
         }
         
         // Get file size and calculate checksum
@@ -111,7 +112,7 @@ impl RegistryManager {
         let registry_keys = self.scan_registry_keys_for_backup().await?;
         
         let backup = RegistryBackup {
-            id: backup_id,
+            id: backup_id.clone(),
             timestamp,
             description,
             backup_path,
@@ -200,19 +201,13 @@ impl RegistryManager {
         self.create_system_restore_point("Before registry restoration").await?;
         
         // Import registry backup
-        let import_command = format!(
-            "reg import \"{}\"",
-            backup.backup_path.display()
-        );
+        let import_command = CommandLine::new("reg.exe", ["import".to_string(), backup.backup_path.display().to_string()]);
         
-        let output = tokio::process::Command::new("cmd")
-            .args(&["/C", &import_command])
-            .output()
-            .await?;
+        let output = self.runner.run(&import_command).await?;
             
-        if !output.status.success() {
+        if !output.success() {
             return Err(anyhow!("Registry import failed: {}", 
-                String::from_utf8_lossy(&output.stderr)));
+                output.stderr.trim()));
         }
         
         info!("Registry backup restored successfully: {}", backup_id);
@@ -242,14 +237,11 @@ impl RegistryManager {
         let key_backup = self.backup_specific_key(key_path).await?;
         
         // Delete the key
-        let delete_command = format!("reg delete \"{}\" /f", key_path);
+        let delete_command = CommandLine::new("reg.exe", ["delete", key_path, "/f"]);
         
-        let output = tokio::process::Command::new("cmd")
-            .args(&["/C", &delete_command])
-            .output()
-            .await?;
+        let output = self.runner.run(&delete_command).await?;
             
-        let success = output.status.success();
+        let success = output.success();
         
         // Update operation log
         {
@@ -259,7 +251,7 @@ impl RegistryManager {
                 error_message: if success { 
                     None 
                 } else { 
-                    Some(String::from_utf8_lossy(&output.stderr).to_string()) 
+                    Some(output.stderr.clone()) 
                 },
                 ..operation
             });
@@ -267,7 +259,7 @@ impl RegistryManager {
         
         if !success {
             return Err(anyhow!("Failed to delete registry key: {}", 
-                String::from_utf8_lossy(&output.stderr)));
+                output.stderr.trim()));
         }
         
         info!("Registry key deleted successfully: {}", key_path);
@@ -303,19 +295,16 @@ impl RegistryManager {
 
     /// Create system restore point
     async fn create_system_restore_point(&self, description: &str) -> Result<()> {
-        let command = format!(
-            "powershell.exe -Command \"Checkpoint-Computer -Description '{}' -RestorePointType 'MODIFY_SETTINGS'\"",
-            description
+        let command = CommandLine::powershell(
+            "Checkpoint-Computer -Description $Description -RestorePointType 'MODIFY_SETTINGS'",
+            &[("Description", description.into())],
         );
         
-        let output = tokio::process::Command::new("cmd")
-            .args(&["/C", &command])
-            .output()
-            .await?;
+        let output = self.runner.run(&command).await?;
             
-        if !output.status.success() {
+        if !output.success() {
             warn!("Failed to create system restore point: {}", 
-                  String::from_utf8_lossy(&output.stderr));
+                  output.stderr.trim());
         }
         
         Ok(())
@@ -345,16 +334,16 @@ impl RegistryManager {
         let filename = format!("key_backup_{}.reg", backup_id[..8].to_string());
         let backup_path = self.backup_directory.join(&filename);
         
-        let export_command = format!("reg export \"{}\" \"{}\" /y", key_path, backup_path.display());
+        let export_command = CommandLine::new(
+            "reg.exe",
+            ["export".to_string(), key_path.to_string(), backup_path.display().to_string(), "/y".to_string()],
+        );
         
-        let output = tokio::process::Command::new("cmd")
-            .args(&["/C", &export_command])
-            .output()
-            .await?;
+        let output = self.runner.run(&export_command).await?;
             
-        if !output.status.success() {
+        if !output.success() {
             return Err(anyhow!("Failed to backup registry key: {}", 
-                String::from_utf8_lossy(&output.stderr)));
+                output.stderr.trim()));
         }
         
         Ok(backup_path)
@@ -0,0 +1,171 @@
+use std::collections::BTreeMap;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RegistryHive {
+    LocalMachine,
+    CurrentUser,
+}
+
+impl RegistryHive {
+    /// Long form used in .reg files and `reg.exe`
+    pub fn root_name(&self) -> &'static str {
+        match self {
+            RegistryHive::LocalMachine => "HKEY_LOCAL_MACHINE",
+            RegistryHive::CurrentUser => "HKEY_CURRENT_USER",
+        }
+    }
+}
+
+/// The value types inventory code cares about; binary and multi-string values are skipped
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RegistryValue {
+    Number(u64),
+    Text(String),
+}
+
+impl RegistryValue {
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            RegistryValue::Text(text) => Some(text),
+            RegistryValue::Number(_) => None,
+        }
+    }
+
+    pub fn as_number(&self) -> Option<u64> {
+        match self {
+            RegistryValue::Number(number) => Some(*number),
+            RegistryValue::Text(text) => text.trim().parse().ok(),
+        }
+    }
+}
+
+/// Read-only access to the registry, so inventory code can run against recorded fixtures
+pub trait RegistryProvider: Send + Sync {
+    /// Names of the direct subkeys; a missing key reads as empty
+    fn subkeys(&self, hive: RegistryHive, path: &str) -> Result<Vec<String>>;
+
+    /// Values of a key by name; a missing key reads as empty
+    fn values(&self, hive: RegistryHive, path: &str) -> Result<BTreeMap<String, RegistryValue>>;
+
+    fn key_exists(&self, hive: RegistryHive, path: &str) -> Result<bool>;
+}
+
+/// The registry of the machine we run on
+#[cfg(windows)]
+pub struct WindowsRegistry;
+
+#[cfg(windows)]
+impl WindowsRegistry {
+    fn open(hive: RegistryHive, path: &str) -> Result<Option<winreg::RegKey>> {
+        use winreg::enums::{HKEY_CURRENT_USER, HKEY_LOCAL_MACHINE, KEY_READ};
+
+        let root = winreg::RegKey::predef(match hive {
+            RegistryHive::LocalMachine => HKEY_LOCAL_MACHINE,
+            RegistryHive::CurrentUser => HKEY_CURRENT_USER,
+        });
+        match root.open_subkey_with_flags(path, KEY_READ) {
+            Ok(key) => Ok(Some(key)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(anyhow::anyhow!("Cannot open {}\\{}: {}", hive.root_name(), path, e)),
+        }
+    }
+}
+
+#[cfg(windows)]
+impl RegistryProvider for WindowsRegistry {
+    fn subkeys(&self, hive: RegistryHive, path: &str) -> Result<Vec<String>> {
+        let Some(key) = Self::open(hive, path)? else { return Ok(Vec::new()) };
+        Ok(key.enum_keys().filter_map(|name| name.ok()).collect())
+    }
+
+    fn values(&self, hive: RegistryHive, path: &str) -> Result<BTreeMap<String, RegistryValue>> {
+        use winreg::enums::RegType;
+        use winreg::types::FromRegValue;
+
+        let Some(key) = Self::open(hive, path)? else { return Ok(BTreeMap::new()) };
+        let mut values = BTreeMap::new();
+        for (name, value) in key.enum_values().filter_map(|value| value.ok()) {
+            let value = match value.vtype {
+                RegType::REG_SZ | RegType::REG_EXPAND_SZ => String::from_reg_value(&value).ok().map(RegistryValue::Text),
+                RegType::REG_DWORD => u32::from_reg_value(&value).ok().map(|number| RegistryValue::Number(number as u64)),
+                RegType::REG_QWORD => u64::from_reg_value(&value).ok().map(RegistryValue::Number),
+                _ => None,
+            };
+            if let Some(value) = value {
+                values.insert(name, value);
+            }
+        }
+        Ok(values)
+    }
+
+    fn key_exists(&self, hive: RegistryHive, path: &str) -> Result<bool> {
+        Ok(Self::open(hive, path)?.is_some())
+    }
+}
+
+/// A registry snapshot loaded from JSON, keyed by `HKEY_...\path`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RecordedRegistry {
+    pub keys: BTreeMap<String, BTreeMap<String, RegistryValue>>,
+}
+
+impl RecordedRegistry {
+    /// Capture the keys below `paths` from another provider, e.g. to record a fixture
+    #[cfg(debug_assertions)]
+    pub fn record(provider: &dyn RegistryProvider, hive: RegistryHive, paths: &[&str]) -> Result<Self> {
+        let mut recorded = Self::default();
+        let mut pending: Vec<String> = paths.iter().map(|path| path.to_string()).collect();
+        while let Some(path) = pending.pop() {
+            recorded.keys.insert(full_path(hive, &path), provider.values(hive, &path)?);
+            pending.extend(provider.subkeys(hive, &path)?.into_iter().map(|name| format!("{}\\{}", path, name)));
+        }
+        Ok(recorded)
+    }
+}
+
+impl RegistryProvider for RecordedRegistry {
+    fn subkeys(&self, hive: RegistryHive, path: &str) -> Result<Vec<String>> {
+        let prefix = format!("{}\\", full_path(hive, path).to_lowercase());
+        let mut names: Vec<String> = self.keys
+            .keys()
+            .filter(|key| key.to_lowercase().starts_with(&prefix))
+            .filter_map(|key| key.get(prefix.len()..)?.split('\\').next().map(str::to_string))
+            .collect();
+        names.sort();
+        names.dedup();
+        Ok(names)
+    }
+
+    fn values(&self, hive: RegistryHive, path: &str) -> Result<BTreeMap<String, RegistryValue>> {
+        let wanted = full_path(hive, path).to_lowercase();
+        Ok(self.keys
+            .iter()
+            .find(|(key, _)| key.to_lowercase() == wanted)
+            .map(|(_, values)| values.clone())
+            .unwrap_or_default())
+    }
+
+    fn key_exists(&self, hive: RegistryHive, path: &str) -> Result<bool> {
+        let wanted = full_path(hive, path).to_lowercase();
+        let below = format!("{}\\", wanted);
+        Ok(self.keys.keys().map(|key| key.to_lowercase()).any(|key| key == wanted || key.starts_with(&below)))
+    }
+}
+
+pub fn full_path(hive: RegistryHive, path: &str) -> String {
+    format!("{}\\{}", hive.root_name(), path)
+}
+
+/// Split `HKEY_LOCAL_MACHINE\...` or `HKLM\...` into hive and path
+pub fn parse_key_path(key: &str) -> Option<(RegistryHive, &str)> {
+    let (root, path) = key.split_once('\\')?;
+    let hive = match root.to_uppercase().as_str() {
+        "HKEY_LOCAL_MACHINE" | "HKLM" => RegistryHive::LocalMachine,
+        "HKEY_CURRENT_USER" | "HKCU" => RegistryHive::CurrentUser,
+        _ => return None,
+    };
+    Some((hive, path.trim_end_matches('\\')))
+}
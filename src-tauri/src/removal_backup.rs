@@ -0,0 +1,498 @@
+use std::path::{Path, PathBuf};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::bloatware::UninstallResult;
+use crate::command_runner::{CommandLine, CommandRunner};
+use crate::removal_plan::AppRemovalPlan;
+use crate::winget::{self, WingetOutcome};
+
+/// File in each backup directory describing what the directory holds
+const MANIFEST_FILE: &str = "backup.json";
+
+const SERVICE_QUERY: &str = r#"
+$service = Get-CimInstance Win32_Service | Where-Object { $_.Name -eq $Name }
+if (-not $service) { throw "Service $Name is not installed" }
+$service | Select-Object Name, DisplayName, PathName, StartMode, StartName | ConvertTo-Json -Compress
+"#;
+
+/// Register a service again unless a reinstall already did; its key import restores the rest
+const SERVICE_RESTORE: &str = r#"
+if (Get-Service -Name $Name -ErrorAction SilentlyContinue) { return }
+New-Service -Name $Name -BinaryPathName $BinaryPath -DisplayName $DisplayName -StartupType $StartupType -ErrorAction Stop | Out-Null
+"#;
+
+/// Registers a package again while a copy is still staged on the machine
+const APPX_RESTORE: &str = "Add-AppxPackage -RegisterByFamilyName -MainPackage $Family -ErrorAction Stop";
+
+/// Everything captured before an app was removed, to put it back
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemovalBackup {
+    pub id: String,
+    pub app_name: String,
+    pub display_name: String,
+    pub created: DateTime<Utc>,
+    pub directory: PathBuf,
+    pub registry_exports: Vec<RegistryExport>,
+    pub services: Vec<ServiceBackup>,
+    pub scheduled_tasks: Vec<TaskBackup>,
+    /// Tried in order until one installs the app again
+    pub reinstall: Vec<ReinstallSource>,
+    /// What could not be captured
+    pub errors: Vec<String>,
+    /// Outcome of the removal this backup was taken for
+    #[serde(default)]
+    pub result: Option<UninstallResult>,
+    #[serde(default)]
+    pub restored: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegistryExport {
+    pub key: String,
+    pub file: PathBuf,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServiceBackup {
+    pub name: String,
+    pub display_name: String,
+    pub binary_path: String,
+    /// Win32_Service start mode: Auto, Manual, Disabled, Boot or System
+    pub start_mode: String,
+    pub account: Option<String>,
+    /// Export of the service key, with its description, dependencies and recovery actions
+    pub registry_export: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskBackup {
+    pub path: String,
+    pub file: PathBuf,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "source_type")]
+pub enum ReinstallSource {
+    /// A package winget can install from a configured source
+    Winget { id: String, source: String },
+    /// An Appx package family, registered again from a copy still staged on the machine
+    Appx { family: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RestoreResult {
+    pub backup_id: String,
+    pub app_name: String,
+    pub success: bool,
+    pub details: Vec<String>,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ServiceRecord {
+    name: String,
+    display_name: Option<String>,
+    path_name: Option<String>,
+    start_mode: Option<String>,
+    start_name: Option<String>,
+}
+
+impl RemovalBackup {
+    /// Export the registry keys, services and scheduled tasks the plan deletes, and record how to reinstall.
+    /// Fails only when the backup itself cannot be stored; single items that cannot be captured are listed in `errors`.
+    pub async fn capture(runner: &dyn CommandRunner, root: &Path, app: &AppRemovalPlan) -> Result<Self> {
+        let created = Utc::now();
+        let id = format!("{}_{}", sanitize(&app.app_name), created.format("%Y%m%d_%H%M%S_%3f"));
+        let directory = root.join(&id);
+        tokio::fs::create_dir_all(&directory).await
+            .map_err(|e| anyhow!("Cannot create backup directory {}: {}", directory.display(), e))?;
+
+        let mut backup = Self {
+            id,
+            app_name: app.app_name.clone(),
+            display_name: app.display_name.clone(),
+            created,
+            directory,
+            registry_exports: Vec::new(),
+            services: Vec::new(),
+            scheduled_tasks: Vec::new(),
+            reinstall: app.reinstall_sources.clone(),
+            errors: Vec::new(),
+            result: None,
+            restored: None,
+        };
+
+        // One file per key; reg export overwrites its target
+        for (index, key) in app.registry_keys.iter().enumerate() {
+            let file = backup.directory.join(format!("registry_{}.reg", index + 1));
+            match export_key(runner, key, &file).await {
+                Ok(()) => backup.registry_exports.push(RegistryExport { key: key.clone(), file }),
+                Err(e) => backup.errors.push(format!("Registry key {}: {}", key, e)),
+            }
+        }
+
+        for (index, name) in app.services.iter().enumerate() {
+            match capture_service(runner, name, &backup.directory.join(format!("service_{}.reg", index + 1))).await {
+                Ok((service, export_error)) => {
+                    backup.errors.extend(export_error);
+                    backup.services.push(service);
+                }
+                Err(e) => backup.errors.push(format!("Service {}: {}", name, e)),
+            }
+        }
+
+        for (index, task) in app.scheduled_tasks.iter().enumerate() {
+            let path = task_path(task);
+            let file = backup.directory.join(format!("task_{}.xml", index + 1));
+            let xml = runner.checked(&CommandLine::new("schtasks.exe", ["/Query", "/TN", path.as_str(), "/XML"])).await;
+            match xml {
+                Ok(xml) => match tokio::fs::write(&file, xml).await {
+                    Ok(()) => backup.scheduled_tasks.push(TaskBackup { path, file }),
+                    Err(e) => backup.errors.push(format!("Scheduled task {}: {}", path, e)),
+                },
+                Err(e) => backup.errors.push(format!("Scheduled task {}: {}", path, e)),
+            }
+        }
+
+        if backup.reinstall.is_empty() {
+            backup.errors.push("No package source is known to reinstall the app from".to_string());
+        }
+
+        backup.save().await?;
+        Ok(backup)
+    }
+
+    pub async fn save(&self) -> Result<()> {
+        let manifest = self.directory.join(MANIFEST_FILE);
+        tokio::fs::write(&manifest, serde_json::to_vec_pretty(self)?).await
+            .map_err(|e| anyhow!("Cannot write {}: {}", manifest.display(), e))
+    }
+
+    pub async fn load(root: &Path, id: &str) -> Result<Self> {
+        if id.is_empty() || id.contains(['/', '\\']) || id.contains("..") {
+            return Err(anyhow!("Invalid backup ID: {}", id));
+        }
+        let manifest = root.join(id).join(MANIFEST_FILE);
+        let content = tokio::fs::read(&manifest).await
+            .map_err(|e| anyhow!("No removal backup {}: {}", id, e))?;
+        serde_json::from_slice(&content).map_err(|e| anyhow!("Invalid removal backup {}: {}", id, e))
+    }
+
+    /// Every readable backup under `root`, oldest first
+    pub fn load_all(root: &Path) -> Vec<Self> {
+        let Ok(entries) = std::fs::read_dir(root) else { return Vec::new() };
+        let mut backups: Vec<Self> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path().join(MANIFEST_FILE))
+            .filter(|manifest| manifest.is_file())
+            .filter_map(|manifest| {
+                let content = std::fs::read(&manifest).ok()?;
+                serde_json::from_slice(&content)
+                    .map_err(|e| warn!("Ignoring unreadable removal backup {}: {}", manifest.display(), e))
+                    .ok()
+            })
+            .collect();
+        backups.sort_by_key(|backup| backup.created);
+        backups
+    }
+
+    /// Reinstall the app, then put back its registry keys, services and scheduled tasks
+    pub async fn restore(&self, runner: &dyn CommandRunner) -> RestoreResult {
+        let mut result = RestoreResult {
+            backup_id: self.id.clone(),
+            app_name: self.app_name.clone(),
+            success: false,
+            details: Vec::new(),
+            errors: Vec::new(),
+        };
+
+        // Reinstall first; the imports below then bring back the settings the app had
+        let mut reinstalled = false;
+        let mut failures = Vec::new();
+        for source in &self.reinstall {
+            match reinstall(runner, source).await {
+                Ok(detail) => {
+                    result.details.push(detail);
+                    reinstalled = true;
+                    break;
+                }
+                Err(e) => failures.push(format!("Reinstall from {}: {}", source, e)),
+            }
+        }
+        if reinstalled {
+            result.details.extend(failures);
+        } else if self.reinstall.is_empty() {
+            result.errors.push(format!("No package source is known for {}; reinstall it manually", self.display_name));
+        } else {
+            result.errors.extend(failures);
+        }
+
+        for export in &self.registry_exports {
+            match import_key(runner, &export.file).await {
+                Ok(()) => result.details.push(format!("Restored registry key {}", export.key)),
+                Err(e) => result.errors.push(format!("Failed to restore registry key {}: {}", export.key, e)),
+            }
+        }
+
+        for service in &self.services {
+            let command = CommandLine::powershell(SERVICE_RESTORE, &[
+                ("Name", service.name.as_str().into()),
+                ("BinaryPath", service.binary_path.as_str().into()),
+                ("DisplayName", service.display_name.as_str().into()),
+                ("StartupType", startup_type(&service.start_mode).into()),
+            ]);
+            let restored = match runner.checked(&command).await {
+                Ok(_) => match &service.registry_export {
+                    Some(file) => import_key(runner, file).await,
+                    None => Ok(()),
+                },
+                Err(e) => Err(e),
+            };
+            match restored {
+                Ok(()) => result.details.push(format!("Restored service {}", service.name)),
+                Err(e) => result.errors.push(format!("Failed to restore service {}: {}", service.name, e)),
+            }
+        }
+
+        for task in &self.scheduled_tasks {
+            let file = task.file.display().to_string();
+            match runner.checked(&CommandLine::new("schtasks.exe", ["/Create", "/TN", task.path.as_str(), "/XML", file.as_str(), "/F"])).await {
+                Ok(_) => result.details.push(format!("Restored scheduled task {}", task.path)),
+                Err(e) => result.errors.push(format!("Failed to restore scheduled task {}: {}", task.path, e)),
+            }
+        }
+
+        result.success = reinstalled && result.errors.is_empty();
+        result
+    }
+}
+
+impl std::fmt::Display for ReinstallSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReinstallSource::Winget { id, source } => write!(f, "winget {} ({})", id, source),
+            ReinstallSource::Appx { family } => write!(f, "Appx {}", family),
+        }
+    }
+}
+
+async fn reinstall(runner: &dyn CommandRunner, source: &ReinstallSource) -> Result<String> {
+    match source {
+        ReinstallSource::Winget { id, source } => match winget::install(runner, id, Some(source)).await? {
+            WingetOutcome::Completed => Ok(format!("Reinstalled {} with winget", id)),
+            WingetOutcome::RebootRequired => Ok(format!("Reinstalled {} with winget; restart to finish", id)),
+        },
+        ReinstallSource::Appx { family } => {
+            runner.checked(&CommandLine::powershell(APPX_RESTORE, &[("Family", family.as_str().into())])).await?;
+            Ok(format!("Registered {} again", family))
+        }
+    }
+}
+
+async fn capture_service(runner: &dyn CommandRunner, name: &str, export_file: &Path) -> Result<(ServiceBackup, Option<String>)> {
+    let output = runner.checked(&CommandLine::powershell(SERVICE_QUERY, &[("Name", name.into())])).await?;
+    let record: ServiceRecord = serde_json::from_str(output.trim())
+        .map_err(|e| anyhow!("Unexpected service description: {}", e))?;
+    let path_name = record.path_name.ok_or_else(|| anyhow!("The service has no binary path"))?;
+
+    let key = format!(r"HKEY_LOCAL_MACHINE\SYSTEM\CurrentControlSet\Services\{}", record.name);
+    let (registry_export, export_error) = match export_key(runner, &key, export_file).await {
+        Ok(()) => (Some(export_file.to_path_buf()), None),
+        Err(e) => (None, Some(format!("Service key {}: {}", key, e))),
+    };
+
+    let service = ServiceBackup {
+        display_name: record.display_name.unwrap_or_else(|| record.name.clone()),
+        name: record.name,
+        binary_path: path_name,
+        start_mode: record.start_mode.unwrap_or_else(|| "Manual".to_string()),
+        account: record.start_name,
+        registry_export,
+    };
+    Ok((service, export_error))
+}
+
+async fn export_key(runner: &dyn CommandRunner, key: &str, file: &Path) -> Result<()> {
+    let file = file.display().to_string();
+    runner.checked(&CommandLine::new("reg.exe", ["export", key, file.as_str(), "/y"])).await.map(|_| ())
+}
+
+async fn import_key(runner: &dyn CommandRunner, file: &Path) -> Result<()> {
+    let file = file.display().to_string();
+    runner.checked(&CommandLine::new("reg.exe", ["import", file.as_str()])).await.map(|_| ())
+}
+
+/// New-Service knows no Boot or System start; those services are drivers the key import restores
+fn startup_type(start_mode: &str) -> &'static str {
+    match start_mode {
+        "Auto" => "Automatic",
+        "Disabled" => "Disabled",
+        _ => "Manual",
+    }
+}
+
+/// Scheduled task path in the form schtasks expects
+pub fn task_path(task: &str) -> String {
+    format!("\\{}", task.trim_start_matches('\\'))
+}
+
+fn sanitize(name: &str) -> String {
+    name.chars().map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command_runner::{CommandScript, ScriptedResponse, ScriptedRunner};
+
+    const SERVICE: &str = r#"{"Name":"SpotifyWebHelper","DisplayName":null,"PathName":"C:\\Spotify\\SpotifyWebHelper.exe","StartMode":"Disabled","StartName":"LocalSystem"}"#;
+
+    fn respond(program: &str, contains: &str, exit_code: i32, stdout: &str) -> ScriptedResponse {
+        ScriptedResponse {
+            program: program.to_string(),
+            contains: Some(contains.to_string()),
+            exit_code,
+            stdout: stdout.to_string(),
+            stderr: if exit_code == 0 { String::new() } else { "ERROR: The system was unable to find the specified registry key or value.".to_string() },
+        }
+    }
+
+    fn plan() -> AppRemovalPlan {
+        AppRemovalPlan {
+            app_name: "Spotify".to_string(),
+            display_name: "Spotify Music".to_string(),
+            estimated_size_mb: 0,
+            uninstall_steps: Vec::new(),
+            files: Vec::new(),
+            registry_keys: vec![
+                r"HKEY_CURRENT_USER\Software\Spotify".to_string(),
+                r"HKEY_LOCAL_MACHINE\SOFTWARE\Spotify".to_string(),
+            ],
+            services: vec!["SpotifyWebHelper".to_string()],
+            scheduled_tasks: vec![r"Spotify\Update".to_string()],
+            skipped: Vec::new(),
+            total_bytes: 0,
+            reinstall_sources: vec![
+                ReinstallSource::Winget { id: "Spotify.Spotify".to_string(), source: "winget".to_string() },
+                ReinstallSource::Appx { family: "SpotifyAB.SpotifyMusic_zpdnekdrzrea0".to_string() },
+            ],
+            dependency_impacts: Vec::new(),
+            blocked: false,
+        }
+    }
+
+    fn scratch_directory() -> PathBuf {
+        std::env::temp_dir().join(format!("removal-backup-test-{}", uuid::Uuid::new_v4()))
+    }
+
+    fn commands(runner: &ScriptedRunner) -> Vec<String> {
+        runner.transcript().entries().into_iter().map(|entry| format!("{} {}", entry.command.program, entry.command.args.join(" "))).collect()
+    }
+
+    #[tokio::test]
+    async fn captures_what_the_plan_removes_and_lists_what_it_could_not() {
+        let root = scratch_directory();
+        let runner = ScriptedRunner::new(CommandScript { responses: vec![
+            respond("reg", r"HKEY_LOCAL_MACHINE\SOFTWARE\Spotify", 1, ""),
+            respond("reg", "export", 0, ""),
+            respond("powershell", "Win32_Service", 0, SERVICE),
+            respond("schtasks", "/Query", 0, "<Task />"),
+        ]});
+
+        let backup = RemovalBackup::capture(&runner, &root, &plan()).await.unwrap();
+
+        assert!(backup.id.starts_with("Spotify_"));
+        assert_eq!(backup.registry_exports.len(), 1);
+        assert_eq!(backup.registry_exports[0].key, r"HKEY_CURRENT_USER\Software\Spotify");
+        assert_eq!(backup.errors.len(), 1);
+        assert!(backup.errors[0].starts_with(r"Registry key HKEY_LOCAL_MACHINE\SOFTWARE\Spotify: reg.exe exited with code 1"), "{}", backup.errors[0]);
+        assert_eq!(backup.services[0].display_name, "SpotifyWebHelper");
+        assert_eq!(backup.services[0].start_mode, "Disabled");
+        assert_eq!(backup.services[0].registry_export.as_deref(), Some(backup.directory.join("service_1.reg").as_path()));
+        assert_eq!(backup.scheduled_tasks[0].path, r"\Spotify\Update");
+        assert_eq!(std::fs::read_to_string(&backup.scheduled_tasks[0].file).unwrap(), "<Task />");
+
+        let registry_1 = backup.directory.join("registry_1.reg").display().to_string();
+        let task_1 = backup.directory.join("task_1.xml");
+        let service_1 = backup.directory.join("service_1.reg").display().to_string();
+        let ran = commands(&runner);
+        assert_eq!(ran.len(), 5);
+        assert_eq!(ran[0], format!(r"reg.exe export HKEY_CURRENT_USER\Software\Spotify {} /y", registry_1));
+        assert!(ran[1].starts_with(r"reg.exe export HKEY_LOCAL_MACHINE\SOFTWARE\Spotify "));
+        assert!(ran[2].starts_with("powershell.exe -NoProfile"));
+        assert_eq!(ran[3], format!(r"reg.exe export HKEY_LOCAL_MACHINE\SYSTEM\CurrentControlSet\Services\SpotifyWebHelper {} /y", service_1));
+        assert_eq!(ran[4], r"schtasks.exe /Query /TN \Spotify\Update /XML");
+        assert!(task_1.is_file());
+
+        let saved = RemovalBackup::load(&root, &backup.id).await.unwrap();
+        assert_eq!(saved.reinstall, plan().reinstall_sources);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test]
+    async fn restores_by_reinstalling_first_then_importing() {
+        let root = scratch_directory();
+        let runner = ScriptedRunner::new(CommandScript { responses: vec![
+            respond("reg", "export", 0, ""),
+            respond("powershell", "Win32_Service", 0, SERVICE),
+            respond("schtasks", "/Query", 0, "<Task />"),
+            // The winget package is gone from the source; the staged Appx copy is still there
+            respond("winget", "install", 0x8A150010u32 as i32, ""),
+            respond("powershell", "Add-AppxPackage", 0, ""),
+            respond("reg", "import", 0, ""),
+            respond("powershell", "New-Service", 0, ""),
+            respond("schtasks", "/Create", 0, ""),
+        ]});
+        let backup = RemovalBackup::capture(&runner, &root, &plan()).await.unwrap();
+        let captured = runner.transcript().entries().len();
+
+        let result = backup.restore(&runner).await;
+
+        assert!(result.success, "{:?}", result.errors);
+        assert_eq!(result.details[0], "Registered SpotifyAB.SpotifyMusic_zpdnekdrzrea0 again");
+        assert!(result.details[1].starts_with("Reinstall from winget Spotify.Spotify (winget): no package in the sources matches the ID"), "{}", result.details[1]);
+        assert!(result.details.contains(&"Restored service SpotifyWebHelper".to_string()));
+        assert!(result.details.contains(&r"Restored scheduled task \Spotify\Update".to_string()));
+
+        let ran = &commands(&runner)[captured..];
+        assert!(ran[0].starts_with("winget install --id Spotify.Spotify --exact --source winget"), "{}", ran[0]);
+        assert!(ran[1].starts_with("powershell.exe"));
+        assert_eq!(ran[2], format!("reg.exe import {}", backup.directory.join("registry_1.reg").display()));
+        assert_eq!(ran[3], format!("reg.exe import {}", backup.directory.join("registry_2.reg").display()));
+        assert!(ran[4].starts_with("powershell.exe"));
+        assert_eq!(ran[5], format!("reg.exe import {}", backup.directory.join("service_1.reg").display()));
+        assert_eq!(ran[6], format!(r"schtasks.exe /Create /TN \Spotify\Update /XML {} /F", backup.directory.join("task_1.xml").display()));
+        assert_eq!(ran.len(), 7);
+
+        let service = &runner.transcript().entries()[captured + 4].command;
+        assert!(service.script.as_deref().unwrap().contains("-StartupType 'Disabled'"), "{}", service);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test]
+    async fn fails_the_restore_when_nothing_reinstalls_the_app() {
+        let root = scratch_directory();
+        let runner = ScriptedRunner::new(CommandScript { responses: vec![
+            respond("reg", "export", 0, ""),
+            respond("powershell", "Win32_Service", 1, ""),
+            respond("schtasks", "/Query", 0, "<Task />"),
+            respond("reg", "import", 0, ""),
+            respond("schtasks", "/Create", 0, ""),
+        ]});
+        let mut app = plan();
+        app.reinstall_sources.clear();
+        let backup = RemovalBackup::capture(&runner, &root, &app).await.unwrap();
+        assert!(backup.errors.contains(&"No package source is known to reinstall the app from".to_string()));
+
+        let result = backup.restore(&runner).await;
+
+        assert!(!result.success);
+        assert_eq!(result.errors, ["No package source is known for Spotify Music; reinstall it manually"]);
+        assert!(result.details.contains(&r"Restored registry key HKEY_CURRENT_USER\Software\Spotify".to_string()));
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,176 @@
+use std::path::PathBuf;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
+
+use crate::bloatware::{RemovalMethod, RemovalScope, UninstallOptions};
+use crate::dependencies::DependencyImpact;
+use crate::removal_backup::ReinstallSource;
+use crate::path_resolver::{self, TargetResolver};
+use crate::registry_provider::{RegistryHive, RegistryProvider};
+use crate::winget::WingetPackage;
+
+/// Parent key of every service registration
+const SERVICES_KEY: &str = r"SYSTEM\CurrentControlSet\Services";
+/// Mirror of the Task Scheduler folder tree
+const TASK_TREE_KEY: &str = r"SOFTWARE\Microsoft\Windows NT\CurrentVersion\Schedule\TaskCache\Tree";
+
+/// A plan reviewed longer ago than this no longer describes the machine
+pub const PLAN_LIFETIME_MINUTES: i64 = 30;
+
+/// Every concrete action a removal will take, for review before anything runs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemovalPlan {
+    pub id: String,
+    pub created: DateTime<Utc>,
+    pub options: UninstallOptions,
+    pub apps: Vec<AppRemovalPlan>,
+    /// Bytes on disk under the planned paths
+    pub total_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppRemovalPlan {
+    pub app_name: String,
+    pub display_name: String,
+    /// Estimate from the bloatware database
+    pub estimated_size_mb: u64,
+    /// Tried in order until one succeeds; cleanup only runs after a success
+    pub uninstall_steps: Vec<PlannedUninstall>,
+    pub files: Vec<PlannedPath>,
+    pub registry_keys: Vec<String>,
+    pub services: Vec<String>,
+    pub scheduled_tasks: Vec<String>,
+    /// Definition entries and methods left out of the plan, and why
+    pub skipped: Vec<String>,
+    pub total_bytes: u64,
+    /// How to install the app again once it is gone
+    pub reinstall_sources: Vec<ReinstallSource>,
+    /// Shared components the removal takes away from other installed software
+    pub dependency_impacts: Vec<DependencyImpact>,
+    /// A blocking impact keeps the app from being removed when the plan runs
+    pub blocked: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "method")]
+pub enum PlannedUninstall {
+    Winget { packages: Vec<WingetPackage> },
+    Appx {
+        /// Lower-cased package families
+        families: Vec<String>,
+        /// Full names of the copies installed now
+        installed: Vec<String>,
+        scopes: Vec<RemovalScope>,
+    },
+    /// Windows Installer products, removed with `msiexec /x` by product code
+    Msi { products: Vec<MsiProduct> },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MsiProduct {
+    pub product_code: String,
+    pub display_name: String,
+}
+
+impl PlannedUninstall {
+    pub fn method(&self) -> RemovalMethod {
+        match self {
+            PlannedUninstall::Winget { .. } => RemovalMethod::Winget,
+            PlannedUninstall::Appx { .. } => RemovalMethod::UWP,
+            PlannedUninstall::Msi { .. } => RemovalMethod::PowerShell,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlannedPath {
+    pub path: PathBuf,
+    /// Definition entry the path was resolved from
+    pub pattern: String,
+    pub size_bytes: u64,
+    pub file_count: usize,
+    pub is_directory: bool,
+}
+
+impl PlannedPath {
+    pub fn measure(path: PathBuf, pattern: &str) -> Self {
+        let is_directory = path.is_dir();
+        let (mut size_bytes, mut file_count) = (0, 0);
+        for entry in WalkDir::new(&path).follow_links(false).into_iter().filter_map(|e| e.ok()) {
+            if entry.file_type().is_file() {
+                size_bytes += entry.metadata().map(|m| m.len()).unwrap_or(0);
+                file_count += 1;
+            }
+        }
+        Self { path, pattern: pattern.to_string(), size_bytes, file_count, is_directory }
+    }
+}
+
+/// The Appx scopes a removal touches, in the order they are removed
+pub fn appx_scopes(options: UninstallOptions) -> Vec<RemovalScope> {
+    let mut scopes = Vec::new();
+    // Deprovision first, so no profile picks the package up again while it is being removed
+    if options.deprovision {
+        scopes.push(RemovalScope::Provisioned);
+    }
+    scopes.push(if options.all_users { RemovalScope::AllUsers } else { RemovalScope::CurrentUser });
+    scopes
+}
+
+/// Existing keys matching `keys`, wildcards resolved against the registry; refusals and failures are reported in `skipped`
+pub fn existing_keys(resolver: &TargetResolver, registry: &dyn RegistryProvider, keys: &[String], skipped: &mut Vec<String>) -> Vec<String> {
+    let mut existing = Vec::new();
+    for key in keys {
+        // Cleanup deletes every key a wildcard expands to, so refuse ones that reach a whole hive
+        if let Err(e) = path_resolver::check_key_anchored(key) {
+            skipped.push(format!("Registry key {}", e));
+            continue;
+        }
+        match resolver.resolve_registry_keys(registry, key) {
+            Ok(resolved) => existing.extend(resolved),
+            Err(e) => skipped.push(format!("Registry key {}: {}", key, e)),
+        }
+    }
+    existing.sort();
+    existing.dedup();
+    existing
+}
+
+/// Services among `names` that are registered
+pub fn existing_services(registry: &dyn RegistryProvider, names: &[String], skipped: &mut Vec<String>) -> Vec<String> {
+    names.iter().filter(|name| {
+        match registry.key_exists(RegistryHive::LocalMachine, &format!(r"{}\{}", SERVICES_KEY, name)) {
+            Ok(exists) => exists,
+            Err(e) => {
+                skipped.push(format!("Service {}: {}", name, e));
+                false
+            }
+        }
+    }).cloned().collect()
+}
+
+/// Scheduled tasks among `paths`, given relative to the Task Scheduler root
+pub fn existing_tasks(registry: &dyn RegistryProvider, paths: &[String], skipped: &mut Vec<String>) -> Vec<String> {
+    paths.iter().filter(|path| {
+        match registry.key_exists(RegistryHive::LocalMachine, &format!(r"{}\{}", TASK_TREE_KEY, path.trim_start_matches('\\'))) {
+            Ok(exists) => exists,
+            Err(e) => {
+                skipped.push(format!("Scheduled task {}: {}", path, e));
+                false
+            }
+        }
+    }).cloned().collect()
+}
+
+/// Nested paths would be removed and counted twice; keep the outermost
+pub fn outermost_paths(mut paths: Vec<(PathBuf, String)>) -> Vec<(PathBuf, String)> {
+    paths.sort();
+    let mut kept: Vec<(PathBuf, String)> = Vec::new();
+    for (path, pattern) in paths {
+        if !kept.iter().any(|(outer, _)| path.starts_with(outer)) {
+            kept.push((path, pattern));
+        }
+    }
+    kept
+}
@@ -0,0 +1,338 @@
+use std::path::Path;
+use anyhow::Result;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::bloatware::BloatwareScanResult;
+use crate::file_manager::ScanResult;
+#[cfg(windows)]
+use crate::registry::RegistryScanResult;
+use crate::OptimizationResult;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReportFormat {
+    Json,
+    Csv,
+    Html,
+}
+
+/// Any result that can be exported as a report
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ReportData {
+    DuplicateScan(ScanResult),
+    #[cfg(windows)]
+    RegistryScan(RegistryScanResult),
+    BloatwareScan(BloatwareScanResult),
+    Optimization(OptimizationResult),
+}
+
+/// One table of findings, one row per finding
+struct ReportTable {
+    title: &'static str,
+    headers: &'static [&'static str],
+    rows: Vec<Vec<String>>,
+}
+
+impl ReportData {
+    fn title(&self) -> &'static str {
+        match self {
+            ReportData::DuplicateScan(_) => "Duplicate File Scan",
+            #[cfg(windows)]
+            ReportData::RegistryScan(_) => "Registry Scan",
+            ReportData::BloatwareScan(_) => "Bloatware Scan",
+            ReportData::Optimization(_) => "System Optimization",
+        }
+    }
+
+    fn summary(&self) -> Vec<(&'static str, String)> {
+        match self {
+            ReportData::DuplicateScan(result) => vec![
+                ("Files scanned", result.total_files.to_string()),
+                ("Total size", format_size(result.total_size)),
+                ("Duplicate groups", result.duplicate_groups.len().to_string()),
+                ("Potential savings", format_size(result.duplicate_groups.iter().map(|g| g.potential_savings).sum())),
+                ("Directories", result.scanned_directories.iter().map(|d| d.display().to_string()).collect::<Vec<_>>().join("; ")),
+                ("Duration", format!("{} ms", result.scan_duration_ms)),
+                ("Errors", result.errors.len().to_string()),
+            ],
+            #[cfg(windows)]
+            ReportData::RegistryScan(result) => vec![
+                ("Keys scanned", result.total_keys_scanned.to_string()),
+                ("Orphaned keys", result.orphaned_keys.len().to_string()),
+                ("Bloatware keys", result.bloatware_keys.len().to_string()),
+                ("Dangerous keys", result.dangerous_keys.len().to_string()),
+                ("Duration", format!("{} ms", result.scan_duration_ms)),
+            ],
+            ReportData::BloatwareScan(result) => vec![
+                ("Applications scanned", result.total_apps_scanned.to_string()),
+                ("Bloatware found", result.bloatware_found.len().to_string()),
+                ("Potential savings", format!("{} MB", result.potential_savings_mb)),
+                ("Duration", format!("{} ms", result.scan_duration_ms)),
+                ("Errors", result.errors.len().to_string()),
+            ],
+            ReportData::Optimization(result) => vec![
+                ("Status", if result.success { "Succeeded" } else { "Failed" }.to_string()),
+                ("Message", result.message.clone()),
+                ("Space freed", format!("{} MB", result.space_freed_mb)),
+                ("Files removed", result.files_removed.to_string()),
+                ("Registry entries cleaned", result.registry_entries_cleaned.to_string()),
+                ("Bloatware removed", result.bloatware_removed.to_string()),
+                ("Errors", result.errors.len().to_string()),
+            ],
+        }
+    }
+
+    fn tables(&self) -> Vec<ReportTable> {
+        match self {
+            ReportData::DuplicateScan(result) => vec![ReportTable {
+                title: "Duplicate files",
+                headers: &["Group", "Hash", "Path", "Size", "Modified", "Potential savings"],
+                rows: result.duplicate_groups.iter().enumerate().flat_map(|(index, group)| {
+                    group.files.iter().map(move |file| vec![
+                        (index + 1).to_string(),
+                        group.hash.clone(),
+                        file.path.display().to_string(),
+                        file.size.to_string(),
+                        file.modified.to_rfc3339(),
+                        group.potential_savings.to_string(),
+                    ])
+                }).collect(),
+            }],
+            #[cfg(windows)]
+            ReportData::RegistryScan(result) => vec![ReportTable {
+                title: "Registry keys",
+                headers: &["Finding", "Path", "Type", "Values", "Subkeys", "Last modified"],
+                rows: [("Orphaned", &result.orphaned_keys), ("Bloatware", &result.bloatware_keys), ("Dangerous", &result.dangerous_keys)]
+                    .into_iter()
+                    .flat_map(|(finding, keys)| keys.iter().map(move |key| vec![
+                        finding.to_string(),
+                        key.path.clone(),
+                        key.key_type.clone(),
+                        key.value_count.to_string(),
+                        key.subkey_count.to_string(),
+                        key.last_modified.to_rfc3339(),
+                    ]))
+                    .collect(),
+            }],
+            ReportData::BloatwareScan(result) => vec![ReportTable {
+                title: "Bloatware",
+                headers: &["Name", "Publisher", "Version", "Category", "Size (MB)", "Confidence", "Critical", "Install location"],
+                rows: result.bloatware_found.iter().map(|app| vec![
+                    app.display_name.clone(),
+                    app.publisher.clone(),
+                    app.version.clone(),
+                    format!("{:?}", app.category),
+                    app.size_mb.to_string(),
+                    format!("{:.2}", app.confidence_score),
+                    app.is_critical.to_string(),
+                    app.install_location.display().to_string(),
+                ]).collect(),
+            }],
+            ReportData::Optimization(result) => vec![ReportTable {
+                title: "Steps",
+                headers: &["Kind", "Message"],
+                rows: result.details.iter().map(|detail| vec!["Detail".to_string(), detail.clone()])
+                    .chain(result.errors.iter().map(|error| vec!["Error".to_string(), error.clone()]))
+                    .collect(),
+            }],
+        }
+    }
+}
+
+/// Write a report in the requested format to `path`
+pub fn write_report(data: &ReportData, format: ReportFormat, path: &Path) -> Result<()> {
+    let content = match format {
+        ReportFormat::Json => serde_json::to_string_pretty(data)?,
+        ReportFormat::Csv => render_csv(data),
+        ReportFormat::Html => render_html(data),
+    };
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+/// CSV with one row per finding; multiple tables are separated by a blank line
+fn render_csv(data: &ReportData) -> String {
+    let mut csv = String::new();
+
+    for (index, table) in data.tables().iter().enumerate() {
+        if index > 0 {
+            csv.push_str("\r\n");
+        }
+        push_csv_row(&mut csv, table.headers.iter().map(|header| header.to_string()));
+        for row in &table.rows {
+            push_csv_row(&mut csv, row.iter().cloned());
+        }
+    }
+
+    csv
+}
+
+fn push_csv_row(csv: &mut String, cells: impl Iterator<Item = String>) {
+    let cells: Vec<String> = cells.map(|cell| csv_cell(&cell)).collect();
+    csv.push_str(&cells.join(","));
+    csv.push_str("\r\n");
+}
+
+/// Quote per RFC 4180 and defuse cells spreadsheets would evaluate as formulas
+fn csv_cell(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@', '\t', '\r']) && value.parse::<f64>().is_err() {
+        format!("'{}", value)
+    } else {
+        value.to_string()
+    };
+
+    if value.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+/// Standalone HTML page with inline styles and no external resources
+fn render_html(data: &ReportData) -> String {
+    let mut html = String::new();
+    let title = data.title();
+
+    html.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str(&format!("<title>{} Report</title>\n", escape_html(title)));
+    html.push_str("<style>\n\
+        body { font-family: 'Segoe UI', sans-serif; margin: 2em; color: #1f2933; }\n\
+        h1 { font-size: 1.6em; margin-bottom: 0.2em; }\n\
+        .generated { color: #616e7c; margin-top: 0; }\n\
+        dl { display: grid; grid-template-columns: max-content auto; gap: 0.3em 1.5em; }\n\
+        dt { font-weight: 600; }\n\
+        dd { margin: 0; }\n\
+        table { border-collapse: collapse; width: 100%; font-size: 0.9em; margin-bottom: 2em; }\n\
+        th, td { border: 1px solid #cbd2d9; padding: 0.35em 0.6em; text-align: left; vertical-align: top; }\n\
+        th { background: #f5f7fa; }\n\
+        tr:nth-child(even) td { background: #fafbfc; }\n\
+        td { word-break: break-all; }\n\
+        </style>\n</head>\n<body>\n");
+
+    html.push_str(&format!("<h1>{} Report</h1>\n", escape_html(title)));
+    html.push_str(&format!("<p class=\"generated\">Generated {}</p>\n", Utc::now().format("%Y-%m-%d %H:%M:%S UTC")));
+
+    html.push_str("<h2>Summary</h2>\n<dl>\n");
+    for (label, value) in data.summary() {
+        html.push_str(&format!("<dt>{}</dt><dd>{}</dd>\n", escape_html(label), escape_html(&value)));
+    }
+    html.push_str("</dl>\n");
+
+    for table in data.tables() {
+        html.push_str(&format!("<h2>{} ({})</h2>\n", escape_html(table.title), table.rows.len()));
+        if table.rows.is_empty() {
+            html.push_str("<p>No findings.</p>\n");
+            continue;
+        }
+
+        html.push_str("<table>\n<thead><tr>");
+        for header in table.headers {
+            html.push_str(&format!("<th>{}</th>", escape_html(header)));
+        }
+        html.push_str("</tr></thead>\n<tbody>\n");
+        for row in &table.rows {
+            html.push_str("<tr>");
+            for cell in row {
+                html.push_str(&format!("<td>{}</td>", escape_html(cell)));
+            }
+            html.push_str("</tr>\n");
+        }
+        html.push_str("</tbody>\n</table>\n");
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn optimization(message: &str, details: &[&str], errors: &[&str]) -> ReportData {
+        ReportData::Optimization(OptimizationResult {
+            success: errors.is_empty(),
+            message: message.to_string(),
+            details: details.iter().map(|detail| detail.to_string()).collect(),
+            errors: errors.iter().map(|error| error.to_string()).collect(),
+            space_freed_mb: 0,
+            accounting: Default::default(),
+            files_removed: 0,
+            registry_entries_cleaned: 0,
+            bloatware_removed: 0,
+        })
+    }
+
+    #[test]
+    fn defuses_cells_spreadsheets_would_evaluate() {
+        for (value, expected) in [
+            ("=HYPERLINK(\"http://example.com\")", "\"'=HYPERLINK(\"\"http://example.com\"\")\""),
+            ("+cmd", "'+cmd"),
+            ("-2+3", "'-2+3"),
+            ("@SUM(A1)", "'@SUM(A1)"),
+            ("\t=1+1", "'\t=1+1"),
+            ("\r=1+1", "\"'\r=1+1\""),
+        ] {
+            assert_eq!(csv_cell(value), expected, "{:?}", value);
+        }
+
+        // Plain numbers keep their sign
+        assert_eq!(csv_cell("-12.5"), "-12.5");
+        assert_eq!(csv_cell("+3"), "+3");
+        assert_eq!(csv_cell("C:\\Temp\\a=b"), "C:\\Temp\\a=b");
+    }
+
+    #[test]
+    fn quotes_csv_cells_per_rfc_4180() {
+        assert_eq!(csv_cell("plain"), "plain");
+        assert_eq!(csv_cell(""), "");
+        assert_eq!(csv_cell("a,b"), "\"a,b\"");
+        assert_eq!(csv_cell("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_cell("two\nlines"), "\"two\nlines\"");
+        assert_eq!(csv_cell("two\r\nlines"), "\"two\r\nlines\"");
+
+        let csv = render_csv(&optimization("", &["Removed C:\\a, C:\\b"], &["=cmd|' /C calc'!A0"]));
+        assert_eq!(csv, "Kind,Message\r\nDetail,\"Removed C:\\a, C:\\b\"\r\nError,'=cmd|' /C calc'!A0\r\n");
+    }
+
+    #[test]
+    fn escapes_html_in_every_cell() {
+        assert_eq!(escape_html("<a href=\"x\">Tom & Jerry's</a>"), "&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&#39;s&lt;/a&gt;");
+
+        let html = render_html(&optimization("<b>done</b>", &["<script>alert(1)</script>"], &["\"quoted\" & 'single'"]));
+        assert!(!html.contains("<script>"));
+        assert!(!html.contains("<b>"));
+        assert!(html.contains("<dd>&lt;b&gt;done&lt;/b&gt;</dd>"));
+        assert!(html.contains("<td>&lt;script&gt;alert(1)&lt;/script&gt;</td>"));
+        assert!(html.contains("<td>&quot;quoted&quot; &amp; &#39;single&#39;</td>"));
+        assert!(html.contains("<h2>Steps (2)</h2>"));
+    }
+}
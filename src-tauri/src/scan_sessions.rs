@@ -0,0 +1,236 @@
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::file_manager::{DuplicateGroup, FileInfo, ScanResult};
+
+/// A persisted scan: what was scanned, how, and what was found
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanSession<R, O> {
+    pub id: String,
+    pub created_at: DateTime<Utc>,
+    pub options: O,
+    pub result: R,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanSessionSummary {
+    pub id: String,
+    pub created_at: DateTime<Utc>,
+    pub scanned_directories: Vec<PathBuf>,
+    pub finding_count: usize,
+    pub potential_savings: u64,
+    /// Session this one was incrementally rescanned from
+    pub based_on: Option<String>,
+}
+
+/// Directory listing remembered for incremental rescans
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DirectorySnapshot {
+    pub modified: DateTime<Utc>,
+    /// Files collected from this directory, with their hash once computed
+    pub files: Vec<FileInfo>,
+    pub subdirectories: Vec<PathBuf>,
+}
+
+pub type TreeSnapshot = HashMap<PathBuf, DirectorySnapshot>;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupGrowth {
+    pub hash: String,
+    pub added_files: Vec<FileInfo>,
+    pub removed_files: Vec<FileInfo>,
+    pub savings_change: i64,
+}
+
+/// Changes between two duplicate scan sessions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanDiff {
+    pub from_session: String,
+    pub to_session: String,
+    pub new_groups: Vec<DuplicateGroup>,
+    pub resolved_groups: Vec<DuplicateGroup>,
+    pub changed_groups: Vec<GroupGrowth>,
+    pub total_size_change: i64,
+    pub potential_savings_change: i64,
+}
+
+/// Sessions kept per store; saving another removes the oldest
+pub const SESSION_RETENTION: usize = 20;
+
+/// Scan sessions persisted as one JSON file each, plus an index of summaries
+pub struct ScanSessionStore {
+    directory: PathBuf,
+    index: Vec<ScanSessionSummary>,
+}
+
+impl ScanSessionStore {
+    pub fn new(directory: PathBuf) -> Self {
+        let index = match std::fs::read(directory.join("index.json")) {
+            Ok(content) => serde_json::from_slice(&content).unwrap_or_else(|e| {
+                warn!("Ignoring unreadable scan session index in {}: {}", directory.display(), e);
+                Vec::new()
+            }),
+            Err(_) => Vec::new(),
+        };
+
+        Self { directory, index }
+    }
+
+    /// Session summaries, newest first
+    pub fn list(&self) -> Vec<ScanSessionSummary> {
+        let mut sessions = self.index.clone();
+        sessions.sort_by_key(|session| Reverse(session.created_at));
+        sessions
+    }
+
+    pub fn latest_id(&self) -> Option<String> {
+        self.index.iter().max_by_key(|summary| summary.created_at).map(|summary| summary.id.clone())
+    }
+
+    pub fn save<R: Serialize, O: Serialize>(&mut self, session: &ScanSession<R, O>, summary: ScanSessionSummary) -> Result<()> {
+        std::fs::create_dir_all(&self.directory)?;
+        std::fs::write(self.session_path(&session.id), serde_json::to_vec(session)?)?;
+
+        self.index.retain(|existing| existing.id != summary.id);
+        self.index.push(summary);
+        self.index.sort_by_key(|session| Reverse(session.created_at));
+        let expired = self.index.split_off(self.index.len().min(SESSION_RETENTION));
+        std::fs::write(self.directory.join("index.json"), serde_json::to_vec_pretty(&self.index)?)?;
+
+        for session in expired {
+            for path in [self.session_path(&session.id), self.snapshot_path(&session.id)] {
+                if let Err(e) = std::fs::remove_file(&path) {
+                    if e.kind() != std::io::ErrorKind::NotFound {
+                        warn!("Failed to remove expired scan session file {}: {}", path.display(), e);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn load<R: DeserializeOwned, O: DeserializeOwned>(&self, session_id: &str) -> Result<ScanSession<R, O>> {
+        if !self.index.iter().any(|summary| summary.id == session_id) {
+            return Err(anyhow!("Scan session not found: {}", session_id));
+        }
+        let content = std::fs::read(self.session_path(session_id))?;
+        Ok(serde_json::from_slice(&content)?)
+    }
+
+    pub fn save_snapshot(&self, session_id: &str, snapshot: &TreeSnapshot) -> Result<()> {
+        std::fs::create_dir_all(&self.directory)?;
+        std::fs::write(self.snapshot_path(session_id), serde_json::to_vec(snapshot)?)?;
+        Ok(())
+    }
+
+    /// Snapshot of a session, empty when it was never recorded
+    pub fn load_snapshot(&self, session_id: &str) -> TreeSnapshot {
+        std::fs::read(self.snapshot_path(session_id))
+            .ok()
+            .and_then(|content| serde_json::from_slice(&content).ok())
+            .unwrap_or_default()
+    }
+
+    fn session_path(&self, session_id: &str) -> PathBuf {
+        self.directory.join(format!("{}.json", session_id))
+    }
+
+    fn snapshot_path(&self, session_id: &str) -> PathBuf {
+        self.directory.join(format!("{}.snapshot.json", session_id))
+    }
+}
+
+/// Compare duplicate groups of two scans by content hash
+pub fn diff_scan_results(from_session: &str, from: &ScanResult, to_session: &str, to: &ScanResult) -> ScanDiff {
+    let before: HashMap<&str, &DuplicateGroup> = from.duplicate_groups.iter().map(|group| (group.hash.as_str(), group)).collect();
+    let after: HashMap<&str, &DuplicateGroup> = to.duplicate_groups.iter().map(|group| (group.hash.as_str(), group)).collect();
+
+    let new_groups = to.duplicate_groups.iter()
+        .filter(|group| !before.contains_key(group.hash.as_str()))
+        .cloned()
+        .collect();
+    let resolved_groups = from.duplicate_groups.iter()
+        .filter(|group| !after.contains_key(group.hash.as_str()))
+        .cloned()
+        .collect();
+
+    let mut changed_groups = Vec::new();
+    for group in &to.duplicate_groups {
+        let Some(previous) = before.get(group.hash.as_str()) else { continue };
+        let previous_paths: HashSet<&PathBuf> = previous.files.iter().map(|file| &file.path).collect();
+        let current_paths: HashSet<&PathBuf> = group.files.iter().map(|file| &file.path).collect();
+
+        let added_files: Vec<FileInfo> = group.files.iter().filter(|file| !previous_paths.contains(&file.path)).cloned().collect();
+        let removed_files: Vec<FileInfo> = previous.files.iter().filter(|file| !current_paths.contains(&file.path)).cloned().collect();
+        if added_files.is_empty() && removed_files.is_empty() {
+            continue;
+        }
+
+        changed_groups.push(GroupGrowth {
+            hash: group.hash.clone(),
+            added_files,
+            removed_files,
+            savings_change: group.potential_savings as i64 - previous.potential_savings as i64,
+        });
+    }
+
+    let savings = |result: &ScanResult| result.duplicate_groups.iter().map(|group| group.potential_savings).sum::<u64>() as i64;
+
+    ScanDiff {
+        from_session: from_session.to_string(),
+        to_session: to_session.to_string(),
+        new_groups,
+        resolved_groups,
+        changed_groups,
+        total_size_change: to.total_size as i64 - from.total_size as i64,
+        potential_savings_change: savings(to) - savings(from),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(id: &str, created_at: DateTime<Utc>) -> ScanSessionSummary {
+        ScanSessionSummary {
+            id: id.to_string(),
+            created_at,
+            scanned_directories: Vec::new(),
+            finding_count: 0,
+            potential_savings: 0,
+            based_on: None,
+        }
+    }
+
+    #[test]
+    fn prunes_the_oldest_sessions_and_their_snapshots() {
+        let directory = std::env::temp_dir().join(format!("scan-sessions-test-{}", uuid::Uuid::new_v4()));
+        let mut store = ScanSessionStore::new(directory.clone());
+        let started = Utc::now();
+
+        for index in 0..SESSION_RETENTION + 2 {
+            let id = format!("scan_{}", index);
+            let session = ScanSession { id: id.clone(), created_at: started + chrono::Duration::seconds(index as i64), options: (), result: () };
+            store.save(&session, summary(&id, session.created_at)).unwrap();
+            store.save_snapshot(&id, &TreeSnapshot::new()).unwrap();
+        }
+
+        let kept: Vec<String> = store.list().into_iter().map(|session| session.id).collect();
+        assert_eq!(kept.len(), SESSION_RETENTION);
+        assert_eq!(kept[0], format!("scan_{}", SESSION_RETENTION + 1));
+        assert!(!kept.contains(&"scan_0".to_string()) && !kept.contains(&"scan_1".to_string()));
+        for expired in ["scan_0", "scan_1"] {
+            assert!(!directory.join(format!("{}.json", expired)).exists());
+            assert!(!directory.join(format!("{}.snapshot.json", expired)).exists());
+        }
+        assert!(store.load::<(), ()>("scan_1").is_err());
+        assert_eq!(ScanSessionStore::new(directory.clone()).list().len(), SESSION_RETENTION);
+        let _ = std::fs::remove_dir_all(&directory);
+    }
+}
@@ -0,0 +1,789 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+use uuid::Uuid;
+use walkdir::WalkDir;
+use tracing::{info, warn};
+
+use crate::path_resolver;
+
+const HEADER_SIZE: usize = 0x4C;
+const LINK_CLSID: [u8; 16] = [
+    0x01, 0x14, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0xC0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x46,
+];
+
+// LinkFlags
+const HAS_LINK_TARGET_ID_LIST: u32 = 0x0000_0001;
+const HAS_LINK_INFO: u32 = 0x0000_0002;
+const HAS_NAME: u32 = 0x0000_0004;
+const HAS_RELATIVE_PATH: u32 = 0x0000_0008;
+const HAS_WORKING_DIR: u32 = 0x0000_0010;
+const HAS_ARGUMENTS: u32 = 0x0000_0020;
+const HAS_ICON_LOCATION: u32 = 0x0000_0040;
+const IS_UNICODE: u32 = 0x0000_0080;
+const FORCE_NO_LINK_INFO: u32 = 0x0000_0100;
+const HAS_DARWIN_ID: u32 = 0x0000_1000;
+
+// LinkInfoFlags
+const VOLUME_ID_AND_LOCAL_BASE_PATH: u32 = 0x1;
+const COMMON_NETWORK_RELATIVE_LINK_AND_PATH_SUFFIX: u32 = 0x2;
+
+// ExtraData block signatures
+const ENVIRONMENT_VARIABLE_DATA_BLOCK: u32 = 0xA000_0001;
+const DARWIN_DATA_BLOCK: u32 = 0xA000_0006;
+const KNOWN_FOLDER_DATA_BLOCK: u32 = 0xA000_000B;
+
+/// Known folders worth naming in the UI, keyed by FOLDERID
+const KNOWN_FOLDERS: [(&str, &str); 12] = [
+    ("905e63b6-c1bf-494e-b29c-65b732d3d21a", "ProgramFiles"),
+    ("7c5a40ef-a0fb-4bfc-874a-c0f2e0b9fa8e", "ProgramFilesX86"),
+    ("6d809377-6af0-444b-8957-a3773f02200e", "ProgramFilesX64"),
+    ("1ac14e77-02e7-4e5d-b744-2eb1ae5198b7", "System"),
+    ("d65231b0-b2f1-4857-a4ce-a8e7c6ea7d27", "SystemX86"),
+    ("f38bf404-1d43-42f2-9305-67de0b28fc23", "Windows"),
+    ("f1b32785-6fba-4fcf-9d55-7b8e7f157091", "LocalAppData"),
+    ("3eb685db-65f9-4cf6-a03a-e3ef65729f3d", "RoamingAppData"),
+    ("62ab5d82-fdc1-4dc3-a9dd-070d1d495d97", "ProgramData"),
+    ("5e6c858f-0e22-4760-9afe-ea3317b67173", "Profile"),
+    ("b4bfcc3a-db2c-424c-b029-7fe99a87c641", "Desktop"),
+    ("fdd39ad0-238f-46af-adb4-6c85480369c7", "Documents"),
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KnownFolderReference {
+    pub folder_id: String,
+    pub folder_name: Option<String>,
+    pub offset: u32,
+}
+
+/// Parsed Shell Link (.lnk) file
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ShellLink {
+    pub link_flags: u32,
+    pub file_attributes: u32,
+    pub file_size: u32,
+    pub has_id_list: bool,
+    pub local_base_path: Option<String>,
+    pub network_share: Option<String>,
+    pub common_path_suffix: Option<String>,
+    pub name: Option<String>,
+    pub relative_path: Option<String>,
+    pub working_directory: Option<String>,
+    pub arguments: Option<String>,
+    pub icon_location: Option<String>,
+    pub environment_target: Option<String>,
+    pub known_folder: Option<KnownFolderReference>,
+    pub darwin_id: Option<String>,
+}
+
+impl ShellLink {
+    /// Parse the binary Shell Link format (MS-SHLLINK)
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let reader = ByteReader { data };
+
+        if reader.u32(0)? as usize != HEADER_SIZE || reader.slice(4, 16)? != LINK_CLSID {
+            return Err(anyhow!("Not a shell link file"));
+        }
+
+        let mut link = ShellLink {
+            link_flags: reader.u32(20)?,
+            file_attributes: reader.u32(24)?,
+            file_size: reader.u32(52)?,
+            ..Default::default()
+        };
+        let flags = link.link_flags;
+        let mut offset = HEADER_SIZE;
+
+        if flags & HAS_LINK_TARGET_ID_LIST != 0 {
+            // Shell item IDs are opaque here, the path comes from LinkInfo or the extra data
+            link.has_id_list = true;
+            let size = reader.u16(offset)? as usize;
+            reader.slice(offset + 2, size)?;
+            offset += 2 + size;
+        }
+
+        if flags & HAS_LINK_INFO != 0 && flags & FORCE_NO_LINK_INFO == 0 {
+            let size = reader.u32(offset)? as usize;
+            link.parse_link_info(&ByteReader { data: reader.slice(offset, size)? })?;
+            offset += size;
+        } else if flags & HAS_LINK_INFO != 0 {
+            offset += reader.u32(offset)? as usize;
+        }
+
+        let unicode = flags & IS_UNICODE != 0;
+        for (flag, field) in [
+            (HAS_NAME, &mut link.name),
+            (HAS_RELATIVE_PATH, &mut link.relative_path),
+            (HAS_WORKING_DIR, &mut link.working_directory),
+            (HAS_ARGUMENTS, &mut link.arguments),
+            (HAS_ICON_LOCATION, &mut link.icon_location),
+        ] {
+            if flags & flag != 0 {
+                let (value, consumed) = reader.counted_string(offset, unicode)?;
+                *field = Some(value);
+                offset += consumed;
+            }
+        }
+
+        link.parse_extra_data(&reader, offset);
+
+        Ok(link)
+    }
+
+    /// True for MSI advertised shortcuts, whose target is resolved by Windows Installer
+    pub fn is_advertised(&self) -> bool {
+        self.link_flags & HAS_DARWIN_ID != 0 || self.darwin_id.is_some()
+    }
+
+    /// Full target path from LinkInfo, if the link carries one
+    pub fn link_info_target(&self) -> Option<String> {
+        let suffix = self.common_path_suffix.clone().unwrap_or_default();
+        if let Some(base) = &self.local_base_path {
+            return Some(format!("{}{}", base, suffix));
+        }
+        self.network_share.as_ref().map(|share| {
+            if suffix.is_empty() { share.clone() } else { format!("{}\\{}", share, suffix) }
+        })
+    }
+
+    fn parse_link_info(&mut self, info: &ByteReader) -> Result<()> {
+        let header_size = info.u32(4)? as usize;
+        let info_flags = info.u32(8)?;
+
+        if info_flags & VOLUME_ID_AND_LOCAL_BASE_PATH != 0 {
+            self.local_base_path = if header_size >= 0x24 && info.u32(28)? != 0 {
+                Some(info.utf16_z(info.u32(28)? as usize)?)
+            } else {
+                Some(info.ansi_z(info.u32(16)? as usize)?)
+            };
+        }
+
+        if info_flags & COMMON_NETWORK_RELATIVE_LINK_AND_PATH_SUFFIX != 0 {
+            let network_offset = info.u32(20)? as usize;
+            let net_name_offset = info.u32(network_offset + 8)? as usize;
+            self.network_share = Some(if net_name_offset > 0x14 {
+                let unicode_offset = info.u32(network_offset + 20)? as usize;
+                info.utf16_z(network_offset + unicode_offset)?
+            } else {
+                info.ansi_z(network_offset + net_name_offset)?
+            });
+        }
+
+        let suffix = if header_size >= 0x24 && info.u32(32)? != 0 {
+            info.utf16_z(info.u32(32)? as usize)?
+        } else {
+            info.ansi_z(info.u32(24)? as usize)?
+        };
+        if !suffix.is_empty() {
+            self.common_path_suffix = Some(suffix);
+        }
+
+        Ok(())
+    }
+
+    /// Walk the ExtraData blocks; malformed trailing data is ignored
+    fn parse_extra_data(&mut self, reader: &ByteReader, mut offset: usize) {
+        while let (Ok(size), Ok(signature)) = (reader.u32(offset), reader.u32(offset + 4)) {
+            let size = size as usize;
+            if size < 8 {
+                break;
+            }
+            let Ok(block) = reader.slice(offset, size) else { break };
+            let block = ByteReader { data: block };
+
+            match signature {
+                ENVIRONMENT_VARIABLE_DATA_BLOCK => {
+                    let target = block.utf16_z(268)
+                        .ok()
+                        .filter(|value| !value.is_empty())
+                        .or_else(|| block.ansi_z(8).ok().filter(|value| !value.is_empty()));
+                    self.environment_target = target;
+                }
+                KNOWN_FOLDER_DATA_BLOCK => {
+                    if let (Ok(guid), Ok(folder_offset)) = (block.slice(8, 16), block.u32(24)) {
+                        let folder_id = format_guid(guid);
+                        let folder_name = KNOWN_FOLDERS.iter()
+                            .find(|(id, _)| *id == folder_id)
+                            .map(|(_, name)| name.to_string());
+                        self.known_folder = Some(KnownFolderReference { folder_id, folder_name, offset: folder_offset });
+                    }
+                }
+                DARWIN_DATA_BLOCK => {
+                    self.darwin_id = block.utf16_z(268)
+                        .ok()
+                        .filter(|value| !value.is_empty())
+                        .or_else(|| block.ansi_z(8).ok());
+                }
+                _ => {}
+            }
+
+            offset += size;
+        }
+    }
+}
+
+/// Bounds-checked little-endian reader over a byte slice
+struct ByteReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> ByteReader<'a> {
+    fn slice(&self, offset: usize, len: usize) -> Result<&'a [u8]> {
+        offset.checked_add(len)
+            .and_then(|end| self.data.get(offset..end))
+            .ok_or_else(|| anyhow!("Shell link truncated at offset {}", offset))
+    }
+
+    fn u16(&self, offset: usize) -> Result<u16> {
+        let bytes = self.slice(offset, 2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&self, offset: usize) -> Result<u32> {
+        let bytes = self.slice(offset, 4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    /// NUL-terminated string in the system code page, decoded as Latin-1
+    fn ansi_z(&self, offset: usize) -> Result<String> {
+        let rest = self.data.get(offset..).ok_or_else(|| anyhow!("Shell link truncated at offset {}", offset))?;
+        let end = rest.iter().position(|&b| b == 0).unwrap_or(rest.len());
+        Ok(rest[..end].iter().map(|&b| b as char).collect())
+    }
+
+    /// NUL-terminated UTF-16LE string
+    fn utf16_z(&self, offset: usize) -> Result<String> {
+        let rest = self.data.get(offset..).ok_or_else(|| anyhow!("Shell link truncated at offset {}", offset))?;
+        let units: Vec<u16> = rest.chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .take_while(|&unit| unit != 0)
+            .collect();
+        Ok(String::from_utf16_lossy(&units))
+    }
+
+    /// StringData entry: a character count followed by the characters, returns bytes consumed
+    fn counted_string(&self, offset: usize, unicode: bool) -> Result<(String, usize)> {
+        let count = self.u16(offset)? as usize;
+        if unicode {
+            let bytes = self.slice(offset + 2, count * 2)?;
+            let units: Vec<u16> = bytes.chunks_exact(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]])).collect();
+            Ok((String::from_utf16_lossy(&units), 2 + count * 2))
+        } else {
+            let bytes = self.slice(offset + 2, count)?;
+            Ok((bytes.iter().map(|&b| b as char).collect(), 2 + count))
+        }
+    }
+}
+
+/// Format a little-endian GUID as lowercase registry text without braces
+fn format_guid(bytes: &[u8]) -> String {
+    format!(
+        "{:08x}-{:04x}-{:04x}-{:02x}{:02x}-{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}",
+        u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        u16::from_le_bytes([bytes[4], bytes[5]]),
+        u16::from_le_bytes([bytes[6], bytes[7]]),
+        bytes[8], bytes[9], bytes[10], bytes[11], bytes[12], bytes[13], bytes[14], bytes[15],
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ShortcutLocation {
+    Desktop,
+    PublicDesktop,
+    StartMenu,
+    CommonStartMenu,
+    QuickLaunch,
+    TaskbarPin,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BrokenShortcut {
+    pub path: PathBuf,
+    pub location: ShortcutLocation,
+    pub target: String,
+    pub arguments: Option<String>,
+    pub working_directory: Option<String>,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShortcutScanResult {
+    pub total_shortcuts: usize,
+    pub broken_shortcuts: Vec<BrokenShortcut>,
+    pub unresolved_shortcuts: usize,
+    pub scan_duration_ms: u64,
+    pub scanned_directories: Vec<PathBuf>,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuarantinedShortcut {
+    pub id: String,
+    pub original_path: PathBuf,
+    pub quarantine_path: PathBuf,
+    pub target: String,
+    pub quarantined_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuarantineResult {
+    pub processed: usize,
+    pub errors: Vec<String>,
+}
+
+/// Outcome of checking a shortcut's target
+enum TargetState {
+    Present,
+    Missing(String),
+    /// No checkable local path (shell namespace, advertised, network or offline volume)
+    Unknown,
+}
+
+pub struct ShortcutManager {
+    quarantine_directory: PathBuf,
+    quarantine: Arc<RwLock<Vec<QuarantinedShortcut>>>,
+}
+
+impl ShortcutManager {
+    pub fn new(backup_dir: PathBuf) -> Self {
+        let quarantine_directory = backup_dir.join("quarantine").join("shortcuts");
+        let quarantine = std::fs::read(quarantine_directory.join("manifest.json"))
+            .ok()
+            .and_then(|content| serde_json::from_slice(&content).ok())
+            .unwrap_or_default();
+
+        Self {
+            quarantine_directory,
+            quarantine: Arc::new(RwLock::new(quarantine)),
+        }
+    }
+
+    /// Scan the Desktop, Start Menu, Quick Launch and taskbar pin folders for broken shortcuts
+    pub async fn scan_broken_shortcuts(&self) -> Result<ShortcutScanResult> {
+        let start_time = std::time::Instant::now();
+        let locations = shortcut_locations();
+        let mut result = ShortcutScanResult {
+            total_shortcuts: 0,
+            broken_shortcuts: Vec::new(),
+            unresolved_shortcuts: 0,
+            scan_duration_ms: 0,
+            scanned_directories: locations.iter().map(|(path, _)| path.clone()).collect(),
+            errors: Vec::new(),
+        };
+
+        info!("Starting broken shortcut scan");
+
+        let mut seen = std::collections::HashSet::new();
+        for (directory, location) in locations {
+            if !directory.is_dir() {
+                continue;
+            }
+
+            for entry in WalkDir::new(&directory).follow_links(false).into_iter().filter_map(|e| e.ok()) {
+                let path = entry.path();
+                let is_shortcut = entry.file_type().is_file() && path.extension()
+                    .map(|ext| ext.eq_ignore_ascii_case("lnk"))
+                    .unwrap_or(false);
+                // Taskbar pins live below Quick Launch, so keep the most specific location
+                if !is_shortcut || !seen.insert(path.to_path_buf()) {
+                    continue;
+                }
+
+                result.total_shortcuts += 1;
+
+                let link = match tokio::fs::read(path).await.map_err(anyhow::Error::from).and_then(|data| ShellLink::parse(&data)) {
+                    Ok(link) => link,
+                    Err(e) => {
+                        result.errors.push(format!("Failed to parse {}: {}", path.display(), e));
+                        continue;
+                    }
+                };
+
+                match check_target(&link, path) {
+                    TargetState::Present => {}
+                    TargetState::Unknown => result.unresolved_shortcuts += 1,
+                    TargetState::Missing(target) => {
+                        result.broken_shortcuts.push(BrokenShortcut {
+                            path: path.to_path_buf(),
+                            location,
+                            target,
+                            arguments: link.arguments.clone(),
+                            working_directory: link.working_directory.clone(),
+                            size: entry.metadata().map(|m| m.len()).unwrap_or(0),
+                        });
+                    }
+                }
+            }
+        }
+
+        result.scan_duration_ms = start_time.elapsed().as_millis() as u64;
+
+        info!("Shortcut scan completed: {} of {} shortcuts broken, {}ms",
+              result.broken_shortcuts.len(), result.total_shortcuts, result.scan_duration_ms);
+
+        Ok(result)
+    }
+
+    /// Move broken shortcuts into quarantine so they can be restored later
+    pub async fn quarantine_shortcuts(&self, shortcuts: Vec<BrokenShortcut>) -> Result<QuarantineResult> {
+        let mut result = QuarantineResult { processed: 0, errors: Vec::new() };
+        tokio::fs::create_dir_all(&self.quarantine_directory).await?;
+
+        let mut quarantine = self.quarantine.write().await;
+        for shortcut in shortcuts {
+            // Re-check right before moving, the target may have been reinstalled meanwhile
+            let still_broken = match tokio::fs::read(&shortcut.path).await {
+                Ok(data) => ShellLink::parse(&data)
+                    .map(|link| matches!(check_target(&link, &shortcut.path), TargetState::Missing(_)))
+                    .unwrap_or(false),
+                Err(_) => false,
+            };
+            if !still_broken {
+                result.errors.push(format!("Skipped {}: shortcut is no longer broken", shortcut.path.display()));
+                continue;
+            }
+
+            let id = Uuid::new_v4().to_string();
+            let quarantine_path = self.quarantine_directory.join(format!("{}.lnk", id));
+            if let Err(e) = move_file(&shortcut.path, &quarantine_path).await {
+                result.errors.push(format!("Failed to quarantine {}: {}", shortcut.path.display(), e));
+                continue;
+            }
+
+            quarantine.push(QuarantinedShortcut {
+                id,
+                original_path: shortcut.path,
+                quarantine_path,
+                target: shortcut.target,
+                quarantined_at: Utc::now(),
+            });
+            result.processed += 1;
+        }
+
+        self.save_manifest(&quarantine).await?;
+        info!("Quarantined {} broken shortcuts", result.processed);
+
+        Ok(result)
+    }
+
+    /// Move quarantined shortcuts back to where they came from
+    pub async fn restore_shortcuts(&self, ids: Vec<String>) -> Result<QuarantineResult> {
+        let mut result = QuarantineResult { processed: 0, errors: Vec::new() };
+
+        let mut quarantine = self.quarantine.write().await;
+        for id in ids {
+            let Some(index) = quarantine.iter().position(|entry| entry.id == id) else {
+                result.errors.push(format!("Quarantine entry not found: {}", id));
+                continue;
+            };
+
+            let entry = &quarantine[index];
+            if entry.original_path.exists() {
+                result.errors.push(format!("Cannot restore {}: a file already exists there", entry.original_path.display()));
+                continue;
+            }
+
+            if let Some(parent) = entry.original_path.parent() {
+                if let Err(e) = tokio::fs::create_dir_all(parent).await {
+                    result.errors.push(format!("Failed to recreate {}: {}", parent.display(), e));
+                    continue;
+                }
+            }
+            if let Err(e) = move_file(&entry.quarantine_path, &entry.original_path).await {
+                result.errors.push(format!("Failed to restore {}: {}", entry.original_path.display(), e));
+                continue;
+            }
+
+            quarantine.remove(index);
+            result.processed += 1;
+        }
+
+        self.save_manifest(&quarantine).await?;
+
+        Ok(result)
+    }
+
+    /// List shortcuts currently held in quarantine
+    pub async fn list_quarantined(&self) -> Vec<QuarantinedShortcut> {
+        self.quarantine.read().await.clone()
+    }
+
+    async fn save_manifest(&self, quarantine: &[QuarantinedShortcut]) -> Result<()> {
+        tokio::fs::create_dir_all(&self.quarantine_directory).await?;
+        let content = serde_json::to_vec_pretty(quarantine)?;
+        tokio::fs::write(self.quarantine_directory.join("manifest.json"), content).await?;
+        Ok(())
+    }
+}
+
+/// Folders holding user-facing shortcuts, most specific first
+fn shortcut_locations() -> Vec<(PathBuf, ShortcutLocation)> {
+    let mut locations = Vec::new();
+    let env_path = |name: &str| std::env::var_os(name).map(PathBuf::from);
+
+    if let Some(app_data) = env_path("APPDATA") {
+        let quick_launch = app_data.join("Microsoft").join("Internet Explorer").join("Quick Launch");
+        locations.push((quick_launch.join("User Pinned").join("TaskBar"), ShortcutLocation::TaskbarPin));
+        locations.push((quick_launch, ShortcutLocation::QuickLaunch));
+        locations.push((app_data.join("Microsoft").join("Windows").join("Start Menu"), ShortcutLocation::StartMenu));
+    }
+    if let Some(program_data) = env_path("PROGRAMDATA") {
+        locations.push((program_data.join("Microsoft").join("Windows").join("Start Menu"), ShortcutLocation::CommonStartMenu));
+    }
+    if let Some(profile) = env_path("USERPROFILE") {
+        locations.push((profile.join("Desktop"), ShortcutLocation::Desktop));
+    }
+    if let Some(one_drive) = env_path("OneDrive") {
+        locations.push((one_drive.join("Desktop"), ShortcutLocation::Desktop));
+    }
+    if let Some(public) = env_path("PUBLIC") {
+        locations.push((public.join("Desktop"), ShortcutLocation::PublicDesktop));
+    }
+
+    locations
+}
+
+/// Decide whether a shortcut's target still exists
+fn check_target(link: &ShellLink, link_path: &Path) -> TargetState {
+    if link.is_advertised() {
+        return TargetState::Unknown;
+    }
+
+    let mut candidates = Vec::new();
+    if let Some(target) = &link.environment_target {
+        candidates.push(path_resolver::expand_environment_strings(target));
+    }
+    if let Some(target) = link.link_info_target() {
+        candidates.push(target);
+    }
+    if let (Some(relative), Some(parent)) = (&link.relative_path, link_path.parent()) {
+        candidates.push(parent.join(relative).to_string_lossy().to_string());
+    }
+
+    let candidates: Vec<String> = candidates.into_iter().filter(|c| !c.is_empty()).collect();
+    let Some(primary) = candidates.first().cloned() else {
+        return TargetState::Unknown;
+    };
+
+    if candidates.iter().any(|candidate| Path::new(candidate).exists()) {
+        return TargetState::Present;
+    }
+
+    // Never call a target missing because its share or removable volume is unreachable
+    let checkable = candidates.iter().any(|candidate| {
+        !candidate.starts_with(r"\\") && volume_root(candidate).map(|root| root.exists()).unwrap_or(false)
+    });
+    if !checkable {
+        return TargetState::Unknown;
+    }
+
+    TargetState::Missing(primary)
+}
+
+/// Root of the volume a path lives on, e.g. `C:\` or `/`
+fn volume_root(path: &str) -> Option<PathBuf> {
+    Path::new(path).ancestors().last().map(|root| {
+        let root = root.to_string_lossy();
+        if root.len() == 2 && root.ends_with(':') { PathBuf::from(format!("{}\\", root)) } else { PathBuf::from(root.as_ref()) }
+    })
+}
+
+/// Move a file, falling back to copy-and-delete across volumes
+async fn move_file(from: &Path, to: &Path) -> Result<()> {
+    if tokio::fs::rename(from, to).await.is_ok() {
+        return Ok(());
+    }
+
+    tokio::fs::copy(from, to).await?;
+    if let Err(e) = tokio::fs::remove_file(from).await {
+        warn!("Copied {} but failed to remove the original: {}", from.display(), e);
+        let _ = tokio::fs::remove_file(to).await;
+        return Err(e.into());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16z(text: &str) -> Vec<u8> {
+        text.encode_utf16().chain([0]).flat_map(u16::to_le_bytes).collect()
+    }
+
+    fn header(flags: u32) -> Vec<u8> {
+        let mut data = vec![0; HEADER_SIZE];
+        data[0..4].copy_from_slice(&(HEADER_SIZE as u32).to_le_bytes());
+        data[4..20].copy_from_slice(&LINK_CLSID);
+        data[20..24].copy_from_slice(&flags.to_le_bytes());
+        data[24..28].copy_from_slice(&0x20u32.to_le_bytes());
+        data[52..56].copy_from_slice(&4096u32.to_le_bytes());
+        data
+    }
+
+    /// LinkInfo with a local base path, in the ANSI fields and, newer style, the Unicode ones
+    fn link_info(ansi_base: &str, unicode_base: &str, unicode_suffix: &str) -> Vec<u8> {
+        const INFO_HEADER: usize = 0x24;
+        let mut strings = Vec::new();
+        let mut place = |bytes: Vec<u8>| {
+            let offset = INFO_HEADER + strings.len();
+            strings.extend(bytes);
+            offset as u32
+        };
+        let ansi_base = place(ansi_base.bytes().chain([0]).collect());
+        let ansi_suffix = place(vec![0]);
+        let unicode_base = place(utf16z(unicode_base));
+        let unicode_suffix = place(utf16z(unicode_suffix));
+
+        let fields = [
+            (INFO_HEADER + strings.len()) as u32,
+            INFO_HEADER as u32,
+            VOLUME_ID_AND_LOCAL_BASE_PATH,
+            0,
+            ansi_base,
+            0,
+            ansi_suffix,
+            unicode_base,
+            unicode_suffix,
+        ];
+        let mut info: Vec<u8> = fields.iter().flat_map(|field| field.to_le_bytes()).collect();
+        info.extend(strings);
+        info
+    }
+
+    fn counted(text: &str) -> Vec<u8> {
+        let units: Vec<u16> = text.encode_utf16().collect();
+        (units.len() as u16).to_le_bytes().into_iter().chain(units.iter().flat_map(|unit| unit.to_le_bytes())).collect()
+    }
+
+    /// Environment variable and Darwin blocks share a layout: ANSI text at 8, Unicode text at 268
+    fn text_block(signature: u32, text: &str) -> Vec<u8> {
+        let mut block = vec![0; 0x314];
+        block[0..4].copy_from_slice(&0x314u32.to_le_bytes());
+        block[4..8].copy_from_slice(&signature.to_le_bytes());
+        let unicode = utf16z(text);
+        block[268..268 + unicode.len()].copy_from_slice(&unicode);
+        block
+    }
+
+    fn known_folder_block(guid: [u8; 16], offset: u32) -> Vec<u8> {
+        let mut block = Vec::new();
+        block.extend(0x1Cu32.to_le_bytes());
+        block.extend(KNOWN_FOLDER_DATA_BLOCK.to_le_bytes());
+        block.extend(guid);
+        block.extend(offset.to_le_bytes());
+        block
+    }
+
+    /// FOLDERID_ProgramFiles in its on-disk byte order
+    const PROGRAM_FILES: [u8; 16] = [
+        0xB6, 0x63, 0x5E, 0x90, 0xBF, 0xC1, 0x4E, 0x49, 0xB2, 0x9C, 0x65, 0xB7, 0x32, 0xD3, 0xD2, 0x1A,
+    ];
+
+    fn full_link() -> Vec<u8> {
+        let mut data = header(HAS_LINK_TARGET_ID_LIST | HAS_LINK_INFO | HAS_NAME | HAS_ARGUMENTS | IS_UNICODE);
+        data.extend(4u16.to_le_bytes());
+        data.extend([0x02, 0x00, 0x00, 0x00]);
+        data.extend(link_info(r"C:\PROGRA~1\", r"C:\Program Files\Café\", "café.exe"));
+        data.extend(counted("Café Launcher"));
+        data.extend(counted("--safe-mode"));
+        data.extend(text_block(ENVIRONMENT_VARIABLE_DATA_BLOCK, r"%ProgramFiles%\Café\café.exe"));
+        data.extend(known_folder_block(PROGRAM_FILES, 0x1E));
+        data.extend(text_block(DARWIN_DATA_BLOCK, "w_1^VX!!!!!!!!!MKKSkAppFiles>tW{~$4Q]c@II=l2xaTO5Z"));
+        data.extend(0u32.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn parses_a_bare_header() {
+        let link = ShellLink::parse(&header(0)).unwrap();
+
+        assert_eq!((link.link_flags, link.file_attributes, link.file_size), (0, 0x20, 4096));
+        assert!(!link.has_id_list && !link.is_advertised());
+        assert_eq!(link.link_info_target(), None);
+    }
+
+    #[test]
+    fn prefers_the_unicode_link_info_paths() {
+        let link = ShellLink::parse(&full_link()).unwrap();
+
+        assert!(link.has_id_list);
+        assert_eq!(link.local_base_path.as_deref(), Some(r"C:\Program Files\Café\"));
+        assert_eq!(link.link_info_target().as_deref(), Some(r"C:\Program Files\Café\café.exe"));
+        assert_eq!(link.name.as_deref(), Some("Café Launcher"));
+        assert_eq!(link.arguments.as_deref(), Some("--safe-mode"));
+    }
+
+    #[test]
+    fn reads_the_ansi_link_info_path_of_older_links() {
+        let mut info = link_info(r"D:\Games\Solitaire.exe", "", "");
+        // An 0x1C-byte header has no Unicode offsets
+        info[4..8].copy_from_slice(&0x1Cu32.to_le_bytes());
+        let mut data = header(HAS_LINK_INFO);
+        data.extend(info);
+
+        let link = ShellLink::parse(&data).unwrap();
+        assert_eq!(link.link_info_target().as_deref(), Some(r"D:\Games\Solitaire.exe"));
+    }
+
+    #[test]
+    fn reads_the_extra_data_blocks() {
+        let link = ShellLink::parse(&full_link()).unwrap();
+
+        assert_eq!(link.environment_target.as_deref(), Some(r"%ProgramFiles%\Café\café.exe"));
+        let known_folder = link.known_folder.as_ref().unwrap();
+        assert_eq!(known_folder.folder_id, "905e63b6-c1bf-494e-b29c-65b732d3d21a");
+        assert_eq!(known_folder.folder_name.as_deref(), Some("ProgramFiles"));
+        assert_eq!(known_folder.offset, 0x1E);
+        assert!(link.is_advertised());
+    }
+
+    #[test]
+    fn rejects_truncated_links_without_panicking() {
+        let data = full_link();
+        let strings_end = data.len() - 0x314 * 2 - 0x1C - 4;
+
+        for length in 0..data.len() {
+            let parsed = ShellLink::parse(&data[..length]);
+            if length < strings_end {
+                assert!(parsed.is_err(), "parsed {} of {} bytes", length, data.len());
+            }
+        }
+        assert!(ShellLink::parse(b"not a shell link").is_err());
+    }
+
+    #[test]
+    fn rejects_offsets_outside_the_link() {
+        let mut overrun_id_list = header(HAS_LINK_TARGET_ID_LIST);
+        overrun_id_list.extend(0xFFFFu16.to_le_bytes());
+        assert!(ShellLink::parse(&overrun_id_list).is_err());
+
+        let mut data = header(HAS_LINK_INFO);
+        let info_start = data.len();
+        data.extend(link_info(r"C:\x\", r"C:\x\", ""));
+        // Unicode base path and suffix, then the ANSI base path of an older 0x1C-byte header
+        for (header_size, field) in [(0x24u32, 28), (0x24, 32), (0x1C, 16)] {
+            let mut bad = data.clone();
+            bad[info_start + 4..info_start + 8].copy_from_slice(&header_size.to_le_bytes());
+            bad[info_start + field..info_start + field + 4].copy_from_slice(&0xFFFF_FFF0u32.to_le_bytes());
+            assert!(ShellLink::parse(&bad).is_err(), "offset field {:#x}", field);
+        }
+
+        let mut network = data.clone();
+        network[info_start + 8..info_start + 12].copy_from_slice(&(VOLUME_ID_AND_LOCAL_BASE_PATH | COMMON_NETWORK_RELATIVE_LINK_AND_PATH_SUFFIX).to_le_bytes());
+        network[info_start + 20..info_start + 24].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(ShellLink::parse(&network).is_err());
+
+        let mut oversize_info = data.clone();
+        oversize_info[info_start..info_start + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(ShellLink::parse(&oversize_info).is_err());
+
+        let mut oversize_string = header(HAS_NAME | IS_UNICODE);
+        oversize_string.extend(0x7FFFu16.to_le_bytes());
+        assert!(ShellLink::parse(&oversize_string).is_err());
+    }
+}
@@ -0,0 +1,230 @@
+use std::path::{Path, PathBuf};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use walkdir::WalkDir;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VolumeDelta {
+    pub volume: PathBuf,
+    pub free_before: u64,
+    pub free_after: u64,
+}
+
+/// Freed space as estimated from file sizes and as measured on disk
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SpaceAccounting {
+    /// Sum of the logical sizes of removed files
+    pub estimated_bytes: u64,
+    /// Allocation released by removed files; files with other hard links release nothing
+    pub allocated_bytes: u64,
+    /// Free-space growth across the touched volumes, skewed by anything else writing meanwhile
+    pub measured_bytes: i64,
+    pub volumes: Vec<VolumeDelta>,
+}
+
+impl SpaceAccounting {
+    /// Fold in an operation that ran after this one; measurement windows never overlap
+    pub fn merge(&mut self, other: SpaceAccounting) {
+        self.estimated_bytes += other.estimated_bytes;
+        self.allocated_bytes += other.allocated_bytes;
+        self.measured_bytes += other.measured_bytes;
+        self.volumes.extend(other.volumes);
+    }
+}
+
+/// Size of a file and the allocation its removal gives back
+#[derive(Debug, Clone, Copy)]
+pub struct FileAllocation {
+    pub logical: u64,
+    pub released: u64,
+}
+
+/// Tracks removals and the free space of the volumes they happen on
+pub struct SpaceMeter {
+    volumes: Vec<(String, PathBuf, u64)>,
+    estimated_bytes: u64,
+    allocated_bytes: u64,
+}
+
+impl SpaceMeter {
+    /// Snapshot free space of every volume holding one of `paths`
+    pub fn start<P: AsRef<Path>>(paths: impl IntoIterator<Item = P>) -> Self {
+        let mut meter = Self {
+            volumes: Vec::new(),
+            estimated_bytes: 0,
+            allocated_bytes: 0,
+        };
+        for path in paths {
+            meter.watch(path.as_ref());
+        }
+        meter
+    }
+
+    /// Include the volume of `path`, if it is not tracked yet
+    pub fn watch(&mut self, path: &Path) {
+        let Some((key, root)) = volume_of(path) else { return };
+        if self.volumes.iter().any(|(existing, _, _)| *existing == key) {
+            return;
+        }
+
+        match free_space(&root) {
+            Ok(free) => self.volumes.push((key, root, free)),
+            Err(e) => warn!("Cannot read free space of {}: {}", root.display(), e),
+        }
+    }
+
+    pub fn record(&mut self, allocation: FileAllocation) {
+        self.estimated_bytes += allocation.logical;
+        self.allocated_bytes += allocation.released;
+    }
+
+    pub fn finish(self) -> SpaceAccounting {
+        let volumes: Vec<VolumeDelta> = self.volumes
+            .into_iter()
+            .filter_map(|(_, root, free_before)| match free_space(&root) {
+                Ok(free_after) => Some(VolumeDelta { volume: root, free_before, free_after }),
+                Err(e) => {
+                    warn!("Cannot read free space of {}: {}", root.display(), e);
+                    None
+                }
+            })
+            .collect();
+
+        SpaceAccounting {
+            estimated_bytes: self.estimated_bytes,
+            allocated_bytes: self.allocated_bytes,
+            measured_bytes: volumes.iter().map(|delta| delta.free_after as i64 - delta.free_before as i64).sum(),
+            volumes,
+        }
+    }
+}
+
+/// Measure every file below `path`, or `path` itself if it is a file; missing paths measure zero
+pub fn tree_allocation(path: &Path) -> FileAllocation {
+    let mut total = FileAllocation { logical: 0, released: 0 };
+    for entry in WalkDir::new(path).follow_links(false).into_iter().filter_map(|entry| entry.ok()) {
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        match file_allocation(entry.path()) {
+            Ok(allocation) => {
+                total.logical += allocation.logical;
+                total.released += allocation.released;
+            }
+            Err(e) => warn!("Cannot measure {}: {}", entry.path().display(), e),
+        }
+    }
+    total
+}
+
+/// Measure a file right before it is removed
+#[cfg(not(windows))]
+pub fn file_allocation(path: &Path) -> Result<FileAllocation> {
+    use std::os::unix::fs::MetadataExt;
+
+    let metadata = std::fs::symlink_metadata(path)?;
+    let released = if metadata.nlink() > 1 { 0 } else { metadata.blocks() * 512 };
+    Ok(FileAllocation { logical: metadata.len(), released })
+}
+
+/// Measure a file right before it is removed
+#[cfg(windows)]
+pub fn file_allocation(path: &Path) -> Result<FileAllocation> {
+    use std::os::windows::fs::OpenOptionsExt;
+    use std::os::windows::io::AsRawHandle;
+    use windows::Win32::Foundation::HANDLE;
+    use windows::Win32::Storage::FileSystem::{GetCompressedFileSizeW, GetFileInformationByHandle, BY_HANDLE_FILE_INFORMATION, INVALID_FILE_SIZE};
+
+    let metadata = std::fs::symlink_metadata(path)?;
+    // Attribute-only access works on files we may delete but not read
+    let file = std::fs::OpenOptions::new().access_mode(0).open(path)?;
+    let mut information = BY_HANDLE_FILE_INFORMATION::default();
+    unsafe { GetFileInformationByHandle(HANDLE(file.as_raw_handle() as isize), &mut information)? };
+    if information.nNumberOfLinks > 1 {
+        return Ok(FileAllocation { logical: metadata.len(), released: 0 });
+    }
+
+    // Compressed and sparse files occupy less than their logical size
+    let name = wide(path);
+    let mut high = 0u32;
+    let low = unsafe { GetCompressedFileSizeW(windows::core::PCWSTR(name.as_ptr()), Some(&mut high as *mut u32)) };
+    if low == INVALID_FILE_SIZE {
+        let error = std::io::Error::last_os_error();
+        if error.raw_os_error() != Some(0) {
+            return Err(error.into());
+        }
+    }
+    let stored = ((high as u64) << 32) | low as u64;
+
+    let cluster = volume_of(path).and_then(|(_, root)| cluster_size(&root).ok()).unwrap_or(4096);
+    Ok(FileAllocation { logical: metadata.len(), released: stored.div_ceil(cluster) * cluster })
+}
+
+/// Volume a path lives on, as a comparable key and a path that stays valid
+#[cfg(not(windows))]
+fn volume_of(path: &Path) -> Option<(String, PathBuf)> {
+    use std::os::unix::fs::MetadataExt;
+
+    let existing = path.ancestors().find(|ancestor| ancestor.exists())?;
+    let device = std::fs::metadata(existing).ok()?.dev();
+
+    // Climb to the mount point so removals below cannot invalidate it
+    let root = existing.ancestors()
+        .take_while(|ancestor| std::fs::metadata(ancestor).map(|m| m.dev() == device).unwrap_or(false))
+        .last()?;
+    Some((device.to_string(), root.to_path_buf()))
+}
+
+/// Volume a path lives on, as a comparable key and a path that stays valid
+#[cfg(windows)]
+fn volume_of(path: &Path) -> Option<(String, PathBuf)> {
+    let root = path.ancestors().last().filter(|root| !root.as_os_str().is_empty())?;
+    Some((root.to_string_lossy().to_lowercase(), root.to_path_buf()))
+}
+
+#[cfg(not(windows))]
+fn free_space(root: &Path) -> Result<u64> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(root.as_os_str().as_bytes())?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(stat.f_bfree as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(windows)]
+fn free_space(root: &Path) -> Result<u64> {
+    use windows::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+
+    let name = wide(root);
+    let mut free = 0u64;
+    unsafe { GetDiskFreeSpaceExW(windows::core::PCWSTR(name.as_ptr()), None, None, Some(&mut free as *mut u64))? };
+    Ok(free)
+}
+
+#[cfg(windows)]
+fn cluster_size(root: &Path) -> Result<u64> {
+    use windows::Win32::Storage::FileSystem::GetDiskFreeSpaceW;
+
+    let name = wide(root);
+    let (mut sectors_per_cluster, mut bytes_per_sector) = (0u32, 0u32);
+    unsafe {
+        GetDiskFreeSpaceW(
+            windows::core::PCWSTR(name.as_ptr()),
+            Some(&mut sectors_per_cluster as *mut u32),
+            Some(&mut bytes_per_sector as *mut u32),
+            None,
+            None,
+        )?
+    };
+    Ok(sectors_per_cluster as u64 * bytes_per_sector as u64)
+}
+
+#[cfg(windows)]
+fn wide(path: &Path) -> Vec<u16> {
+    use std::os::windows::ffi::OsStrExt;
+    path.as_os_str().encode_wide().chain(std::iter::once(0)).collect()
+}
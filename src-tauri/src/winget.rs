@@ -0,0 +1,511 @@
+use std::fmt;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::command_runner::{CommandLine, CommandRunner};
+use crate::inventory::{InstalledApplication, InventorySource};
+
+/// Marks a column winget cut short to fit the console
+const ELLIPSIS: char = '…';
+
+/// One row of `winget list`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WingetPackage {
+    pub name: String,
+    /// Source ID, or a local `ARP\...` / `MSIX\...` ID for packages no source knows
+    pub id: String,
+    pub version: String,
+    pub available: Option<String>,
+    pub source: Option<String>,
+}
+
+/// Source package from `winget export`, whose IDs are never truncated
+#[derive(Debug, Clone)]
+pub struct ExportedPackage {
+    pub id: String,
+    pub source: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WingetOutcome {
+    Completed,
+    /// Removed, but files are only gone after a restart
+    RebootRequired,
+}
+
+/// A failed winget run, by exit code
+#[derive(Debug, Clone)]
+pub struct WingetError {
+    pub code: u32,
+    pub reason: &'static str,
+}
+
+impl fmt::Display for WingetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (winget exit code 0x{:08X})", self.reason, self.code)
+    }
+}
+
+impl std::error::Error for WingetError {}
+
+/// Installed packages as winget sees them, source IDs completed from an export
+pub async fn list_installed(runner: &dyn CommandRunner) -> Result<(Vec<WingetPackage>, Vec<ExportedPackage>)> {
+    let output = run(runner, &["list", "--accept-source-agreements", "--disable-interactivity"]).await?;
+    let packages = parse_list(&output)?;
+
+    let export_path = std::env::temp_dir().join(format!("winget_export_{}.json", uuid::Uuid::new_v4()));
+    let export_path_arg = export_path.to_string_lossy().to_string();
+    let exported = match run(runner, &["export", "-o", &export_path_arg, "--accept-source-agreements", "--disable-interactivity"]).await {
+        Ok(_) => tokio::fs::read_to_string(&export_path).await.map_err(anyhow::Error::from).and_then(|json| parse_export(&json)),
+        Err(e) => Err(e),
+    };
+    let _ = tokio::fs::remove_file(&export_path).await;
+
+    // Without the export, only untruncated IDs are usable
+    Ok((packages, exported.unwrap_or_default()))
+}
+
+/// Remove one package by its exact ID, without prompts
+pub async fn uninstall(runner: &dyn CommandRunner, package: &WingetPackage) -> Result<WingetOutcome> {
+    let output = runner.run(&CommandLine::new("winget", uninstall_args(package))).await?;
+    let code = output.exit_code.ok_or_else(|| anyhow!("winget was terminated"))?;
+    exit_outcome(code).map_err(anyhow::Error::from)
+}
+
+/// Install one package by its exact source ID, without prompts
+pub async fn install(runner: &dyn CommandRunner, id: &str, source: Option<&str>) -> Result<WingetOutcome> {
+    let output = runner.run(&CommandLine::new("winget", install_args(id, source))).await?;
+    let code = output.exit_code.ok_or_else(|| anyhow!("winget was terminated"))?;
+    exit_outcome(code).map_err(anyhow::Error::from)
+}
+
+pub fn install_args(id: &str, source: Option<&str>) -> Vec<String> {
+    let mut args: Vec<String> = vec!["install".into(), "--id".into(), id.to_string(), "--exact".into()];
+    if let Some(source) = source {
+        args.extend(["--source".into(), source.to_string()]);
+    }
+    args.extend(["--silent", "--accept-package-agreements", "--accept-source-agreements", "--disable-interactivity"].map(String::from));
+    args
+}
+
+pub fn uninstall_args(package: &WingetPackage) -> Vec<String> {
+    let mut args: Vec<String> = vec!["uninstall".into(), "--id".into(), package.id.clone(), "--exact".into()];
+    if let Some(source) = &package.source {
+        args.extend(["--source".into(), source.clone()]);
+    }
+    args.extend(["--silent", "--accept-source-agreements", "--disable-interactivity"].map(String::from));
+    args
+}
+
+async fn run(runner: &dyn CommandRunner, args: &[&str]) -> Result<String> {
+    let output = runner.run(&CommandLine::new("winget", args.iter().copied())).await?;
+    match output.exit_code {
+        Some(0) => Ok(output.stdout),
+        Some(code) => match exit_outcome(code) {
+            Err(e) => Err(e.into()),
+            Ok(_) => Err(anyhow!("winget {} needs a restart to complete", args[0])),
+        },
+        None => Err(anyhow!("winget was terminated")),
+    }
+}
+
+/// Map a winget exit code; codes are HRESULTs in the 0x8A15xxxx range
+pub fn exit_outcome(exit_code: i32) -> std::result::Result<WingetOutcome, WingetError> {
+    let code = exit_code as u32;
+    let reason = match code {
+        0 => return Ok(WingetOutcome::Completed),
+        0x8A150109 | 0x8A15010B => return Ok(WingetOutcome::RebootRequired),
+        // An install found the package already there and nothing newer to upgrade to
+        0x8A15002B => return Ok(WingetOutcome::Completed),
+        0x8A150001 => "winget hit an internal error",
+        0x8A150002 => "winget rejected the command line",
+        0x8A150003 => "the winget command failed",
+        0x8A150010 => "no package in the sources matches the ID",
+        0x8A150014 => "no installed package matches the ID",
+        0x8A150015 => "more than one installed package matches the ID",
+        0x8A150101 => "the application is running",
+        0x8A150102 => "another installation is in progress",
+        0x8A150103 => "files of the package are in use",
+        0x8A150104 => "a dependency of the uninstaller is missing",
+        0x8A150105 => "the disk is full",
+        0x8A150106 => "there is not enough memory",
+        0x8A150107 => "a network connection is required",
+        0x8A150108 => "the uninstaller failed and asks to contact support",
+        0x8A15010A => "a restart is required before the package can be removed",
+        0x8A15010C => "the uninstallation was cancelled",
+        0x8A15010F => "the uninstallation is blocked by policy",
+        0x8A150111 => "the package is in use by another application",
+        _ => "winget failed",
+    };
+    Err(WingetError { code, reason })
+}
+
+/// Parse the tables printed by `winget list`, progress spinner and all
+pub fn parse_list(output: &str) -> Result<Vec<WingetPackage>> {
+    // Spinner frames are overwritten with carriage returns; keep what remained visible
+    let lines: Vec<&str> = output
+        .lines()
+        .map(|line| line.rsplit('\r').next().unwrap_or("").trim_start_matches('\u{feff}').trim_end())
+        .collect();
+    let is_separator = |line: &&str| line.len() >= 10 && line.chars().all(|c| c == '-');
+
+    // Packages that need explicit targeting to upgrade follow in a second table after a blank line
+    let mut packages = Vec::new();
+    let mut tables = 0;
+    let mut next = 0;
+    while let Some(offset) = lines[next..].iter().position(is_separator) {
+        let separator = next + offset;
+        let header = lines[next..separator].iter().rev().find(|line| !line.trim().is_empty())
+            .ok_or_else(|| anyhow!("winget list printed no header"))?;
+        let rows = lines[separator + 1..].iter().take_while(|line| !line.trim().is_empty()).count();
+        packages.extend(parse_table(header, &lines[separator + 1..separator + 1 + rows])?);
+        tables += 1;
+        next = separator + 1 + rows;
+    }
+
+    if tables == 0 {
+        return Err(anyhow!("winget list printed no table"));
+    }
+    Ok(packages)
+}
+
+fn parse_table(header: &str, rows: &[&str]) -> Result<Vec<WingetPackage>> {
+    let header: Vec<char> = header.chars().collect();
+    let starts: Vec<usize> = (0..header.len())
+        .filter(|&i| header[i] != ' ' && (i == 0 || header[i - 1] == ' '))
+        .collect();
+    let names: Vec<String> = starts.iter().map(|&start| column(&header, &starts, start)).collect();
+    let columns = Columns::from_header(&names)?;
+
+    let mut packages = Vec::new();
+    for line in rows {
+        let row: Vec<char> = line.chars().collect();
+        // Footer lines like "3 upgrades available." do not reach the ID column
+        if row.len() <= starts[columns.id] {
+            continue;
+        }
+
+        let field = |index: Option<usize>| index.map(|i| column(&row, &starts, starts[i])).filter(|value| !value.is_empty());
+        let (Some(name), Some(id)) = (field(Some(columns.name)), field(Some(columns.id))) else { continue };
+        packages.push(WingetPackage {
+            name,
+            id,
+            version: field(Some(columns.version)).unwrap_or_default(),
+            available: field(columns.available),
+            source: field(columns.source),
+        });
+    }
+    Ok(packages)
+}
+
+/// Text of the column starting at `start`, up to where the next one starts
+fn column(row: &[char], starts: &[usize], start: usize) -> String {
+    let end = starts.iter().find(|&&next| next > start).copied().unwrap_or(row.len()).min(row.len());
+    if start >= end {
+        return String::new();
+    }
+    row[start..end].iter().collect::<String>().trim().to_string()
+}
+
+struct Columns {
+    name: usize,
+    id: usize,
+    version: usize,
+    available: Option<usize>,
+    source: Option<usize>,
+}
+
+impl Columns {
+    /// English headers are recognized by name; localized ones by position
+    fn from_header(names: &[String]) -> Result<Self> {
+        const ENGLISH: [&str; 5] = ["Name", "Id", "Version", "Available", "Source"];
+        let find = |wanted: &str| names.iter().position(|name| name.eq_ignore_ascii_case(wanted));
+        // German keeps "Name", "ID" and "Version" but renames the rest, so every column has to be English
+        let english = names.iter().all(|name| ENGLISH.iter().any(|known| known.eq_ignore_ascii_case(name)));
+        if let (true, Some(name), Some(id), Some(version)) = (english, find("Name"), find("Id"), find("Version")) {
+            return Ok(Self { name, id, version, available: find("Available"), source: find("Source") });
+        }
+
+        match names.len() {
+            3 => Ok(Self { name: 0, id: 1, version: 2, available: None, source: None }),
+            4 => Ok(Self { name: 0, id: 1, version: 2, available: None, source: Some(3) }),
+            5 => Ok(Self { name: 0, id: 1, version: 2, available: Some(3), source: Some(4) }),
+            count => Err(anyhow!("winget list printed an unexpected table with {} columns", count)),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ExportFile {
+    #[serde(default)]
+    sources: Vec<ExportSource>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ExportSource {
+    source_details: ExportSourceDetails,
+    #[serde(default)]
+    packages: Vec<ExportPackage>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ExportSourceDetails {
+    name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ExportPackage {
+    package_identifier: String,
+}
+
+/// Parse the JSON written by `winget export`
+pub fn parse_export(json: &str) -> Result<Vec<ExportedPackage>> {
+    let file: ExportFile = serde_json::from_str(json.trim_start_matches('\u{feff}'))
+        .map_err(|e| anyhow!("Unreadable winget export: {}", e))?;
+    Ok(file.sources.into_iter().flat_map(|source| {
+        let name = source.source_details.name;
+        source.packages.into_iter().map(move |package| ExportedPackage {
+            id: package.package_identifier,
+            source: name.clone(),
+        })
+    }).collect())
+}
+
+/// The winget package behind an inventory entry, with a complete ID
+pub fn find_package(packages: &[WingetPackage], exported: &[ExportedPackage], app: &InstalledApplication) -> Option<WingetPackage> {
+    // Packages no source knows carry IDs derived from the registration itself
+    for local_id in app.sources.iter().map(local_id) {
+        if let Some(package) = packages.iter().find(|package| package.source.is_none() && text_matches(&package.id, &local_id)) {
+            return Some(WingetPackage { id: local_id, ..package.clone() });
+        }
+    }
+
+    packages.iter()
+        .filter(|package| package.source.is_some())
+        .filter(|package| text_matches(&package.name, &app.display_name))
+        .filter(|package| package.version.is_empty() || app.version.is_empty() || text_matches(&package.version, &app.version))
+        .find_map(|package| complete_id(package, exported))
+}
+
+/// `ARP\<scope>\<arch>\<key>` or `MSIX\<package full name>`
+fn local_id(source: &InventorySource) -> String {
+    match source {
+        InventorySource::UninstallKey(key) => {
+            let scope = if key.starts_with("HKEY_CURRENT_USER") { "User" } else { "Machine" };
+            let architecture = if key.to_lowercase().contains(r"\wow6432node\") { "X86" } else { "X64" };
+            let name = key.rsplit('\\').next().unwrap_or(key);
+            format!(r"ARP\{}\{}\{}", scope, architecture, name)
+        }
+        InventorySource::Appx { package_full_name } => format!(r"MSIX\{}", package_full_name),
+    }
+}
+
+fn complete_id(package: &WingetPackage, exported: &[ExportedPackage]) -> Option<WingetPackage> {
+    if !package.id.ends_with(ELLIPSIS) {
+        return Some(package.clone());
+    }
+
+    let mut candidates = exported.iter()
+        .filter(|export| Some(&export.source) == package.source.as_ref() && text_matches(&package.id, &export.id));
+    match (candidates.next(), candidates.next()) {
+        (Some(export), None) => Some(WingetPackage { id: export.id.clone(), ..package.clone() }),
+        _ => None,
+    }
+}
+
+/// Case-insensitive equality, or prefix equality when winget truncated the listed text
+fn text_matches(listed: &str, full: &str) -> bool {
+    let listed = listed.to_lowercase();
+    let full = full.to_lowercase();
+    match listed.strip_suffix(ELLIPSIS) {
+        Some(prefix) => full.starts_with(prefix),
+        None => listed == full,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use crate::bloatware_matching::AppIdentity;
+    use crate::inventory::InstallScope;
+
+    const LIST_FIXTURE: &str = include_str!("../fixtures/winget/list_en.txt");
+    const LOCALIZED_LIST_FIXTURE: &str = include_str!("../fixtures/winget/list_de.txt");
+    const EXPORT_FIXTURE: &str = include_str!("../fixtures/winget/export.json");
+
+    fn app(display_name: &str, version: &str, source: InventorySource) -> InstalledApplication {
+        InstalledApplication {
+            display_name: display_name.to_string(),
+            publisher: String::new(),
+            version: version.to_string(),
+            install_location: None::<PathBuf>,
+            size_bytes: None,
+            uninstall_command: None,
+            quiet_uninstall_command: None,
+            scope: InstallScope::Machine,
+            identity: AppIdentity::default(),
+            is_framework: false,
+            dependencies: Vec::new(),
+            removable: true,
+            sources: vec![source],
+        }
+    }
+
+    fn uninstall_key(path: &str) -> InventorySource {
+        InventorySource::UninstallKey(format!(r"HKEY_LOCAL_MACHINE\{}", path))
+    }
+
+    #[test]
+    fn parses_the_list_behind_the_spinner() {
+        let packages = parse_list(LIST_FIXTURE).unwrap();
+
+        let ids: Vec<&str> = packages.iter().map(|package| package.id.as_str()).collect();
+        assert_eq!(ids, [
+            "7zip.7zip",
+            "Microsoft.VisualStudioCode",
+            "Dell.SupportAssistForBusinessP…",
+            "Dell.SupportAssistForBusinessP…",
+            r"ARP\Machine\X86\McAfee LiveSafe",
+            "MSIX\\Microsoft.BingWeather_4.53…",
+            "Spotify.Spotify",
+        ]);
+        assert_eq!(packages[0].name, "7-Zip 23.01 (x64)");
+        assert_eq!(packages[0].available, None);
+        assert_eq!(packages[0].source.as_deref(), Some("winget"));
+        assert_eq!(packages[1].available.as_deref(), Some("1.94.2"));
+        assert_eq!(packages[3].name, "Dell SupportAssist OS Recover…");
+        assert_eq!(packages[4].version, "16.0 R155");
+        assert_eq!(packages[4].source, None);
+        assert_eq!(packages[5].version, "4.53.52892.0");
+    }
+
+    #[test]
+    fn skips_footer_lines() {
+        let packages = parse_list(LIST_FIXTURE).unwrap();
+
+        assert!(packages.iter().all(|package| !package.name.contains("upgrades available")));
+    }
+
+    #[test]
+    fn reads_the_table_after_a_blank_line() {
+        let packages = parse_list(LIST_FIXTURE).unwrap();
+
+        let spotify = packages.iter().find(|package| package.id == "Spotify.Spotify").unwrap();
+        assert_eq!(spotify.version, "1.2.45.454");
+        assert_eq!(spotify.available.as_deref(), Some("1.2.47.364"));
+        assert_eq!(spotify.source.as_deref(), Some("winget"));
+    }
+
+    #[test]
+    fn reads_localized_headers_by_position() {
+        let packages = parse_list(LOCALIZED_LIST_FIXTURE).unwrap();
+
+        assert_eq!(packages.len(), 3);
+        assert_eq!(packages[0].source.as_deref(), Some("winget"));
+        assert_eq!(packages[1].available.as_deref(), Some("1.94.2"));
+        assert_eq!(packages[2].id, r"ARP\Machine\X86\McAfee LiveSafe");
+        assert_eq!(packages[2].source, None);
+    }
+
+    #[test]
+    fn rejects_output_without_a_table() {
+        assert!(parse_list("").is_err());
+        assert!(parse_list("\r   - \r   \\ \rNo installed package found matching input criteria.\r\n").is_err());
+        assert!(parse_list("A  B\r\n----------\r\nx  y\r\n").is_err());
+    }
+
+    #[test]
+    fn parses_the_export_of_every_source() {
+        let exported = parse_export(&format!("\u{feff}{}", EXPORT_FIXTURE)).unwrap();
+
+        assert_eq!(exported.len(), 6);
+        assert!(exported.iter().any(|package| package.id == "Dell.SupportAssistForBusinessPCs.Remediation" && package.source == "winget"));
+        assert!(exported.iter().any(|package| package.id == "9NCBCSZSJRSB" && package.source == "msstore"));
+        assert!(parse_export("{\"Sources\": [{\"Packages\": []}]}").is_err());
+        assert!(parse_export("{}").unwrap().is_empty());
+    }
+
+    #[test]
+    fn finds_source_packages_by_name_and_version() {
+        let packages = parse_list(LIST_FIXTURE).unwrap();
+        let exported = parse_export(EXPORT_FIXTURE).unwrap();
+
+        let code = app("Microsoft Visual Studio Code", "1.93.1", uninstall_key(r"SOFTWARE\Microsoft\Windows\CurrentVersion\Uninstall\{EA457B21-F73E-494C-ACAB-524FDE069978}_is1"));
+        let found = find_package(&packages, &exported, &code).unwrap();
+        assert_eq!(found.id, "Microsoft.VisualStudioCode");
+        assert_eq!(found.source.as_deref(), Some("winget"));
+
+        let older = app("7-Zip 23.01 (x64)", "22.01", uninstall_key(r"SOFTWARE\Microsoft\Windows\CurrentVersion\Uninstall\7-Zip"));
+        assert!(find_package(&packages, &exported, &older).is_none());
+    }
+
+    #[test]
+    fn completes_truncated_ids_from_the_export_only_when_unambiguous() {
+        let packages = parse_list(LIST_FIXTURE).unwrap();
+        let exported = parse_export(EXPORT_FIXTURE).unwrap();
+        let remediation = app("Dell SupportAssist Remediation", "5.5.9.18", uninstall_key(r"SOFTWARE\Microsoft\Windows\CurrentVersion\Uninstall\{D5F3F3C0-1B9E-4D3F-9E2B-4F4F8E0E6B11}"));
+
+        // Both Dell packages share the visible prefix
+        assert!(find_package(&packages, &exported, &remediation).is_none());
+
+        let unambiguous: Vec<ExportedPackage> = exported.into_iter()
+            .filter(|package| !package.id.ends_with("OSRecovery"))
+            .collect();
+        let found = find_package(&packages, &unambiguous, &remediation).unwrap();
+        assert_eq!(found.id, "Dell.SupportAssistForBusinessPCs.Remediation");
+
+        // Without an export a truncated ID is never guessed
+        assert!(find_package(&packages, &[], &remediation).is_none());
+    }
+
+    #[test]
+    fn finds_local_packages_by_their_registration() {
+        let packages = parse_list(LIST_FIXTURE).unwrap();
+
+        let mcafee = app("McAfee LiveSafe", "16.0 R155", uninstall_key(r"SOFTWARE\WOW6432Node\Microsoft\Windows\CurrentVersion\Uninstall\McAfee LiveSafe"));
+        let found = find_package(&packages, &[], &mcafee).unwrap();
+        assert_eq!(found.id, r"ARP\Machine\X86\McAfee LiveSafe");
+        assert_eq!(found.source, None);
+
+        let weather = app("Microsoft.BingWeather", "4.53.52892.0", InventorySource::Appx {
+            package_full_name: "Microsoft.BingWeather_4.53.52892.0_x64__8wekyb3d8bbwe".to_string(),
+        });
+        let found = find_package(&packages, &[], &weather).unwrap();
+        assert_eq!(found.id, r"MSIX\Microsoft.BingWeather_4.53.52892.0_x64__8wekyb3d8bbwe");
+    }
+
+    #[test]
+    fn maps_exit_codes() {
+        assert_eq!(exit_outcome(0).unwrap(), WingetOutcome::Completed);
+        assert_eq!(exit_outcome(0x8A150109u32 as i32).unwrap(), WingetOutcome::RebootRequired);
+        assert_eq!(exit_outcome(0x8A15010Bu32 as i32).unwrap(), WingetOutcome::RebootRequired);
+        assert_eq!(exit_outcome(0x8A15002Bu32 as i32).unwrap(), WingetOutcome::Completed);
+
+        let error = exit_outcome(0x8A150010u32 as i32).unwrap_err();
+        assert_eq!(error.code, 0x8A150010);
+        assert_eq!(error.to_string(), "no package in the sources matches the ID (winget exit code 0x8A150010)");
+        assert_eq!(exit_outcome(0x8A150101u32 as i32).unwrap_err().reason, "the application is running");
+        assert_eq!(exit_outcome(1).unwrap_err().reason, "winget failed");
+    }
+
+    #[test]
+    fn passes_exact_ids_and_sources() {
+        let package = WingetPackage {
+            name: "Spotify".to_string(),
+            id: "Spotify.Spotify".to_string(),
+            version: "1.2.45.454".to_string(),
+            available: None,
+            source: Some("winget".to_string()),
+        };
+
+        let args = uninstall_args(&package);
+        assert_eq!(&args[..6], ["uninstall", "--id", "Spotify.Spotify", "--exact", "--source", "winget"]);
+        assert!(args.contains(&"--silent".to_string()));
+        assert_eq!(&install_args("9NCBCSZSJRSB", None)[..4], ["install", "--id", "9NCBCSZSJRSB", "--exact"]);
+    }
+}